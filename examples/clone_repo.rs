@@ -1,9 +1,9 @@
-/// Example: Clone a GitHub repository and ingest posts
-/// 
-/// Usage:
-/// ```
-/// cargo run --example clone_repo -- https://github.com/username/blog-posts
-/// ```
+//! Example: Clone a GitHub repository and ingest posts
+//! 
+//! Usage:
+//! ```
+//! cargo run --example clone_repo -- https://github.com/username/blog-posts
+//! ```
 
 use personal::db::{Database, InMemDatabase};
 use personal::repo_utils::clone_and_ingest_repository;
@@ -25,7 +25,7 @@ async fn main() {
     println!();
     
     // Clone repository and get posts
-    let posts = match clone_and_ingest_repository(repo_url).await {
+    let posts = match clone_and_ingest_repository(repo_url, std::path::Path::new("tmp/resources")).await {
         Ok(posts) => posts,
         Err(e) => {
             eprintln!("Error: Failed to clone and ingest repository: {}", e);
@@ -0,0 +1,7 @@
+//! Print the syntax highlighting stylesheet bundled as `static/highlight.css`
+//!
+//! Usage: cargo run --example highlight_css > static/highlight.css
+
+fn main() {
+    print!("{}", personal::highlight::theme_css());
+}
@@ -1,7 +1,9 @@
 use chrono::Local;
 use personal::{
+    config,
     data::Post,
     repo_utils::{clone_and_ingest_repository, load_from_local_path},
+    view_history::SourceKind,
     views::ViewCounterStore,
 };
 use std::{
@@ -15,43 +17,52 @@ use std::{
     time::Duration,
 };
 
-const DEFAULT_REMOTE_REPO: &str = "https://github.com/softwarecowboy/blog";
-
 struct Config {
     repo_source: String,
     views_file: PathBuf,
+    resources_dir: PathBuf,
     email_to: String,
     email_from: String,
     email_subject_prefix: String,
     sendmail_bin: String,
     interval_seconds: Option<u64>,
+    /// Days covered by the "this period" columns
+    period_days: u32,
     include_zero_views: bool,
     max_posts: usize,
 }
 
 impl Config {
     fn from_env_and_args() -> Result<Self, Box<dyn Error>> {
+        // The report reads the same content source and views file as the server
+        let settings = config::Config::load()?;
         let args: Vec<String> = env::args().collect();
         let repo_source = args
             .get(1)
             .cloned()
             .or_else(|| env::var("REPORT_REPO_SOURCE").ok())
-            .unwrap_or_else(|| DEFAULT_REMOTE_REPO.to_string());
-
-        let views_file = env::var("VIEW_COUNTS_FILE")
-            .map(PathBuf::from)
-            .unwrap_or_else(|_| PathBuf::from("/opt/personal/runtime/views.tsv"));
+            .unwrap_or_else(|| settings.content.source.clone());
+        let views_file = settings.views_file();
+        let resources_dir = settings.resources_dir();
 
         let email_to = env::var("REPORT_EMAIL_TO")?;
         let email_from = env::var("REPORT_EMAIL_FROM")?;
         let email_subject_prefix = env::var("REPORT_EMAIL_SUBJECT_PREFIX")
-            .unwrap_or_else(|_| "Personal blog views report".to_string());
+            .unwrap_or_else(|_| format!("{} views report", settings.site.title));
         let sendmail_bin =
             env::var("REPORT_SENDMAIL_BIN").unwrap_or_else(|_| "/usr/sbin/sendmail".to_string());
         let interval_seconds = env::var("REPORT_INTERVAL_SECONDS")
             .ok()
             .map(|value| value.parse::<u64>())
             .transpose()?;
+        // Reports sent on an interval cover that interval; one-off reports cover a week
+        let period_days = match env::var("REPORT_PERIOD_DAYS") {
+            Ok(value) => value.parse::<u32>()?,
+            Err(_) => interval_seconds
+                .map(|seconds| seconds.div_ceil(86_400) as u32)
+                .unwrap_or(7),
+        }
+        .max(1);
         let include_zero_views = env::var("REPORT_INCLUDE_ZERO_VIEWS")
             .ok()
             .map(|value| matches!(value.as_str(), "1" | "true" | "TRUE" | "yes" | "YES"))
@@ -65,11 +76,13 @@ impl Config {
         Ok(Self {
             repo_source,
             views_file,
+            resources_dir,
             email_to,
             email_from,
             email_subject_prefix,
             sendmail_bin,
             interval_seconds,
+            period_days,
             include_zero_views,
             max_posts,
         })
@@ -93,7 +106,7 @@ async fn main() -> Result<(), Box<dyn Error>> {
 }
 
 async fn run_once(config: &Config) -> Result<(), Box<dyn Error>> {
-    let posts = sync_posts(&config.repo_source).await?;
+    let posts = sync_posts(&config.repo_source, &config.resources_dir).await?;
     let views = ViewCounterStore::load(config.views_file.clone())?;
     let report = build_report(config, &posts, &views);
     send_email(config, &report)?;
@@ -106,11 +119,11 @@ async fn run_once(config: &Config) -> Result<(), Box<dyn Error>> {
     Ok(())
 }
 
-async fn sync_posts(repo_source: &str) -> Result<Vec<Post>, Box<dyn Error>> {
+async fn sync_posts(repo_source: &str, resources_dir: &Path) -> Result<Vec<Post>, Box<dyn Error>> {
     if Path::new(repo_source).exists() {
-        Ok(load_from_local_path(repo_source).await?)
+        Ok(load_from_local_path(repo_source, resources_dir).await?)
     } else {
-        Ok(clone_and_ingest_repository(repo_source).await?)
+        Ok(clone_and_ingest_repository(repo_source, resources_dir).await?)
     }
 }
 
@@ -132,6 +145,12 @@ fn build_report(config: &Config, posts: &[Post], views: &ViewCounterStore) -> St
     }
 
     let total_views: u64 = rows.iter().map(|(_, count)| *count).sum();
+    let total_requests: u64 = views.raw_counts().values().sum();
+    let today = views.today();
+    let period_start = today - chrono::Duration::days(config.period_days as i64 - 1);
+    let period = views.history().range(period_start, today);
+    let period_views = |slug: &str| period.get(slug).map_or(0, |count| count.views);
+    let total_period_views: u64 = period.values().map(|count| count.views).sum();
     let shown_rows = rows.into_iter().take(config.max_posts).collect::<Vec<_>>();
 
     let mut report = String::new();
@@ -144,7 +163,45 @@ fn build_report(config: &Config, posts: &[Post], views: &ViewCounterStore) -> St
     report.push_str(&format!("Repository source: {}\n", config.repo_source));
     report.push_str(&format!("Views file: {}\n", config.views_file.display()));
     report.push_str(&format!("Posts loaded: {}\n", posts.len()));
-    report.push_str(&format!("Total counted views: {}\n\n", total_views));
+    report.push_str(&format!("Total counted views: {}\n", total_views));
+    report.push_str(&format!(
+        "Total requests before filtering: {}\n",
+        total_requests
+    ));
+    report.push_str(&format!(
+        "Views this period ({} to {}): {}\n\n",
+        period_start, today, total_period_views
+    ));
+
+    let movers = views.history().movers(today, config.period_days, 5);
+    if !movers.is_empty() {
+        report.push_str("Top movers\n");
+        report.push_str("----------\n");
+        for mover in &movers {
+            report.push_str(&format!(
+                "{:+} {} ({} this period, {} the period before)\n",
+                mover.change, mover.slug, mover.current, mover.previous
+            ));
+        }
+        report.push('\n');
+    }
+
+    let sources = views.history().sources(period_start, today);
+    if !sources.is_empty() {
+        report.push_str("Top referrers and campaigns this period\n");
+        report.push_str("---------------------------------------\n");
+        for kind in SourceKind::ALL {
+            for source in sources.list(kind).iter().take(5) {
+                report.push_str(&format!(
+                    "{}: {} ({} views)\n",
+                    kind.as_str(),
+                    source.value,
+                    source.views
+                ));
+            }
+        }
+        report.push('\n');
+    }
 
     if shown_rows.is_empty() {
         report.push_str("No view data recorded yet.\n");
@@ -157,21 +214,25 @@ fn build_report(config: &Config, posts: &[Post], views: &ViewCounterStore) -> St
     for (index, (slug, count)) in shown_rows.iter().enumerate() {
         if let Some(post) = posts_by_slug.get(slug.as_str()) {
             report.push_str(&format!(
-                "{}. {}\n   slug: {}\n   date: {}\n   views: {}\n   description: {}\n\n",
+                "{}. {}\n   slug: {}\n   date: {}\n   views: {} ({} requests)\n   views this period: {}\n   description: {}\n\n",
                 index + 1,
                 post.markdown.title,
                 slug,
                 post.markdown.date,
                 count,
+                views.get_raw(slug),
+                period_views(slug),
                 post.markdown.description
             ));
         } else {
             report.push_str(&format!(
-                "{}. {}\n   slug: {}\n   views: {}\n   note: post no longer exists in synced repository\n\n",
+                "{}. {}\n   slug: {}\n   views: {} ({} requests)\n   views this period: {}\n   note: post no longer exists in synced repository\n\n",
                 index + 1,
                 slug,
                 slug,
-                count
+                count,
+                views.get_raw(slug),
+                period_views(slug)
             ));
         }
     }
@@ -0,0 +1,315 @@
+/// Command-line interface of the `personal` binary and the commands that run once and exit
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::sync::Arc;
+
+use chrono::{Local, NaiveDate};
+use clap::{Args, Parser, Subcommand};
+use tempfile::TempDir;
+
+use crate::{
+    config::Config,
+    data::{slugify, Markdown, Series},
+    db::{Database, InMemDatabase},
+    diagnostics::log_diagnostics,
+    error::ApplicationError,
+    export::Export,
+    http::assets::StaticAssets,
+    repo_utils::{ingest_repository, Ingestion, Repository},
+    resources::RESOURCES_URL,
+    snapshot::Snapshot,
+    views::ViewCounterStore,
+};
+
+/// Exit status when `check` finds posts that would be skipped
+pub const EXIT_INVALID_CONTENT: u8 = 1;
+/// Exit status when a command can't run at all; also what clap uses for usage errors
+pub const EXIT_ERROR: u8 = 2;
+
+/// Serve, check and export the blog
+///
+/// Exits with 0 on success, 1 when `check` finds invalid posts and 2 on any other error.
+#[derive(Debug, Parser)]
+#[command(name = "personal", version)]
+pub struct Cli {
+    /// TOML configuration file read instead of `CONFIG_FILE` or `personal.toml`
+    #[arg(long, global = true, value_name = "FILE")]
+    pub config: Option<PathBuf>,
+
+    /// Defaults to `serve`
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Serve the blog over HTTP
+    Serve(ServeArgs),
+    /// Parse every post of a content repository and report the ones that would be skipped
+    Check(CheckArgs),
+    /// Render the blog into a directory of static files
+    Export(ExportArgs),
+    /// Write a new post with its frontmatter filled in
+    NewPost(NewPostArgs),
+}
+
+impl Default for Command {
+    fn default() -> Self {
+        Command::Serve(ServeArgs::default())
+    }
+}
+
+#[derive(Debug, Default, Args)]
+pub struct ServeArgs {
+    /// Address and port to listen on
+    #[arg(long, value_name = "ADDR")]
+    pub bind: Option<SocketAddr>,
+    /// Local content directory, watched for edits, or git URL of the content repository
+    #[arg(long)]
+    pub source: Option<String>,
+    /// Branch of the content repository to serve
+    #[arg(long)]
+    pub branch: Option<String>,
+}
+
+impl ServeArgs {
+    /// Override the configuration with the flags that were given
+    pub fn apply(&self, config: &mut Config) {
+        if let Some(bind) = self.bind {
+            config.server.bind = bind;
+        }
+        if let Some(source) = &self.source {
+            config.content.source = source.clone();
+        }
+        if let Some(branch) = &self.branch {
+            config.content.branch = Some(branch.clone());
+        }
+    }
+}
+
+#[derive(Debug, Default, Args)]
+pub struct CheckArgs {
+    /// Print a JSON report instead of one line per skipped post
+    #[arg(long)]
+    pub json: bool,
+    /// Local path or git URL; defaults to the configured source
+    pub source: Option<String>,
+    /// Branch to clone from a git URL; defaults to the configured branch
+    #[arg(long)]
+    pub branch: Option<String>,
+}
+
+#[derive(Debug, Args)]
+pub struct ExportArgs {
+    /// Directory to write the site to
+    #[arg(long, short, default_value = "dist")]
+    pub out: PathBuf,
+    /// Local path or git URL; defaults to the configured source
+    pub source: Option<String>,
+    /// Branch to clone from a git URL; defaults to the configured branch
+    #[arg(long)]
+    pub branch: Option<String>,
+    /// Write assets under their plain names only and link pages to those
+    #[arg(long)]
+    pub no_fingerprint: bool,
+}
+
+#[derive(Debug, Args)]
+pub struct NewPostArgs {
+    /// Title of the post
+    pub title: String,
+    /// Defaults to the title in lowercase with dashes between words
+    #[arg(long)]
+    pub slug: Option<String>,
+    /// Shown in post listings, at most 200 characters
+    #[arg(long, default_value = "")]
+    pub description: String,
+    /// Repeat the flag or separate tags with commas
+    #[arg(long = "tag", value_name = "TAG", value_delimiter = ',')]
+    pub tags: Vec<String>,
+    /// Title of the series the post belongs to
+    #[arg(long)]
+    pub series: Option<String>,
+    /// Episode number within the series
+    #[arg(long, requires = "series", default_value_t = 1)]
+    pub episode: u8,
+    /// Publication date as YYYY-MM-DD; defaults to today
+    #[arg(long)]
+    pub date: Option<NaiveDate>,
+    /// Keep the post unlisted until the flag is removed from its frontmatter
+    #[arg(long)]
+    pub draft: bool,
+    /// Content repository to write the post into
+    #[arg(long, default_value = ".")]
+    pub repo: PathBuf,
+}
+
+/// Report the posts of a content repository that would be skipped
+///
+/// # Returns
+/// [`EXIT_INVALID_CONTENT`] when at least one post fails, so it can gate content pushes in CI
+pub async fn check(args: &CheckArgs, config: &Config) -> ExitCode {
+    let source = args.source.as_deref().unwrap_or(&config.content.source);
+    let branch = args.branch.as_deref().or(config.content.branch.as_deref());
+    let ingestion = match ingest_source(source, branch).await {
+        Ok(ingestion) => ingestion,
+        Err(err) => {
+            eprintln!("Failed to check {source}: {err}");
+            return ExitCode::from(EXIT_ERROR);
+        }
+    };
+
+    if args.json {
+        let report = serde_json::json!({
+            "source": source,
+            "posts": ingestion.posts.len(),
+            "diagnostics": ingestion.diagnostics,
+        });
+        println!("{report:#}");
+    } else {
+        for diagnostic in &ingestion.diagnostics {
+            println!("{diagnostic}");
+        }
+        println!(
+            "{} posts ok, {} skipped in {}",
+            ingestion.posts.len(),
+            ingestion.diagnostics.len(),
+            source
+        );
+    }
+
+    if ingestion.diagnostics.is_empty() {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::from(EXIT_INVALID_CONTENT)
+    }
+}
+
+/// Render the configured site from a content repository into a directory
+///
+/// Posts that fail to parse are reported and left out, as the server would.
+pub async fn export(args: &ExportArgs, config: &Config) -> ExitCode {
+    let source = args.source.as_deref().unwrap_or(&config.content.source);
+    let branch = args.branch.as_deref().or(config.content.branch.as_deref());
+    let loaded = async {
+        let (root, clone_dir) = checkout(source, branch)?;
+        let repo = Repository::try_from(root.as_path())?;
+        let ingestion = ingest_repository(&repo).await?;
+        Ok::<_, ApplicationError>((repo, ingestion, clone_dir))
+    };
+    // The clone stays alive until its resources are copied
+    let (repo, ingestion, _clone_dir) = match loaded.await {
+        Ok(loaded) => loaded,
+        Err(err) => {
+            eprintln!("Failed to load {source}: {err}");
+            return ExitCode::from(EXIT_ERROR);
+        }
+    };
+    log_diagnostics(&ingestion.diagnostics);
+
+    let mut db = InMemDatabase::new();
+    for post in ingestion.posts {
+        if let Err(err) = db.insert_parsed_to_database(post) {
+            eprintln!("Failed to load {source}: {err}");
+            return ExitCode::from(EXIT_ERROR);
+        }
+    }
+    // Pages show the view counts the server has recorded so far, if any
+    let views = match ViewCounterStore::load(config.views_file()) {
+        Ok(views) => views.counts().clone(),
+        Err(err) => {
+            eprintln!("Exporting without view counts: {err}");
+            Default::default()
+        }
+    };
+
+    let export = Export::new(&args.out)
+        .with_static_assets(StaticAssets::new(config.server.static_dir.clone()))
+        .with_assets(&repo.resources, RESOURCES_URL)
+        .with_fingerprinting(!args.no_fingerprint);
+    let site = Arc::new(config.site.clone());
+    match export.run(&Snapshot::new(db), site, &views) {
+        Ok(summary) => {
+            println!(
+                "Exported {} pages and {} assets to {}",
+                summary.pages,
+                summary.assets,
+                args.out.display()
+            );
+            ExitCode::SUCCESS
+        }
+        Err(err) => {
+            eprintln!("Failed to export to {}: {err}", args.out.display());
+            ExitCode::from(EXIT_ERROR)
+        }
+    }
+}
+
+/// Write a new post file with its frontmatter and an empty body
+///
+/// # Returns
+/// Path of the new file, `posts/<slug>.md` inside the repository
+///
+/// # Effects
+/// - Refuses to replace an existing file
+pub fn new_post(args: &NewPostArgs) -> Result<PathBuf, ApplicationError> {
+    let slug = args.slug.clone().unwrap_or_else(|| slugify(&args.title));
+    let posts_dir = args.repo.join("posts");
+    let path = posts_dir.join(format!("{slug}.md"));
+    let error = |reason: &str| ApplicationError::PostCreationError {
+        path: path.clone(),
+        reason: reason.to_string(),
+    };
+    if slug.is_empty() || slug.contains(['/', '\\']) {
+        return Err(error("the slug must be a non-empty file name"));
+    }
+    if !posts_dir.is_dir() {
+        return Err(error("the repository has no posts directory"));
+    }
+    if path.exists() {
+        return Err(error("a post with this slug already exists"));
+    }
+
+    let markdown = Markdown {
+        title: args.title.clone(),
+        description: args.description.clone(),
+        slug,
+        tags: args.tags.clone(),
+        date: args.date.unwrap_or_else(|| Local::now().date_naive()),
+        series: args.series.clone().map(|title| Series {
+            title,
+            ep: args.episode,
+        }),
+        draft: args.draft,
+    };
+    let frontmatter = serde_yaml::to_string(&markdown)?;
+    std::fs::write(&path, format!("---\n{frontmatter}---\n\n"))?;
+    Ok(path)
+}
+
+/// Parse every post of a local directory or a fresh clone of a git URL
+async fn ingest_source(source: &str, branch: Option<&str>) -> Result<Ingestion, ApplicationError> {
+    // Keep the clone alive until parsing is done
+    let (root, _clone_dir) = checkout(source, branch)?;
+    let repo = Repository::try_from(root.as_path())?;
+    ingest_repository(&repo).await
+}
+
+/// A local directory as it is, or a clone of `branch` of a git URL, the remote's default
+/// branch when it is `None`
+fn checkout(
+    source: &str,
+    branch: Option<&str>,
+) -> Result<(PathBuf, Option<TempDir>), ApplicationError> {
+    if Path::new(source).exists() {
+        return Ok((PathBuf::from(source), None));
+    }
+    let temp_dir = tempfile::tempdir()?;
+    let mut builder = git2::build::RepoBuilder::new();
+    if let Some(branch) = branch {
+        builder.branch(branch);
+    }
+    builder.clone(source, temp_dir.path())?;
+    Ok((temp_dir.path().to_path_buf(), Some(temp_dir)))
+}
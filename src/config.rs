@@ -0,0 +1,492 @@
+/// Typed runtime configuration read from a TOML file, with environment variable overrides
+use std::net::{IpAddr, SocketAddr};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use serde::Deserialize;
+
+use crate::error::ApplicationError;
+use crate::repo_utils::DEFAULT_RESOURCES_DIR;
+use crate::view_history::Retention;
+
+/// Read from the working directory when `CONFIG_FILE` doesn't name another file
+pub const DEFAULT_CONFIG_FILE: &str = "personal.toml";
+pub const DEFAULT_REMOTE_REPO: &str = "https://github.com/softwarecowboy/blog";
+
+/// Everything a deployment can change without rebuilding; unset keys keep their defaults
+#[derive(Debug, Clone, Default, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub server: ServerConfig,
+    pub site: SiteConfig,
+    pub content: ContentConfig,
+    pub reload: ReloadConfig,
+    pub views: ViewsConfig,
+    pub admin: AdminConfig,
+}
+
+#[derive(Debug, Clone, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct ServerConfig {
+    /// Address and port to listen on
+    pub bind: SocketAddr,
+    /// Files served under `/static` in place of the copies built into the binary
+    pub static_dir: Option<PathBuf>,
+    /// Memory kept for rendered pages, in megabytes; 0 renders every request
+    pub page_cache_mb: usize,
+    /// Reverse proxies whose `X-Forwarded-For` and `X-Real-IP` headers name the client
+    pub trusted_proxies: Vec<IpAddr>,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        Self {
+            bind: SocketAddr::from(([127, 0, 0, 1], 3000)),
+            static_dir: None,
+            page_cache_mb: 32,
+            trusted_proxies: Vec::new(),
+        }
+    }
+}
+
+/// How the site presents itself in pages, feeds and the sitemap
+#[derive(Debug, Clone, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct SiteConfig {
+    /// Public URL the site is served under, without a trailing slash
+    pub base_url: String,
+    pub title: String,
+    pub description: String,
+    pub author: String,
+    /// Handle credited in Twitter cards, `@` included
+    pub twitter: Option<String>,
+    /// Profiles of the author listed in the structured data of every page
+    pub profiles: Vec<String>,
+}
+
+impl Default for SiteConfig {
+    fn default() -> Self {
+        Self {
+            base_url: "https://rwd.works".to_string(),
+            title: "rwd.works".to_string(),
+            description:
+                "Rust, data, and software design - opinionated, benchmarked, and occasionally correct."
+                    .to_string(),
+            author: "Rafał Waldemar Draws".to_string(),
+            twitter: Some("@software_cowboy".to_string()),
+            profiles: vec![
+                "https://github.com/softwarecowboy".to_string(),
+                "https://linkedin.com/in/rafaldraws".to_string(),
+            ],
+        }
+    }
+}
+
+impl SiteConfig {
+    /// Absolute URL of `path`, which starts with a slash
+    pub fn url(&self, path: &str) -> String {
+        format!("{}{path}", self.base_url)
+    }
+}
+
+/// Where posts come from
+#[derive(Debug, Clone, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct ContentConfig {
+    /// Git URL to clone and keep in sync, or a local directory to watch
+    pub source: String,
+    /// Branch to track instead of the remote's default branch
+    pub branch: Option<String>,
+    /// Where the remote repository is mirrored
+    pub mirror_dir: PathBuf,
+    /// SQLite copy of remote content, served on start until the first sync
+    pub database_file: PathBuf,
+    /// Where the repository `resources/` are published for `/static/misc/`; defaults to
+    /// `tmp/resources` for local content and `/opt/personal/runtime/resources` otherwise
+    pub resources_dir: Option<PathBuf>,
+}
+
+impl Default for ContentConfig {
+    fn default() -> Self {
+        Self {
+            source: DEFAULT_REMOTE_REPO.to_string(),
+            branch: None,
+            mirror_dir: PathBuf::from("/opt/personal/runtime/content"),
+            database_file: PathBuf::from("/opt/personal/runtime/content.sqlite"),
+            resources_dir: None,
+        }
+    }
+}
+
+impl ContentConfig {
+    /// The directory to serve when the source is a path rather than a git URL
+    pub fn local_path(&self) -> Option<&Path> {
+        let remote = self.source.contains("://") || self.source.starts_with("git@");
+        (!remote).then(|| Path::new(&self.source))
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct ReloadConfig {
+    /// Quiet time after a reload request before the content is rebuilt
+    pub debounce_seconds: u64,
+    /// Minutes between scheduled reloads of remote content; unset reloads every midnight
+    /// and 0 only reloads on request
+    pub interval_minutes: Option<u64>,
+}
+
+impl Default for ReloadConfig {
+    fn default() -> Self {
+        Self {
+            debounce_seconds: 10,
+            interval_minutes: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct ViewsConfig {
+    /// View counts snapshot; defaults to `tmp/views.tsv` for local content and
+    /// `/opt/personal/runtime/views.tsv` otherwise
+    pub file: Option<PathBuf>,
+    /// Seconds between compactions of the view log into the snapshot
+    pub flush_seconds: u64,
+    /// Minutes before the same visitor counts again for the same post
+    pub dedup_minutes: u32,
+    /// Days kept as daily buckets
+    pub history_days: u32,
+    /// Months kept as monthly buckets
+    pub history_months: u32,
+}
+
+impl Default for ViewsConfig {
+    fn default() -> Self {
+        let retention = Retention::default();
+        Self {
+            file: None,
+            flush_seconds: 300,
+            dedup_minutes: 30,
+            history_days: retention.days,
+            history_months: retention.months,
+        }
+    }
+}
+
+/// Secrets guarding the admin endpoints, the push webhook and draft previews; each is
+/// disabled while its secret is unset
+#[derive(Debug, Clone, Default, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct AdminConfig {
+    /// Bearer token accepted by `/admin/*`
+    pub token: Option<String>,
+    /// Basic auth login accepted by `/admin/*` when both are set
+    pub username: Option<String>,
+    pub password: Option<String>,
+    /// Shared secret signing push webhook payloads
+    pub webhook_secret: Option<String>,
+    /// Key deriving the per-post tokens of draft preview links
+    pub preview_secret: Option<String>,
+}
+
+impl ViewsConfig {
+    pub fn retention(&self) -> Retention {
+        Retention {
+            days: self.history_days,
+            months: self.history_months,
+        }
+    }
+}
+
+impl Config {
+    /// Load the configuration the way the binaries do
+    ///
+    /// # Effects
+    /// - Reads the file named by `CONFIG_FILE`, which must exist, or `personal.toml` when present
+    /// - Applies the environment overrides listed in [`Config::apply_env`]
+    pub fn load() -> Result<Self, ApplicationError> {
+        Self::load_from(None)
+    }
+
+    /// Like [`Config::load`], reading `file` instead when one is given
+    pub fn load_from(file: Option<&Path>) -> Result<Self, ApplicationError> {
+        let mut config = match (file, std::env::var("CONFIG_FILE")) {
+            (Some(file), _) => Self::from_file(file)?,
+            (None, Ok(path)) => Self::from_file(Path::new(&path))?,
+            (None, Err(_)) if Path::new(DEFAULT_CONFIG_FILE).exists() => {
+                Self::from_file(Path::new(DEFAULT_CONFIG_FILE))?
+            }
+            (None, Err(_)) => Self::default(),
+        };
+        config.apply_env(|name| std::env::var(name).ok())?;
+        Ok(config)
+    }
+
+    pub fn from_file(path: &Path) -> Result<Self, ApplicationError> {
+        let text =
+            std::fs::read_to_string(path).map_err(|source| ApplicationError::ReadingError {
+                path: path.to_path_buf(),
+                source,
+            })?;
+        Self::from_toml(&text).map_err(|reason| ApplicationError::ConfigError {
+            origin: path.display().to_string(),
+            reason,
+        })
+    }
+
+    pub fn from_toml(text: &str) -> Result<Self, String> {
+        let mut config: Config =
+            toml::from_str(text).map_err(|err| err.to_string().trim().to_string())?;
+        config.normalize();
+        Ok(config)
+    }
+
+    /// Override file values with the environment variables that are set and not empty
+    ///
+    /// # Arguments
+    ///
+    /// * `var` - Looks up an environment variable by name
+    ///
+    /// # Returns
+    /// An error naming the first variable whose value can't be parsed
+    pub fn apply_env(
+        &mut self,
+        var: impl Fn(&str) -> Option<String>,
+    ) -> Result<(), ApplicationError> {
+        let var = |name: &str| var(name).filter(|value| !value.is_empty());
+        let parse = |name: &str, target: &mut dyn FnMut(&str) -> bool| match var(name) {
+            Some(value) if !target(&value) => Err(ApplicationError::ConfigError {
+                origin: name.to_string(),
+                reason: format!("invalid value {value:?}"),
+            }),
+            _ => Ok(()),
+        };
+
+        parse("BIND_ADDRESS", &mut |value| {
+            set_parsed(&mut self.server.bind, value)
+        })?;
+        if let Some(static_dir) = var("STATIC_DIR") {
+            self.server.static_dir = Some(PathBuf::from(static_dir));
+        }
+        parse("PAGE_CACHE_MB", &mut |value| {
+            set_parsed(&mut self.server.page_cache_mb, value)
+        })?;
+        parse("TRUSTED_PROXIES", &mut |value| {
+            value
+                .split(',')
+                .map(|ip| ip.trim().parse())
+                .collect::<Result<Vec<IpAddr>, _>>()
+                .map(|proxies| self.server.trusted_proxies = proxies)
+                .is_ok()
+        })?;
+
+        let site = &mut self.site;
+        for (name, field) in [
+            ("SITE_BASE_URL", &mut site.base_url),
+            ("SITE_TITLE", &mut site.title),
+            ("SITE_DESCRIPTION", &mut site.description),
+            ("SITE_AUTHOR", &mut site.author),
+        ] {
+            if let Some(value) = var(name) {
+                *field = value;
+            }
+        }
+        if let Some(twitter) = var("SITE_TWITTER") {
+            site.twitter = Some(twitter);
+        }
+
+        let content = &mut self.content;
+        if let Some(source) = var("CONTENT_SOURCE") {
+            content.source = source;
+        }
+        if let Some(branch) = var("CONTENT_BRANCH") {
+            content.branch = Some(branch);
+        }
+        if let Some(mirror_dir) = var("CONTENT_MIRROR_DIR") {
+            content.mirror_dir = PathBuf::from(mirror_dir);
+        }
+        if let Some(database_file) = var("CONTENT_DB_FILE") {
+            content.database_file = PathBuf::from(database_file);
+        }
+        if let Some(resources_dir) = var("CONTENT_RESOURCES_DIR") {
+            content.resources_dir = Some(PathBuf::from(resources_dir));
+        }
+
+        let reload = &mut self.reload;
+        parse("RELOAD_DEBOUNCE_SECONDS", &mut |value| {
+            set_parsed(&mut reload.debounce_seconds, value)
+        })?;
+        parse("RELOAD_INTERVAL_MINUTES", &mut |value| {
+            value
+                .parse()
+                .map(|minutes| reload.interval_minutes = Some(minutes))
+                .is_ok()
+        })?;
+
+        let views = &mut self.views;
+        if let Some(file) = var("VIEW_COUNTS_FILE") {
+            views.file = Some(PathBuf::from(file));
+        }
+        parse("VIEW_FLUSH_SECONDS", &mut |value| {
+            set_parsed(&mut views.flush_seconds, value)
+        })?;
+        parse("VIEW_DEDUP_MINUTES", &mut |value| {
+            set_parsed(&mut views.dedup_minutes, value)
+        })?;
+        parse("VIEW_HISTORY_DAYS", &mut |value| {
+            set_parsed(&mut views.history_days, value)
+        })?;
+        parse("VIEW_HISTORY_MONTHS", &mut |value| {
+            set_parsed(&mut views.history_months, value)
+        })?;
+
+        let admin = &mut self.admin;
+        for (name, field) in [
+            ("ADMIN_TOKEN", &mut admin.token),
+            ("ADMIN_USERNAME", &mut admin.username),
+            ("ADMIN_PASSWORD", &mut admin.password),
+            ("WEBHOOK_SECRET", &mut admin.webhook_secret),
+            ("PREVIEW_SECRET", &mut admin.preview_secret),
+        ] {
+            if let Some(value) = var(name) {
+                *field = Some(value);
+            }
+        }
+
+        self.normalize();
+        Ok(())
+    }
+
+    /// The views snapshot to use for the configured content source
+    pub fn views_file(&self) -> PathBuf {
+        match (&self.views.file, self.content.local_path()) {
+            (Some(file), _) => file.clone(),
+            (None, Some(_)) => PathBuf::from("tmp/views.tsv"),
+            (None, None) => PathBuf::from("/opt/personal/runtime/views.tsv"),
+        }
+    }
+
+    /// The directory resources are published into for the configured content source
+    pub fn resources_dir(&self) -> PathBuf {
+        match (&self.content.resources_dir, self.content.local_path()) {
+            (Some(dir), _) => dir.clone(),
+            (None, Some(_)) => PathBuf::from("tmp/resources"),
+            (None, None) => PathBuf::from(DEFAULT_RESOURCES_DIR),
+        }
+    }
+
+    fn normalize(&mut self) {
+        let trimmed = self.site.base_url.trim_end_matches('/').len();
+        self.site.base_url.truncate(trimmed);
+    }
+}
+
+fn set_parsed<T: FromStr>(target: &mut T, value: &str) -> bool {
+    match value.trim().parse() {
+        Ok(parsed) => {
+            *target = parsed;
+            true
+        }
+        Err(_) => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_file_values_and_env_overrides() {
+        let mut config = Config::from_toml(
+            r#"
+            [server]
+            bind = "0.0.0.0:8080"
+
+            [site]
+            base_url = "https://blog.example/"
+            title = "Example"
+
+            [content]
+            source = "/srv/blog"
+
+            [views]
+            history_days = 30
+
+            [admin]
+            token = "from-file"
+            username = "editor"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config.server.bind.port(), 8080);
+        assert_eq!(
+            config.site.url("/feed.atom"),
+            "https://blog.example/feed.atom"
+        );
+        assert_eq!(config.site.author, SiteConfig::default().author);
+        assert_eq!(config.content.local_path(), Some(Path::new("/srv/blog")));
+        assert_eq!(config.views_file(), PathBuf::from("tmp/views.tsv"));
+        assert_eq!(config.resources_dir(), PathBuf::from("tmp/resources"));
+
+        let env: HashMap<&str, &str> = HashMap::from([
+            ("SITE_TITLE", "Overridden"),
+            ("SITE_AUTHOR", ""),
+            ("CONTENT_SOURCE", "https://git.example/blog.git"),
+            ("VIEW_HISTORY_DAYS", "14"),
+            ("RELOAD_INTERVAL_MINUTES", "0"),
+            ("PAGE_CACHE_MB", "0"),
+            ("TRUSTED_PROXIES", "127.0.0.1, ::1"),
+            ("ADMIN_TOKEN", "from-env"),
+            ("ADMIN_USERNAME", ""),
+            ("WEBHOOK_SECRET", "signing"),
+        ]);
+        config
+            .apply_env(|name| env.get(name).map(|value| value.to_string()))
+            .unwrap();
+        assert_eq!(config.site.title, "Overridden");
+        assert_eq!(config.site.author, SiteConfig::default().author);
+        assert_eq!(config.content.local_path(), None);
+        assert_eq!(config.views.retention().days, 14);
+        assert_eq!(config.reload.interval_minutes, Some(0));
+        assert_eq!(config.server.page_cache_mb, 0);
+        assert_eq!(config.admin.token.as_deref(), Some("from-env"));
+        assert_eq!(config.admin.username.as_deref(), Some("editor"));
+        assert_eq!(config.admin.webhook_secret.as_deref(), Some("signing"));
+        assert_eq!(config.admin.preview_secret, None);
+        assert_eq!(
+            config.server.trusted_proxies,
+            [
+                "127.0.0.1".parse::<IpAddr>().unwrap(),
+                "::1".parse().unwrap()
+            ]
+        );
+        assert_eq!(
+            config.views_file(),
+            PathBuf::from("/opt/personal/runtime/views.tsv")
+        );
+        assert_eq!(
+            config.resources_dir(),
+            PathBuf::from("/opt/personal/runtime/resources")
+        );
+    }
+
+    #[test]
+    fn test_example_file_matches_the_defaults() {
+        let example = Config::from_toml(include_str!("../personal.example.toml")).unwrap();
+        assert_eq!(example, Config::default());
+    }
+
+    #[test]
+    fn test_mistakes_are_reported() {
+        assert!(Config::from_toml("[site]\ntitel = \"typo\"").is_err());
+        assert!(Config::from_toml("[server]\nbind = \"localhost\"").is_err());
+
+        let mut config = Config::default();
+        let err = config
+            .apply_env(|name| (name == "VIEW_FLUSH_SECONDS").then(|| "soon".to_string()))
+            .unwrap_err();
+        assert!(err.to_string().contains("VIEW_FLUSH_SECONDS"));
+    }
+}
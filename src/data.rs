@@ -1,11 +1,20 @@
-use std::{collections::HashMap, fs::read_to_string, path::PathBuf};
+use std::{
+    collections::{BTreeSet, HashMap},
+    fs::read_to_string,
+    path::{Path, PathBuf},
+};
 
 use chrono::NaiveDate;
-use pulldown_cmark::{html, Options, Parser};
+use pulldown_cmark::{html, CodeBlockKind, CowStr, Event, Options, Parser, Tag, TagEnd};
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 use utoipa::ToSchema;
 
-use crate::error::ApplicationError;
+use crate::{
+    error::ApplicationError,
+    highlight::highlight_code_block,
+    resources::{resource_name, ResourceManifest},
+};
 
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, ToSchema)]
 pub struct Post {
@@ -13,14 +22,29 @@ pub struct Post {
     pub markdown: Markdown,
 }
 
+impl Post {
+    /// Hex SHA-256 of the rendered post and its frontmatter; changes with any edit
+    pub fn content_hash(&self) -> String {
+        let mut hasher = Sha256::new();
+        hasher.update(self.data.as_bytes());
+        // Serializing plain data to JSON can't fail
+        hasher.update(serde_json::to_vec(&self.markdown).unwrap_or_default());
+        hex::encode(hasher.finalize())
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, ToSchema)]
 pub struct Markdown {
     pub title: String,
     pub description: String,
     pub slug: String,
     pub tags: Vec<String>,
+    /// Publication date; posts dated in the future stay hidden until that day
     pub date: NaiveDate,
     pub series: Option<Series>,
+    /// Drafts are never listed and are only reachable through a preview link
+    #[serde(default)]
+    pub draft: bool,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, ToSchema)]
@@ -30,7 +54,31 @@ pub struct Series {
 }
 
 pub async fn parse_to_data(path: &PathBuf) -> Result<Post, ApplicationError> {
-    let content = read_to_string(&path).map_err(|source| ApplicationError::ReadingError {
+    parse_with_resources(path, &ResourceManifest::default()).await
+}
+
+/// Parse a post whose relative links point into a repository `resources/` directory
+///
+/// # Arguments
+/// * `path` - The post file
+/// * `resources` - The repository resources, linked by their fingerprinted URLs
+pub async fn parse_with_resources(
+    path: &PathBuf,
+    resources: &ResourceManifest,
+) -> Result<Post, ApplicationError> {
+    Ok(parse_with_links(path, resources).await?.0)
+}
+
+/// Like [`parse_with_resources`], also returning the resources the post links to
+///
+/// # Returns
+/// The post and the path of every resource its relative links point at, relative to
+/// `resources/`, whether or not the resource exists yet
+pub async fn parse_with_links(
+    path: &PathBuf,
+    resources: &ResourceManifest,
+) -> Result<(Post, BTreeSet<String>), ApplicationError> {
+    let content = read_to_string(path).map_err(|source| ApplicationError::ReadingError {
         path: path.clone(),
         source,
     })?;
@@ -44,11 +92,14 @@ pub async fn parse_to_data(path: &PathBuf) -> Result<Post, ApplicationError> {
             path: path.clone(),
             reason: "Missing YAML frontmatter (expected content between --- markers)".to_string(),
         })?;
-    println!("{}", markdown_part);
-    let markdown: Markdown = serde_yaml::from_str(markdown_part)?;
+    let frontmatter_start = content.find("---").map_or(0, |index| index + 3);
+    let markdown: Markdown = serde_yaml::from_str(markdown_part)
+        .map_err(|err| frontmatter_error(path, &content, frontmatter_start, err))?;
     if markdown.description.chars().count() > 200 {
-        return Err(ApplicationError::ParsingError {
+        return Err(ApplicationError::FrontmatterError {
             path: path.clone(),
+            line: frontmatter_key_line(&content, frontmatter_start, "description"),
+            column: None,
             reason: "Frontmatter `description` must be at most 200 characters".to_string(),
         });
     }
@@ -57,28 +108,90 @@ pub async fn parse_to_data(path: &PathBuf) -> Result<Post, ApplicationError> {
         reason: "Missing markdown content after frontmatter".to_string(),
     })?;
 
-    let content_replaced_tags = replace_relative_paths(content).await;
+    let (content_replaced_tags, links) = replace_relative_paths(content, resources).await;
     let html_content = markdown_to_html(&content_replaced_tags);
 
-    Ok(Post {
-        data: html_content,
-        markdown: markdown,
-    })
+    Ok((
+        Post {
+            data: html_content,
+            markdown,
+        },
+        links,
+    ))
+}
+
+/// Convert a YAML error positioned within the frontmatter into one positioned within the file
+fn frontmatter_error(
+    path: &Path,
+    content: &str,
+    frontmatter_start: usize,
+    error: serde_yaml::Error,
+) -> ApplicationError {
+    let message = error.to_string();
+    let Some(location) = error.location() else {
+        return ApplicationError::FrontmatterError {
+            path: path.to_path_buf(),
+            line: None,
+            column: None,
+            reason: message,
+        };
+    };
+
+    let before = &content[..frontmatter_start];
+    let line_offset = before.matches('\n').count();
+    // The frontmatter's first line shares the file line of the opening `---`
+    let column_offset = if location.line() == 1 {
+        frontmatter_start - before.rfind('\n').map_or(0, |index| index + 1)
+    } else {
+        0
+    };
+    // The message repeats the position relative to the frontmatter, which would be misleading
+    let reason = message
+        .rsplit_once(" at line ")
+        .map_or(message.as_str(), |(reason, _)| reason)
+        .to_string();
+
+    ApplicationError::FrontmatterError {
+        path: path.to_path_buf(),
+        line: Some(location.line() + line_offset),
+        column: Some(location.column() + column_offset),
+        reason,
+    }
+}
+
+/// 1-based file line of a top-level frontmatter `key`
+fn frontmatter_key_line(content: &str, frontmatter_start: usize, key: &str) -> Option<usize> {
+    let line_offset = content[..frontmatter_start].matches('\n').count();
+    content[frontmatter_start..]
+        .lines()
+        .position(|line| {
+            line.strip_prefix(key)
+                .is_some_and(|rest| rest.trim_start().starts_with(':'))
+        })
+        .map(|index| index + line_offset + 1)
 }
 
-async fn replace_relative_paths(content: &str) -> String {
+async fn replace_relative_paths(
+    content: &str,
+    resources: &ResourceManifest,
+) -> (String, BTreeSet<String>) {
     use regex::Regex;
     let re = Regex::new(r"\[([^\]]+)\]\(([^)]+)\)").unwrap();
-    re.replace_all(content, |caps: &regex::Captures| {
-        let alt_text = &caps[1];
-        let path = &caps[2];
-        if path.starts_with("http://") || path.starts_with("https://") || path.starts_with("/") {
-            format!("[{}]({})", alt_text, path)
-        } else {
-            format!("[{}](/static/misc/{})", alt_text, path)
-        }
-    })
-    .to_string()
+    let mut links = BTreeSet::new();
+    let replaced = re
+        .replace_all(content, |caps: &regex::Captures| {
+            let alt_text = &caps[1];
+            let path = &caps[2];
+            if path.starts_with("http://") || path.starts_with("https://") || path.starts_with("/")
+            {
+                format!("[{}]({})", alt_text, path)
+            } else {
+                links.insert(resource_name(path).to_string());
+                format!("[{}]({})", alt_text, resources.url(path))
+            }
+        })
+        .to_string();
+    (replaced, links)
 }
 fn markdown_to_html(markdown: &str) -> String {
     // Smart spacing:
@@ -115,10 +228,39 @@ fn markdown_to_html(markdown: &str) -> String {
 
     let parser = Parser::new_ext(&processed, options);
     let mut html_output = String::new();
-    html::push_html(&mut html_output, parser);
+    html::push_html(&mut html_output, highlight_code_blocks(parser).into_iter());
     add_heading_anchors(&html_output)
 }
 
+/// Replace every code block in the event stream with its highlighted HTML
+fn highlight_code_blocks<'a>(events: impl Iterator<Item = Event<'a>>) -> Vec<Event<'a>> {
+    let mut output = Vec::new();
+    let mut block: Option<(String, String)> = None;
+
+    for event in events {
+        match (event, &mut block) {
+            (Event::Start(Tag::CodeBlock(kind)), None) => {
+                let info = match kind {
+                    CodeBlockKind::Fenced(info) => info.to_string(),
+                    CodeBlockKind::Indented => String::new(),
+                };
+                block = Some((info, String::new()));
+            }
+            (Event::Text(text), Some((_, code))) => code.push_str(&text),
+            (Event::End(TagEnd::CodeBlock), Some(_)) => {
+                if let Some((info, code)) = block.take() {
+                    output.push(Event::Html(CowStr::from(highlight_code_block(
+                        &code, &info,
+                    ))));
+                }
+            }
+            (event, _) => output.push(event),
+        }
+    }
+
+    output
+}
+
 fn add_heading_anchors(html: &str) -> String {
     use regex::Regex;
 
@@ -225,7 +367,7 @@ fn add_heading_anchors(html: &str) -> String {
     output
 }
 
-fn slugify(value: &str) -> String {
+pub(crate) fn slugify(value: &str) -> String {
     let mut output = String::new();
     let mut last_dash = false;
 
@@ -233,11 +375,9 @@ fn slugify(value: &str) -> String {
         if ch.is_ascii_alphanumeric() {
             output.push(ch.to_ascii_lowercase());
             last_dash = false;
-        } else if ch.is_whitespace() || ch == '-' {
-            if !output.is_empty() && !last_dash {
-                output.push('-');
-                last_dash = true;
-            }
+        } else if (ch.is_whitespace() || ch == '-') && !output.is_empty() && !last_dash {
+            output.push('-');
+            last_dash = true;
         }
     }
 
@@ -1,173 +1,378 @@
-use std::collections::HashMap;
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, HashMap};
 
-use chrono::Datelike;
+use chrono::{Datelike, Local, NaiveDate};
 
 use crate::data::Post;
 use crate::error::ApplicationError;
+use crate::pagination::{Page, PageRequest, SortOrder};
+use crate::search::{SearchHit, SearchIndex};
+use crate::url::url_encode;
+
+/// A set of posts that can be browsed page by page
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Listing {
+    All,
+    Tag(String),
+    Series(String),
+    Keyword(String),
+    Date { year: i32, month: Option<u32> },
+}
+
+impl Listing {
+    /// Orders this listing can be sorted by, its default first
+    pub fn sorts(&self) -> &'static [SortOrder] {
+        use SortOrder::*;
+        match self {
+            Listing::Series(_) => &[Episode, Newest, Oldest, Title, Views],
+            Listing::Keyword(_) => &[Relevance, Newest, Oldest, Title, Views],
+            Listing::All | Listing::Tag(_) | Listing::Date { .. } => {
+                &[Newest, Oldest, Title, Views]
+            }
+        }
+    }
+
+    /// Address of the listing's first page; all but keyword searches have a path of their own
+    pub fn path(&self) -> String {
+        match self {
+            Listing::All => "/".to_string(),
+            Listing::Tag(tag) => format!("/tags/{}", url_encode(tag)),
+            Listing::Series(series) => format!("/series/{}", url_encode(series)),
+            Listing::Keyword(keyword) => {
+                format!("/posts/by-keyword?keyword={}", url_encode(keyword))
+            }
+            Listing::Date { year, month: None } => format!("/archive/{year}"),
+            Listing::Date {
+                year,
+                month: Some(month),
+            } => format!("/archive/{year}/{month}"),
+        }
+    }
+}
 
 pub trait Database {
     fn get_by_slug(&self, slug: String) -> Option<Post>;
     fn get_by_tag(&self, tag: String) -> Vec<Post>;
     fn get_by_series(&self, series: String) -> Vec<Post>;
+    /// Posts matching the full-text query, most relevant first
     fn get_by_keyword(&self, keyword: String) -> Vec<Post>;
+    /// Full-text search returning ranked hits with highlighted snippets
+    fn search(&self, query: &str) -> Vec<SearchHit>;
     fn get_by_year_month(&self, year: i32, month: Option<u32>) -> Vec<Post>;
     fn get_last_n_posts(&self, n: usize) -> Vec<Post>;
     fn get_all_posts(&self) -> Vec<Post>;
-    fn get_all_tags_with_count(&self) -> Vec<(String, u8)>;
-    fn get_all_dates_with_count(&self) -> Vec<((i32, u32), u8)>;
+    fn get_all_tags_with_count(&self) -> Vec<(String, usize)>;
+    fn get_all_dates_with_count(&self) -> Vec<((i32, u32), usize)>;
+
+    /// Look up a draft or scheduled post; only for the secret preview URL
+    fn get_unpublished_by_slug(&self, slug: String) -> Option<Post>;
+    /// Every draft and scheduled post, for listing preview links
+    fn get_unpublished_posts(&self) -> Vec<Post>;
 
     fn insert_parsed_to_database(&mut self, post: Post) -> Result<(), ApplicationError>;
+
+    /// One sorted page of a listing
+    ///
+    /// # Arguments
+    /// * `listing` - Which posts to page through
+    /// * `request` - Page number, size and sort order
+    /// * `views` - View counts by slug, needed only when sorting by views
+    fn get_page(
+        &self,
+        listing: &Listing,
+        request: &PageRequest,
+        views: &HashMap<String, u64>,
+    ) -> Page<Post> {
+        let posts = match listing {
+            Listing::All => self.get_all_posts(),
+            Listing::Tag(tag) => self.get_by_tag(tag.clone()),
+            Listing::Series(series) => self.get_by_series(series.clone()),
+            Listing::Keyword(keyword) => self.get_by_keyword(keyword.clone()),
+            Listing::Date { year, month } => self.get_by_year_month(*year, *month),
+        };
+        request.paginate(posts, views)
+    }
+}
+
+/// Returns the current date; scheduled posts become visible once it reaches their `date`
+pub type Clock = fn() -> NaiveDate;
+
+pub(crate) fn local_today() -> NaiveDate {
+    Local::now().date_naive()
 }
 
+/// In-memory indexes over the published and scheduled posts
+///
+/// Scheduled (future-dated) posts are indexed like any other post but filtered out of
+/// every query until their date arrives, so they appear without a reload. Drafts are
+/// kept apart and only reachable through the unpublished lookups.
+///
+/// Every index keeps its slugs in the order queries return them, so reads only filter.
 pub struct InMemDatabase {
     pub by_slug: HashMap<String, Post>,
-    pub by_tag: HashMap<String, String>,
-    pub by_series: HashMap<String, String>,
-    pub by_keyword: HashMap<String, String>,
-    pub by_date: HashMap<(i32, u32), String>,
+    /// Slugs of each tag, newest first
+    pub by_tag: BTreeMap<String, Vec<String>>,
+    /// Slugs of each series in episode order
+    pub by_series: BTreeMap<String, Vec<String>>,
+    /// Slugs published on each day, alphabetically
+    pub by_date: BTreeMap<NaiveDate, Vec<String>>,
+    pub drafts: HashMap<String, Post>,
+    pub search_index: SearchIndex,
+    clock: Clock,
+}
+
+impl Default for InMemDatabase {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl InMemDatabase {
     pub fn new() -> InMemDatabase {
         InMemDatabase {
             by_slug: HashMap::new(),
-            by_tag: HashMap::new(),
-            by_keyword: HashMap::new(),
-            by_date: HashMap::new(),
-            by_series: HashMap::new(),
+            by_tag: BTreeMap::new(),
+            by_series: BTreeMap::new(),
+            by_date: BTreeMap::new(),
+            drafts: HashMap::new(),
+            search_index: SearchIndex::new(),
+            clock: local_today,
         }
     }
+
+    /// Decide what is scheduled against `clock` instead of the local date
+    pub fn with_clock(mut self, clock: Clock) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Every post held, drafts and scheduled posts included
+    pub fn posts_including_unpublished(&self) -> impl Iterator<Item = &Post> {
+        self.by_slug.values().chain(self.drafts.values())
+    }
+
+    /// The date the database is currently evaluated at
+    pub fn today(&self) -> NaiveDate {
+        (self.clock)()
+    }
+
+    /// Date of the earliest post that is still scheduled
+    pub fn next_scheduled_date(&self) -> Option<NaiveDate> {
+        let today = self.today();
+        self.by_date
+            .range(today.succ_opt().unwrap_or(today)..)
+            .next()
+            .map(|(date, _)| *date)
+    }
+
+    fn is_published(&self, post: &Post, today: NaiveDate) -> bool {
+        post.markdown.date <= today
+    }
+
+    /// Resolve slugs to the posts published by `today`, keeping their order
+    fn published<'a>(
+        &'a self,
+        slugs: impl IntoIterator<Item = &'a String>,
+        today: NaiveDate,
+    ) -> impl Iterator<Item = &'a Post> {
+        slugs
+            .into_iter()
+            .filter_map(|slug| self.by_slug.get(slug))
+            .filter(move |post| self.is_published(post, today))
+    }
+
+    /// Slugs of every post dated within `range`, newest first
+    fn slugs_between(
+        &self,
+        range: impl std::ops::RangeBounds<NaiveDate>,
+    ) -> impl Iterator<Item = &String> {
+        self.by_date.range(range).rev().flat_map(|(_, slugs)| slugs)
+    }
+}
+
+/// Insert `slug` into `slugs`, keeping them ordered by `key`
+fn insert_sorted<K: Ord>(
+    slugs: &mut Vec<String>,
+    slug: &str,
+    posts: &HashMap<String, Post>,
+    key: impl Fn(&Post) -> K,
+) {
+    let new_key = key(&posts[slug]);
+    let index = slugs.partition_point(|existing| key(&posts[existing]) <= new_key);
+    slugs.insert(index, slug.to_string());
+}
+
+/// Newest first, ties by slug
+fn by_recency(post: &Post) -> (Reverse<NaiveDate>, String) {
+    (Reverse(post.markdown.date), post.markdown.slug.clone())
+}
+
+/// Episode order, ties by date then slug
+fn by_episode(post: &Post) -> (u8, NaiveDate, String) {
+    let ep = post.markdown.series.as_ref().map_or(0, |series| series.ep);
+    (ep, post.markdown.date, post.markdown.slug.clone())
 }
 
 impl Database for InMemDatabase {
     fn insert_parsed_to_database(&mut self, post: Post) -> Result<(), ApplicationError> {
         let slug = post.markdown.slug.clone();
-        let date: (i32, u32) = (post.markdown.date.year(), post.markdown.date.month());
-
-        self.by_slug.entry(slug.clone()).or_insert(post.clone());
-        self.by_date.entry(date).or_insert(slug.clone());
-        for tag in &post.markdown.tags {
-            self.by_tag
-                .entry(tag.clone())
-                .or_insert_with(String::new)
-                .push_str(&format!("{},", slug));
+        if post.markdown.draft {
+            self.drafts.entry(slug).or_insert(post);
+            return Ok(());
         }
-        if let Some(series) = &post.markdown.series {
-            self.by_series
-                .entry(series.title.clone())
-                .or_insert_with(String::new)
-                .push_str(&format!("{},", slug));
+        if self.by_slug.contains_key(&slug) {
+            return Ok(());
+        }
+
+        self.search_index.insert(&post);
+        let date = post.markdown.date;
+        let tags = post.markdown.tags.clone();
+        let series = post
+            .markdown
+            .series
+            .as_ref()
+            .map(|series| series.title.clone());
+        self.by_slug.insert(slug.clone(), post);
+
+        let day = self.by_date.entry(date).or_default();
+        let index = day.partition_point(|existing| *existing < slug);
+        day.insert(index, slug.clone());
+        for tag in tags {
+            let slugs = self.by_tag.entry(tag).or_default();
+            if !slugs.contains(&slug) {
+                insert_sorted(slugs, &slug, &self.by_slug, by_recency);
+            }
+        }
+        if let Some(series) = series {
+            let slugs = self.by_series.entry(series).or_default();
+            insert_sorted(slugs, &slug, &self.by_slug, by_episode);
         }
 
         Ok(())
     }
 
     fn get_by_slug(&self, slug: String) -> Option<Post> {
-        self.by_slug.get(&slug).cloned()
+        let today = (self.clock)();
+        self.by_slug
+            .get(&slug)
+            .filter(|post| self.is_published(post, today))
+            .cloned()
     }
 
     fn get_by_tag(&self, tag: String) -> Vec<Post> {
+        let today = (self.clock)();
         self.by_tag
             .get(&tag)
-            .map(|slugs| {
-                slugs
-                    .split(',')
-                    .filter(|s| !s.is_empty())
-                    .filter_map(|slug| self.by_slug.get(slug).cloned())
-                    .collect()
-            })
+            .map(|slugs| self.published(slugs, today).cloned().collect())
             .unwrap_or_default()
     }
 
     fn get_by_series(&self, series: String) -> Vec<Post> {
+        let today = (self.clock)();
         self.by_series
             .get(&series)
-            .map(|slugs| {
-                slugs
-                    .split(',')
-                    .filter(|s| !s.is_empty())
-                    .filter_map(|slug| self.by_slug.get(slug).cloned())
-                    .collect()
-            })
+            .map(|slugs| self.published(slugs, today).cloned().collect())
             .unwrap_or_default()
     }
 
     fn get_by_keyword(&self, keyword: String) -> Vec<Post> {
-        let keyword_lower = keyword.to_lowercase();
-        let mut results = Vec::new();
-        let mut seen = std::collections::HashSet::new();
-
-        for (slug, post) in &self.by_slug {
-            if slug.to_lowercase().contains(&keyword_lower)
-                || post.markdown.title.to_lowercase().contains(&keyword_lower)
-            {
-                if seen.insert(slug.clone()) {
-                    results.push(post.clone());
-                }
-            }
-        }
-        for (series_name, slugs) in &self.by_series {
-            if series_name.to_lowercase().contains(&keyword_lower) {
-                for slug in slugs.split(',').filter(|s| !s.is_empty()) {
-                    if seen.insert(slug.to_string()) {
-                        if let Some(post) = self.by_slug.get(slug) {
-                            results.push(post.clone());
-                        }
-                    }
-                }
-            }
-        }
-        for (tag, slugs) in &self.by_tag {
-            if tag.to_lowercase().contains(&keyword_lower) {
-                for slug in slugs.split(',').filter(|s| !s.is_empty()) {
-                    if seen.insert(slug.to_string()) {
-                        if let Some(post) = self.by_slug.get(slug) {
-                            results.push(post.clone());
-                        }
-                    }
-                }
-            }
-        }
+        self.search(&keyword)
+            .into_iter()
+            .map(|hit| hit.post)
+            .collect()
+    }
 
-        results
+    fn search(&self, query: &str) -> Vec<SearchHit> {
+        let today = (self.clock)();
+        self.search_index
+            .search(query)
+            .into_iter()
+            .filter_map(|(slug, score, snippet)| {
+                let post = self.by_slug.get(&slug)?;
+                self.is_published(post, today).then(|| SearchHit {
+                    post: post.clone(),
+                    score,
+                    snippet,
+                })
+            })
+            .collect()
     }
 
     fn get_by_year_month(&self, year: i32, month: Option<u32>) -> Vec<Post> {
-        self.by_date
-            .iter()
-            .filter(|((y, m), _)| *y == year && month.map_or(true, |month| *m == month))
-            .filter_map(|(_, slug)| self.by_slug.get(slug).cloned())
-            .collect()
+        let today = (self.clock)();
+        let (first, last_month) = match month {
+            Some(month) => (NaiveDate::from_ymd_opt(year, month, 1), month),
+            None => (NaiveDate::from_ymd_opt(year, 1, 1), 12),
+        };
+        let Some(first) = first else {
+            return Vec::new();
+        };
+        let end = if last_month == 12 {
+            NaiveDate::from_ymd_opt(year + 1, 1, 1)
+        } else {
+            NaiveDate::from_ymd_opt(year, last_month + 1, 1)
+        };
+        let slugs: Vec<&String> = match end {
+            Some(end) => self.slugs_between(first..end).collect(),
+            None => self.slugs_between(first..).collect(),
+        };
+        self.published(slugs, today).cloned().collect()
     }
 
     fn get_last_n_posts(&self, n: usize) -> Vec<Post> {
-        let mut posts: Vec<Post> = self.by_slug.values().cloned().collect();
-        posts.sort_by(|a, b| b.markdown.date.cmp(&a.markdown.date));
-        posts.into_iter().take(n).collect()
+        let today = (self.clock)();
+        self.published(self.slugs_between(..), today)
+            .take(n)
+            .cloned()
+            .collect()
     }
 
     fn get_all_posts(&self) -> Vec<Post> {
-        self.by_slug.values().cloned().collect()
+        let today = (self.clock)();
+        self.published(self.slugs_between(..=today), today)
+            .cloned()
+            .collect()
     }
 
-    fn get_all_tags_with_count(&self) -> Vec<(String, u8)> {
+    fn get_all_tags_with_count(&self) -> Vec<(String, usize)> {
+        let today = (self.clock)();
         self.by_tag
             .iter()
-            .map(|(tag, slugs)| {
-                let count = slugs.split(',').filter(|s| !s.is_empty()).count() as u8;
-                (tag.clone(), count)
-            })
+            .map(|(tag, slugs)| (tag.clone(), self.published(slugs, today).count()))
+            .filter(|(_, count)| *count > 0)
             .collect()
     }
 
-    fn get_all_dates_with_count(&self) -> Vec<((i32, u32), u8)> {
-        let mut date_counts: HashMap<(i32, u32), u8> = HashMap::new();
-
-        for post in self.by_slug.values() {
-            let date = (post.markdown.date.year(), post.markdown.date.month());
-            *date_counts.entry(date).or_insert(0) += 1;
+    fn get_all_dates_with_count(&self) -> Vec<((i32, u32), usize)> {
+        let today = (self.clock)();
+        let mut date_counts: BTreeMap<(i32, u32), usize> = BTreeMap::new();
+        for (date, slugs) in self.by_date.range(..=today) {
+            let count = self.published(slugs, today).count();
+            *date_counts.entry((date.year(), date.month())).or_default() += count;
         }
+        date_counts.into_iter().rev().collect()
+    }
+
+    fn get_unpublished_by_slug(&self, slug: String) -> Option<Post> {
+        let today = (self.clock)();
+        self.drafts.get(&slug).cloned().or_else(|| {
+            self.by_slug
+                .get(&slug)
+                .filter(|post| !self.is_published(post, today))
+                .cloned()
+        })
+    }
 
-        date_counts.into_iter().collect()
+    fn get_unpublished_posts(&self) -> Vec<Post> {
+        let today = (self.clock)();
+        let mut posts: Vec<Post> = self
+            .slugs_between(today.succ_opt().unwrap_or(today)..)
+            .filter_map(|slug| self.by_slug.get(slug))
+            .filter(|post| !self.is_published(post, today))
+            .chain(self.drafts.values())
+            .cloned()
+            .collect();
+        posts.sort_by(|a, b| a.markdown.slug.cmp(&b.markdown.slug));
+        posts
     }
 }
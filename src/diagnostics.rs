@@ -0,0 +1,112 @@
+/// Structured reports for content files that could not be ingested
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use serde::Serialize;
+
+use crate::{data::Post, error::ApplicationError};
+
+/// A single content file that was skipped, with where and why it failed
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Diagnostic {
+    /// Path of the offending file, relative to the content root
+    pub path: PathBuf,
+    /// 1-based line in the file, when the failure can be pinned down
+    pub line: Option<usize>,
+    /// 1-based column in the file, when the failure can be pinned down
+    pub column: Option<usize>,
+    pub reason: String,
+}
+
+impl Diagnostic {
+    pub fn new(path: impl Into<PathBuf>, reason: impl Into<String>) -> Self {
+        Self {
+            path: path.into(),
+            line: None,
+            column: None,
+            reason: reason.into(),
+        }
+    }
+
+    /// Describe `error` as a diagnostic for `path`, keeping its position when known
+    pub fn from_error(path: impl Into<PathBuf>, error: &ApplicationError) -> Self {
+        let path = path.into();
+        match error {
+            ApplicationError::FrontmatterError {
+                line,
+                column,
+                reason,
+                ..
+            } => Self {
+                path,
+                line: *line,
+                column: *column,
+                reason: reason.clone(),
+            },
+            ApplicationError::ParsingError { reason, .. }
+            | ApplicationError::PostCreationError { reason, .. } => Self::new(path, reason),
+            ApplicationError::ReadingError { source, .. } => Self::new(path, source.to_string()),
+            other => Self::new(path, other.to_string()),
+        }
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.path.display())?;
+        if let Some(line) = self.line {
+            write!(f, ":{line}")?;
+            if let Some(column) = self.column {
+                write!(f, ":{column}")?;
+            }
+        }
+        write!(f, ": {}", self.reason)
+    }
+}
+
+/// Report every post whose slug is already taken by a post earlier in path order
+///
+/// The database keeps the first post inserted for a slug, so these posts are not served.
+pub fn duplicate_slugs(posts: &BTreeMap<PathBuf, Post>) -> Vec<Diagnostic> {
+    let mut owners: HashMap<&str, &Path> = HashMap::new();
+    let mut diagnostics = Vec::new();
+    for (path, post) in posts {
+        let slug = post.markdown.slug.as_str();
+        match owners.get(slug) {
+            Some(owner) => diagnostics.push(Diagnostic::new(
+                path.clone(),
+                format!(
+                    "Duplicate slug `{slug}`, already used by {}",
+                    owner.display()
+                ),
+            )),
+            None => {
+                owners.insert(slug, path);
+            }
+        }
+    }
+    diagnostics
+}
+
+/// Print diagnostics to stderr, one per line
+pub fn log_diagnostics(diagnostics: &[Diagnostic]) {
+    for diagnostic in diagnostics {
+        eprintln!("Skipping post {diagnostic}");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_display_includes_position_when_known() {
+        let mut diagnostic = Diagnostic::new("posts/a.md", "broken");
+        assert_eq!(diagnostic.to_string(), "posts/a.md: broken");
+
+        diagnostic.line = Some(3);
+        diagnostic.column = Some(7);
+        assert_eq!(diagnostic.to_string(), "posts/a.md:3:7: broken");
+    }
+}
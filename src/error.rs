@@ -6,6 +6,14 @@ pub enum ApplicationError {
     #[error("Failed to parse post structure from {path}: {reason}")]
     ParsingError { path: PathBuf, reason: String },
 
+    #[error("Invalid frontmatter in {path}: {reason}")]
+    FrontmatterError {
+        path: PathBuf,
+        line: Option<usize>,
+        column: Option<usize>,
+        reason: String,
+    },
+
     #[error("Failed to read file at {path}: {source}")]
     ReadingError {
         path: PathBuf,
@@ -24,4 +32,16 @@ pub enum ApplicationError {
 
     #[error("Failed to clone repository: {0}")]
     GitError(#[from] git2::Error),
+
+    #[error("Failed to watch content directory: {0}")]
+    WatchError(#[from] notify::Error),
+
+    #[error("Invalid configuration in {origin}: {reason}")]
+    ConfigError { origin: String, reason: String },
+
+    #[error("Failed to render {page}: {reason}")]
+    RenderError { page: String, reason: String },
+
+    #[error("Content database error: {0}")]
+    SqliteError(#[from] rusqlite::Error),
 }
@@ -0,0 +1,379 @@
+/// Static export: renders the blog into a directory of files laid out like the server's URLs
+///
+/// Static hosts ignore query strings, so each listing is written as one page holding all of its
+/// posts, without sort links. Keyword search and feeds scoped to a tag or series need the server.
+use std::borrow::Cow;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use askama::Template;
+
+use crate::{
+    config::SiteConfig,
+    data::Post,
+    db::{Database, Listing},
+    error::ApplicationError,
+    feeds::{self, FeedScope},
+    http::{
+        assets::StaticAssets,
+        handlers::{
+            render_robots, render_sitemap, IndexTemplate, PostTemplate, PostsListTemplate,
+            ViewCount,
+        },
+    },
+    pagination::{PageLinks, PageRequest},
+    resources::{fingerprinted_path, list_files, strip_fingerprint},
+    snapshot::Snapshot,
+};
+
+/// What an export wrote
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ExportSummary {
+    /// HTML pages, feeds, the sitemap and robots.txt
+    pub pages: usize,
+    /// Asset files copied, fingerprinted copies not included
+    pub assets: usize,
+}
+
+/// Renders a snapshot into a directory, along with the assets its pages link to
+pub struct Export {
+    out_dir: PathBuf,
+    /// The site's own files, copied under `/static`
+    static_assets: StaticAssets,
+    /// Directories copied under a URL prefix; a later one wins when two provide the same URL
+    assets: Vec<(PathBuf, String)>,
+    /// Whether assets are also written under fingerprinted names for pages to link
+    fingerprinting: bool,
+}
+
+impl Export {
+    pub fn new(out_dir: impl Into<PathBuf>) -> Self {
+        Self {
+            out_dir: out_dir.into(),
+            static_assets: StaticAssets::default(),
+            assets: Vec::new(),
+            fingerprinting: true,
+        }
+    }
+
+    /// Copy these files under `/static` instead of the bundled ones
+    ///
+    /// Directories added with [`Export::with_assets`] win over them.
+    pub fn with_static_assets(mut self, static_assets: StaticAssets) -> Self {
+        self.static_assets = static_assets;
+        self
+    }
+
+    /// Copy every file under `dir` to `url_prefix`, e.g. `static` to `/static`
+    ///
+    /// Missing directories are skipped.
+    pub fn with_assets(mut self, dir: impl Into<PathBuf>, url_prefix: impl Into<String>) -> Self {
+        self.assets.push((dir.into(), url_prefix.into()));
+        self
+    }
+
+    /// Write assets under fingerprinted names too and link pages to those; on by default
+    ///
+    /// Without it every asset is written once under its plain name, which pages link instead,
+    /// for hosts that set their own cache headers.
+    pub fn with_fingerprinting(mut self, fingerprinting: bool) -> Self {
+        self.fingerprinting = fingerprinting;
+        self
+    }
+
+    /// Write the site
+    ///
+    /// # Arguments
+    ///
+    /// * `snapshot` - Content to export
+    /// * `site` - How the site presents itself
+    /// * `views` - View counts shown on post pages, by slug
+    ///
+    /// # Effects
+    /// - Writes every page served at `/a/b` to `a/b/index.html` and every other file under its
+    ///   own path, replacing files with the same names
+    /// - Writes every asset under its fingerprinted name too, and links pages to that name,
+    ///   unless fingerprinting is off
+    pub fn run(
+        &self,
+        snapshot: &Snapshot,
+        site: Arc<SiteConfig>,
+        views: &HashMap<String, u64>,
+    ) -> Result<ExportSummary, ApplicationError> {
+        let assets = self.collect_assets()?;
+        let links: HashMap<String, String> = assets
+            .iter()
+            .map(|(url, bytes)| {
+                let link = if self.fingerprinting {
+                    fingerprinted_path(url, bytes)
+                } else {
+                    url.clone()
+                };
+                (url.clone(), link)
+            })
+            .collect();
+
+        let files = render_files(snapshot, site, &self.static_assets, views)?;
+        for (path, text) in &files {
+            let text = rewrite_asset_urls(text, &links);
+            write_file(&self.out_dir.join(path), text.as_bytes())?;
+        }
+
+        for (url, bytes) in &assets {
+            write_file(&url_file(&self.out_dir, url), bytes)?;
+            if self.fingerprinting {
+                write_file(&url_file(&self.out_dir, &links[url]), bytes)?;
+            }
+        }
+
+        Ok(ExportSummary {
+            pages: files.len(),
+            assets: assets.len(),
+        })
+    }
+
+    /// Asset contents by URL
+    fn collect_assets(&self) -> Result<BTreeMap<String, Cow<'static, [u8]>>, ApplicationError> {
+        let mut assets = BTreeMap::new();
+        for path in self.static_assets.paths() {
+            if let Some(bytes) = self.static_assets.get(&path) {
+                assets.insert(format!("/static/{path}"), bytes);
+            }
+        }
+        for (dir, url_prefix) in &self.assets {
+            if !dir.is_dir() {
+                continue;
+            }
+            for path in list_files(dir)? {
+                let bytes = fs::read(dir.join(&path))?;
+                let url = format!("{}/{path}", url_prefix.trim_end_matches('/'));
+                assets.insert(url, Cow::Owned(bytes));
+            }
+        }
+        Ok(assets)
+    }
+}
+
+fn render_files(
+    snapshot: &Snapshot,
+    site: Arc<SiteConfig>,
+    assets: &StaticAssets,
+    views: &HashMap<String, u64>,
+) -> Result<Vec<(PathBuf, String)>, ApplicationError> {
+    let db = &snapshot.db;
+    let nav = snapshot.nav();
+    let mut files = Vec::new();
+    let mut add_page = |segments: &[&str], html: String| match page_file(segments) {
+        Some(path) => files.push((path, html)),
+        None => eprintln!("Skipping page {segments:?}: not a safe file name"),
+    };
+
+    let (page, pagination) = whole_listing(snapshot, &Listing::All);
+    let index = IndexTemplate {
+        site: site.clone(),
+        assets: assets.clone(),
+        pagination,
+        posts: page,
+        tags_with_count: nav.tags_with_count.clone(),
+        dates_by_year: nav.dates_by_year.clone(),
+    };
+    add_page(&[], render("/", &index)?);
+
+    for post in db.get_all_posts() {
+        let slug = post.markdown.slug.clone();
+        let route = format!("/posts/{slug}");
+        let template = PostTemplate {
+            site: site.clone(),
+            assets: assets.clone(),
+            view_count: ViewCount::Count(views.get(&slug).copied().unwrap_or(0)),
+            post,
+            preview: false,
+            tags_with_count: nav.tags_with_count.clone(),
+            dates_by_year: nav.dates_by_year.clone(),
+        };
+        add_page(&["posts", &slug], render(&route, &template)?);
+    }
+
+    let series: BTreeSet<String> = db
+        .get_all_posts()
+        .into_iter()
+        .filter_map(|post| post.markdown.series.map(|series| series.title))
+        .collect();
+    let mut listings: Vec<(Vec<String>, Listing)> = Vec::new();
+    for (tag, _) in &nav.tags_with_count {
+        listings.push((vec!["tags".into(), tag.clone()], Listing::Tag(tag.clone())));
+    }
+    for title in series {
+        listings.push((vec!["series".into(), title.clone()], Listing::Series(title)));
+    }
+    for (year, months) in &nav.dates_by_year {
+        let segments = vec!["archive".to_string(), year.to_string()];
+        for (_, month, _) in months {
+            let listing = Listing::Date {
+                year: *year,
+                month: Some(*month),
+            };
+            listings.push((
+                [segments.clone(), vec![month.to_string()]].concat(),
+                listing,
+            ));
+        }
+        let listing = Listing::Date {
+            year: *year,
+            month: None,
+        };
+        listings.push((segments, listing));
+    }
+    for (segments, listing) in listings {
+        let (posts, pagination) = whole_listing(snapshot, &listing);
+        let template = PostsListTemplate {
+            site: site.clone(),
+            assets: assets.clone(),
+            posts,
+            snippets: HashMap::new(),
+            pagination,
+            tags_with_count: nav.tags_with_count.clone(),
+            dates_by_year: nav.dates_by_year.clone(),
+        };
+        let segments: Vec<&str> = segments.iter().map(String::as_str).collect();
+        add_page(&segments, render(&listing.path(), &template)?);
+    }
+
+    files.push(("robots.txt".into(), render_robots(&site)));
+    files.push(("sitemap.xml".into(), render_sitemap(&site, db)));
+    let posts = feeds::scoped_posts(db, &FeedScope::All).unwrap_or_default();
+    files.push((
+        "feed.atom".into(),
+        feeds::atom(&site, &FeedScope::All, &posts),
+    ));
+    files.push((
+        "feed.rss".into(),
+        feeds::rss(&site, &FeedScope::All, &posts),
+    ));
+    files.push((
+        "feed.json".into(),
+        feeds::json_feed(&site, &FeedScope::All, &posts),
+    ));
+    Ok(files)
+}
+
+/// All posts of `listing` in its default order, with links for a single page and no sorting
+fn whole_listing(snapshot: &Snapshot, listing: &Listing) -> (Vec<Post>, PageLinks) {
+    let sorts = listing.sorts();
+    let request = PageRequest {
+        page: 1,
+        per_page: usize::MAX,
+        sort: sorts[0],
+    };
+    let page = snapshot.db.get_page(listing, &request, &HashMap::new());
+    let mut pagination = PageLinks::new(&page, &listing.path(), &[], sorts);
+    pagination.sorts.clear();
+    (page.items, pagination)
+}
+
+/// `index.html` inside the directory of a page's route, unless a segment could escape it
+fn page_file(segments: &[&str]) -> Option<PathBuf> {
+    let mut path = PathBuf::new();
+    for segment in segments {
+        if segment.is_empty()
+            || *segment == "."
+            || *segment == ".."
+            || segment.contains(['/', '\\'])
+        {
+            return None;
+        }
+        path.push(segment);
+    }
+    Some(path.join("index.html"))
+}
+
+fn url_file(out_dir: &Path, url: &str) -> PathBuf {
+    let mut path = out_dir.to_path_buf();
+    path.extend(url.split('/').filter(|segment| !segment.is_empty()));
+    path
+}
+
+/// Point every reference to an asset at the URL it is exported under, by its plain URL in
+/// `links`, absolute URLs included
+///
+/// Pages already link fingerprinted URLs, but those name what the server would serve, which
+/// an asset directory of the export may have replaced, or which isn't written at all when
+/// fingerprinting is off.
+fn rewrite_asset_urls(text: &str, links: &HashMap<String, String>) -> String {
+    let mut output = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find("/static/") {
+        let (before, from) = rest.split_at(start);
+        output.push_str(before);
+        let end = from
+            .find(|ch: char| {
+                ch.is_whitespace()
+                    || matches!(ch, '"' | '\'' | '(' | ')' | '<' | '>' | '?' | '#' | '&')
+            })
+            .unwrap_or(from.len());
+        let url = &from[..end];
+        let link = links.get(url).or_else(|| {
+            let original = strip_fingerprint(url)?;
+            links.get(&original)
+        });
+        output.push_str(link.map_or(url, String::as_str));
+        rest = &from[end..];
+    }
+    output.push_str(rest);
+    output
+}
+
+fn render(route: &str, template: &impl Template) -> Result<String, ApplicationError> {
+    template
+        .render()
+        .map_err(|err| ApplicationError::RenderError {
+            page: route.to_string(),
+            reason: err.to_string(),
+        })
+}
+
+fn write_file(path: &Path, contents: &[u8]) -> Result<(), ApplicationError> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(path, contents)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_only_whole_asset_urls_are_rewritten() {
+        let hashed = HashMap::from([(
+            "/static/style.css".to_string(),
+            fingerprinted_path("/static/style.css", b"body {}"),
+        )]);
+        let hashed_url = &hashed["/static/style.css"];
+        let stale_url = fingerprinted_path("/static/style.css", b"body { color: red }");
+
+        let html = format!(
+            r#"<link href="/static/style.css"><link href="{stale_url}"><a href="/static/style.css.map">"#
+        );
+        assert_eq!(
+            rewrite_asset_urls(&html, &hashed),
+            format!(
+                r#"<link href="{hashed_url}"><link href="{hashed_url}"><a href="/static/style.css.map">"#
+            )
+        );
+    }
+
+    #[test]
+    fn test_pages_cannot_leave_the_output() {
+        assert_eq!(
+            page_file(&["tags", "rust"]),
+            Some(PathBuf::from("tags/rust/index.html"))
+        );
+        assert_eq!(page_file(&[]), Some(PathBuf::from("index.html")));
+        assert_eq!(page_file(&["posts", ".."]), None);
+        assert_eq!(page_file(&["tags", "a/b"]), None);
+    }
+}
@@ -0,0 +1,355 @@
+/// Syndication feed builders (Atom, RSS 2.0 and JSON Feed) over parsed posts
+use chrono::NaiveDate;
+use serde::Serialize;
+
+use crate::config::SiteConfig;
+use crate::data::Post;
+use crate::db::{Database, InMemDatabase, Listing};
+use crate::url::url_encode;
+
+/// Maximum number of entries emitted in a single feed
+pub const FEED_LENGTH: usize = 20;
+
+pub const ATOM_CONTENT_TYPE: &str = "application/atom+xml; charset=utf-8";
+pub const RSS_CONTENT_TYPE: &str = "application/rss+xml; charset=utf-8";
+pub const JSON_FEED_CONTENT_TYPE: &str = "application/feed+json; charset=utf-8";
+
+/// Describes which slice of the blog a feed covers
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FeedScope {
+    All,
+    Tag(String),
+    Series(String),
+}
+
+impl FeedScope {
+    pub fn title(&self, site: &SiteConfig) -> String {
+        let title = &site.title;
+        match self {
+            FeedScope::All => title.clone(),
+            FeedScope::Tag(tag) => format!("{title} - posts tagged {tag}"),
+            FeedScope::Series(series) => format!("{title} - {series}"),
+        }
+    }
+
+    /// HTML page listing the same posts as the feed
+    pub fn html_url(&self, site: &SiteConfig) -> String {
+        site.url(&match self {
+            FeedScope::All => "/".to_string(),
+            FeedScope::Tag(tag) => Listing::Tag(tag.clone()).path(),
+            FeedScope::Series(series) => Listing::Series(series.clone()).path(),
+        })
+    }
+
+    /// Absolute URL of the feed itself for the given extension (`atom`, `rss` or `json`)
+    pub fn feed_url(&self, site: &SiteConfig, extension: &str) -> String {
+        site.url(&match self {
+            FeedScope::All => format!("/feed.{extension}"),
+            FeedScope::Tag(tag) => format!("/feed.{extension}?tag={}", url_encode(tag)),
+            FeedScope::Series(series) => {
+                format!("/feed.{extension}?series={}", url_encode(series))
+            }
+        })
+    }
+}
+
+/// The newest posts in `scope`, at most [`FEED_LENGTH`] of them
+///
+/// # Returns
+/// `None` for a tag or series without posts, which has no feed
+pub fn scoped_posts(db: &InMemDatabase, scope: &FeedScope) -> Option<Vec<Post>> {
+    let mut posts = match scope {
+        FeedScope::All => db.get_last_n_posts(FEED_LENGTH),
+        FeedScope::Tag(tag) => db.get_by_tag(tag.clone()),
+        FeedScope::Series(series) => db.get_by_series(series.clone()),
+    };
+    if *scope != FeedScope::All && posts.is_empty() {
+        return None;
+    }
+    posts.sort_by_key(|post| std::cmp::Reverse(post.markdown.date));
+    posts.truncate(FEED_LENGTH);
+    Some(posts)
+}
+
+pub fn atom(site: &SiteConfig, scope: &FeedScope, posts: &[Post]) -> String {
+    let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    xml.push_str("<feed xmlns=\"http://www.w3.org/2005/Atom\">\n");
+    xml.push_str(&format!(
+        "  <title>{}</title>\n",
+        escape_xml(&scope.title(site))
+    ));
+    xml.push_str(&format!(
+        "  <subtitle>{}</subtitle>\n",
+        escape_xml(&site.description)
+    ));
+    xml.push_str(&format!(
+        "  <id>{}</id>\n",
+        escape_xml(&scope.feed_url(site, "atom"))
+    ));
+    xml.push_str(&format!(
+        "  <link rel=\"self\" type=\"application/atom+xml\" href=\"{}\"/>\n",
+        escape_xml(&scope.feed_url(site, "atom"))
+    ));
+    xml.push_str(&format!(
+        "  <link rel=\"alternate\" type=\"text/html\" href=\"{}\"/>\n",
+        escape_xml(&scope.html_url(site))
+    ));
+    xml.push_str(&format!(
+        "  <updated>{}</updated>\n",
+        rfc3339(last_updated(posts))
+    ));
+    xml.push_str(&format!(
+        "  <author><name>{}</name></author>\n",
+        escape_xml(&site.author)
+    ));
+
+    for post in posts {
+        let url = post_url(site, post);
+        xml.push_str("  <entry>\n");
+        xml.push_str(&format!(
+            "    <title>{}</title>\n",
+            escape_xml(&post.markdown.title)
+        ));
+        xml.push_str(&format!("    <id>{}</id>\n", escape_xml(&url)));
+        xml.push_str(&format!(
+            "    <link rel=\"alternate\" type=\"text/html\" href=\"{}\"/>\n",
+            escape_xml(&url)
+        ));
+        xml.push_str(&format!(
+            "    <published>{}</published>\n",
+            rfc3339(post.markdown.date)
+        ));
+        xml.push_str(&format!(
+            "    <updated>{}</updated>\n",
+            rfc3339(post.markdown.date)
+        ));
+        for tag in &post.markdown.tags {
+            xml.push_str(&format!("    <category term=\"{}\"/>\n", escape_xml(tag)));
+        }
+        xml.push_str(&format!(
+            "    <summary>{}</summary>\n",
+            escape_xml(&post.markdown.description)
+        ));
+        xml.push_str(&format!(
+            "    <content type=\"html\">{}</content>\n",
+            escape_xml(&post.data)
+        ));
+        xml.push_str("  </entry>\n");
+    }
+
+    xml.push_str("</feed>");
+    xml
+}
+
+pub fn rss(site: &SiteConfig, scope: &FeedScope, posts: &[Post]) -> String {
+    let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    xml.push_str("<rss version=\"2.0\" xmlns:atom=\"http://www.w3.org/2005/Atom\" xmlns:content=\"http://purl.org/rss/1.0/modules/content/\">\n");
+    xml.push_str("  <channel>\n");
+    xml.push_str(&format!(
+        "    <title>{}</title>\n",
+        escape_xml(&scope.title(site))
+    ));
+    xml.push_str(&format!(
+        "    <link>{}</link>\n",
+        escape_xml(&scope.html_url(site))
+    ));
+    xml.push_str(&format!(
+        "    <description>{}</description>\n",
+        escape_xml(&site.description)
+    ));
+    xml.push_str("    <language>en-us</language>\n");
+    xml.push_str(&format!(
+        "    <atom:link href=\"{}\" rel=\"self\" type=\"application/rss+xml\"/>\n",
+        escape_xml(&scope.feed_url(site, "rss"))
+    ));
+    xml.push_str(&format!(
+        "    <lastBuildDate>{}</lastBuildDate>\n",
+        rfc2822(last_updated(posts))
+    ));
+
+    for post in posts {
+        let url = post_url(site, post);
+        xml.push_str("    <item>\n");
+        xml.push_str(&format!(
+            "      <title>{}</title>\n",
+            escape_xml(&post.markdown.title)
+        ));
+        xml.push_str(&format!("      <link>{}</link>\n", escape_xml(&url)));
+        xml.push_str(&format!(
+            "      <guid isPermaLink=\"true\">{}</guid>\n",
+            escape_xml(&url)
+        ));
+        xml.push_str(&format!(
+            "      <pubDate>{}</pubDate>\n",
+            rfc2822(post.markdown.date)
+        ));
+        for tag in &post.markdown.tags {
+            xml.push_str(&format!("      <category>{}</category>\n", escape_xml(tag)));
+        }
+        xml.push_str(&format!(
+            "      <description>{}</description>\n",
+            escape_xml(&post.markdown.description)
+        ));
+        xml.push_str(&format!(
+            "      <content:encoded>{}</content:encoded>\n",
+            escape_xml(&post.data)
+        ));
+        xml.push_str("    </item>\n");
+    }
+
+    xml.push_str("  </channel>\n");
+    xml.push_str("</rss>");
+    xml
+}
+
+#[derive(Serialize)]
+struct JsonFeed<'a> {
+    version: &'static str,
+    title: String,
+    home_page_url: String,
+    feed_url: String,
+    description: &'a str,
+    language: &'static str,
+    authors: Vec<JsonFeedAuthor<'a>>,
+    items: Vec<JsonFeedItem<'a>>,
+}
+
+#[derive(Serialize)]
+struct JsonFeedAuthor<'a> {
+    name: &'a str,
+}
+
+#[derive(Serialize)]
+struct JsonFeedItem<'a> {
+    id: String,
+    url: String,
+    title: &'a str,
+    summary: &'a str,
+    content_html: &'a str,
+    date_published: String,
+    tags: &'a [String],
+}
+
+pub fn json_feed(site: &SiteConfig, scope: &FeedScope, posts: &[Post]) -> String {
+    let feed = JsonFeed {
+        version: "https://jsonfeed.org/version/1.1",
+        title: scope.title(site),
+        home_page_url: scope.html_url(site),
+        feed_url: scope.feed_url(site, "json"),
+        description: &site.description,
+        language: "en-US",
+        authors: vec![JsonFeedAuthor { name: &site.author }],
+        items: posts
+            .iter()
+            .map(|post| JsonFeedItem {
+                id: post_url(site, post),
+                url: post_url(site, post),
+                title: &post.markdown.title,
+                summary: &post.markdown.description,
+                content_html: &post.data,
+                date_published: rfc3339(post.markdown.date),
+                tags: &post.markdown.tags,
+            })
+            .collect(),
+    };
+
+    serde_json::to_string_pretty(&feed).unwrap_or_default()
+}
+
+fn post_url(site: &SiteConfig, post: &Post) -> String {
+    site.url(&format!("/posts/{}", post.markdown.slug))
+}
+
+fn last_updated(posts: &[Post]) -> NaiveDate {
+    posts
+        .iter()
+        .map(|post| post.markdown.date)
+        .max()
+        .unwrap_or_default()
+}
+
+fn rfc3339(date: NaiveDate) -> String {
+    format!("{}T00:00:00Z", date.format("%Y-%m-%d"))
+}
+
+fn rfc2822(date: NaiveDate) -> String {
+    date.and_hms_opt(0, 0, 0)
+        .unwrap_or_default()
+        .and_utc()
+        .to_rfc2822()
+}
+
+fn escape_xml(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for ch in value.chars() {
+        match ch {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            _ => escaped.push(ch),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::{Markdown, Series};
+
+    fn sample_post() -> Post {
+        Post {
+            data: "<p>Fish & chips</p>".to_string(),
+            markdown: Markdown {
+                title: "Rust <3".to_string(),
+                description: "A short description".to_string(),
+                slug: "rust-love".to_string(),
+                tags: vec!["rust".to_string()],
+                date: NaiveDate::from_ymd_opt(2026, 2, 7).unwrap(),
+                series: Some(Series {
+                    title: "Intro to Rust".to_string(),
+                    ep: 1,
+                }),
+                draft: false,
+            },
+        }
+    }
+
+    #[test]
+    fn test_atom_escapes_content() {
+        let xml = atom(&SiteConfig::default(), &FeedScope::All, &[sample_post()]);
+        assert!(xml.contains("<title>Rust &lt;3</title>"));
+        assert!(xml.contains("&lt;p&gt;Fish &amp; chips&lt;/p&gt;"));
+        assert!(xml.contains("<updated>2026-02-07T00:00:00Z</updated>"));
+        assert!(xml.contains("<id>https://rwd.works/posts/rust-love</id>"));
+    }
+
+    #[test]
+    fn test_rss_uses_rfc2822_dates() {
+        let xml = rss(
+            &SiteConfig::default(),
+            &FeedScope::Tag("rust".to_string()),
+            &[sample_post()],
+        );
+        assert!(xml.contains("<pubDate>Sat, 7 Feb 2026 00:00:00 +0000</pubDate>"));
+        assert!(xml.contains("https://rwd.works/feed.rss?tag=rust"));
+    }
+
+    #[test]
+    fn test_json_feed_structure() {
+        let feed = json_feed(
+            &SiteConfig::default(),
+            &FeedScope::Series("Intro to Rust".to_string()),
+            &[sample_post()],
+        );
+        let value: serde_json::Value = serde_json::from_str(&feed).unwrap();
+        assert_eq!(value["version"], "https://jsonfeed.org/version/1.1");
+        assert_eq!(
+            value["feed_url"],
+            "https://rwd.works/feed.json?series=Intro%20to%20Rust"
+        );
+        assert_eq!(value["items"][0]["content_html"], "<p>Fish & chips</p>");
+    }
+}
@@ -0,0 +1,149 @@
+/// Ingest-time syntax highlighting of fenced code blocks into class-based spans
+use std::sync::LazyLock;
+
+use syntect::{
+    highlighting::ThemeSet,
+    html::{css_for_theme_with_class_style, ClassStyle, ClassedHTMLGenerator},
+    parsing::{SyntaxReference, SyntaxSet},
+    util::LinesWithEndings,
+};
+
+/// Prefix of every generated class, keeping them clear of Tailwind and site styles
+const CLASS_STYLE: ClassStyle = ClassStyle::SpacedPrefixed { prefix: "hl-" };
+
+/// Theme used while the page is light
+pub const LIGHT_THEME: &str = "InspiredGitHub";
+/// Theme used while the `dark` class is set on the document
+pub const DARK_THEME: &str = "base16-ocean.dark";
+
+static SYNTAXES: LazyLock<SyntaxSet> = LazyLock::new(SyntaxSet::load_defaults_newlines);
+
+/// Render a code block as `<pre><code>` with highlighted spans
+///
+/// # Arguments
+/// * `code` - The raw contents of the block
+/// * `info` - The fence info string, e.g. `rust` or `rust,ignore`; may be empty
+///
+/// # Returns
+/// HTML for the block. Unknown languages fall back to escaped plain text.
+pub fn highlight_code_block(code: &str, info: &str) -> String {
+    let language = info
+        .split(|c: char| c == ',' || c.is_whitespace())
+        .next()
+        .unwrap_or_default();
+    let syntax = find_syntax(language);
+
+    let mut generator = ClassedHTMLGenerator::new_with_class_style(syntax, &SYNTAXES, CLASS_STYLE);
+    for line in LinesWithEndings::from(code) {
+        if generator
+            .parse_html_for_line_which_includes_newline(line)
+            .is_err()
+        {
+            return plain_code_block(code, language);
+        }
+    }
+
+    format!(
+        "<pre><code class=\"hl-code{}\">{}</code></pre>\n",
+        language_class(language),
+        generator.finalize()
+    )
+}
+
+fn find_syntax(language: &str) -> &'static SyntaxReference {
+    if language.is_empty() {
+        return SYNTAXES.find_syntax_plain_text();
+    }
+    SYNTAXES
+        .find_syntax_by_token(language)
+        .unwrap_or_else(|| SYNTAXES.find_syntax_plain_text())
+}
+
+fn plain_code_block(code: &str, language: &str) -> String {
+    format!(
+        "<pre><code class=\"hl-code{}\">{}</code></pre>\n",
+        language_class(language),
+        escape_html(code)
+    )
+}
+
+fn language_class(language: &str) -> String {
+    let language: String = language
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '+' | '#'))
+        .collect();
+    if language.is_empty() {
+        String::new()
+    } else {
+        format!(" language-{language}")
+    }
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Stylesheet for the highlighted spans, shipped as `static/highlight.css`
+///
+/// Light rules apply while the document lacks the `dark` class and dark rules while it
+/// has it, following the site's theme toggle rather than the OS preference.
+pub fn theme_css() -> String {
+    let themes = ThemeSet::load_defaults();
+    let mut css =
+        String::from("/* Generated by personal::highlight::theme_css, do not edit by hand */\n\n");
+    for (name, scope) in [(LIGHT_THEME, ":root:not(.dark)"), (DARK_THEME, ".dark")] {
+        let theme_css = css_for_theme_with_class_style(&themes.themes[name], CLASS_STYLE)
+            .expect("bundled themes produce valid CSS");
+        css.push_str(&scope_selectors(&theme_css, scope));
+        css.push('\n');
+    }
+    css
+}
+
+/// Prefix every selector of the rules in `css` with `scope`
+fn scope_selectors(css: &str, scope: &str) -> String {
+    css.lines()
+        .map(|line| match line.strip_suffix(" {") {
+            Some(selectors) => {
+                let scoped: Vec<String> = selectors
+                    .split(", ")
+                    .map(|selector| format!("{scope} {selector}"))
+                    .collect();
+                format!("{} {{\n", scoped.join(", "))
+            }
+            None => format!("{line}\n"),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_highlights_known_language() {
+        let html = highlight_code_block("fn main() {}\n", "rust,ignore");
+        assert!(html.starts_with("<pre><code class=\"hl-code language-rust\">"));
+        assert!(html.contains("<span class=\"hl-"));
+    }
+
+    #[test]
+    fn test_unknown_language_is_escaped_plain_text() {
+        let html = highlight_code_block("<b>&</b>\n", "not-a-language");
+        assert!(html.contains("&lt;b&gt;&amp;&lt;/b&gt;"));
+        assert!(!html.contains("<b>"));
+    }
+
+    #[test]
+    fn test_bundled_stylesheet_is_up_to_date() {
+        let bundled = include_str!("../static/highlight.css");
+        assert_eq!(
+            bundled,
+            theme_css(),
+            "regenerate with `cargo run --example highlight_css > static/highlight.css`"
+        );
+    }
+}
@@ -0,0 +1,517 @@
+/// Admin endpoints and the push webhook receiver for on-demand content reloads
+use axum::{
+    body::Bytes,
+    extract::{Path, Query, State},
+    http::{header, HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+    routing::{get, post},
+    Json, Router,
+};
+use base64::{prelude::BASE64_STANDARD, Engine};
+use chrono::{Duration, NaiveDate};
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+
+use crate::{
+    config::AdminConfig,
+    db::Database,
+    diagnostics::Diagnostic,
+    http::{caching::CachePolicy, dashboard, state::AppState},
+    reload::ReloadTrigger,
+    view_history::{Mover, SourceBreakdown},
+};
+
+/// Period covered by the view endpoints when `days` is not given
+const DEFAULT_VIEW_DAYS: u32 = 7;
+/// Longest period the view endpoints accept
+const MAX_VIEW_DAYS: u32 = 366;
+
+/// Secrets guarding the admin surface; each endpoint is disabled while its secret is unset
+#[derive(Debug, Clone, Default)]
+pub struct AdminCredentials {
+    /// Bearer token expected by `/admin/*`
+    pub token: Option<String>,
+    /// Basic auth user name accepted by `/admin/*` together with `password`
+    pub username: Option<String>,
+    pub password: Option<String>,
+    /// Shared secret used to sign push webhook payloads
+    pub webhook_secret: Option<String>,
+    /// Key deriving the per-post tokens of draft preview links
+    pub preview_secret: Option<String>,
+}
+
+impl AdminCredentials {
+    /// The secrets of the `[admin]` section, environment overrides included
+    pub fn from_config(config: &AdminConfig) -> Self {
+        Self {
+            token: config.token.clone(),
+            username: config.username.clone(),
+            password: config.password.clone(),
+            webhook_secret: config.webhook_secret.clone(),
+            preview_secret: config.preview_secret.clone(),
+        }
+    }
+
+    /// Whether basic auth is configured, so browsers can be asked to log in
+    pub fn has_basic_auth(&self) -> bool {
+        self.username.is_some() && self.password.is_some()
+    }
+
+    /// Check an `Authorization` header value against the token or the basic auth login
+    fn accepts(&self, authorization: &str) -> bool {
+        if let Some(provided) = authorization.strip_prefix("Bearer ") {
+            return self
+                .token
+                .as_ref()
+                .is_some_and(|token| constant_time_eq(provided.as_bytes(), token.as_bytes()));
+        }
+        let (Some(username), Some(password)) = (&self.username, &self.password) else {
+            return false;
+        };
+        let Some(encoded) = authorization.strip_prefix("Basic ") else {
+            return false;
+        };
+        let Ok(decoded) = BASE64_STANDARD.decode(encoded.trim()) else {
+            return false;
+        };
+        let expected = format!("{username}:{password}");
+        constant_time_eq(&decoded, expected.as_bytes())
+    }
+}
+
+/// Post files skipped while loading the served content
+#[derive(Serialize)]
+pub struct DiagnosticsReport {
+    pub commit: Option<String>,
+    pub diagnostics: Vec<Diagnostic>,
+    /// Post files that fail to parse but are still served in their previous version
+    pub stale: Vec<Diagnostic>,
+}
+
+/// A draft or scheduled post with its secret preview link
+#[derive(Serialize)]
+pub struct PreviewLink {
+    pub slug: String,
+    pub title: String,
+    pub date: NaiveDate,
+    pub draft: bool,
+    pub url: String,
+}
+
+/// Where the views of the requested period came from, overall and per post
+#[derive(Serialize)]
+pub struct SourceStats {
+    pub from: NaiveDate,
+    pub to: NaiveDate,
+    pub totals: SourceBreakdown,
+    /// Most viewed first
+    pub posts: Vec<PostSources>,
+}
+
+#[derive(Serialize)]
+pub struct PostSources {
+    pub slug: String,
+    pub views: u64,
+    pub raw: u64,
+    pub sources: SourceBreakdown,
+}
+
+/// Period of a view statistics query, ending today
+#[derive(Deserialize)]
+pub struct ViewsQuery {
+    /// Number of days to cover, 7 by default and at most 366
+    pub days: Option<u32>,
+    /// Maximum number of posts to return
+    pub limit: Option<usize>,
+}
+
+impl ViewsQuery {
+    pub(crate) fn days(&self) -> u32 {
+        self.days
+            .unwrap_or(DEFAULT_VIEW_DAYS)
+            .clamp(1, MAX_VIEW_DAYS)
+    }
+}
+
+/// Views of a single post over the requested period
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct PostViews {
+    pub slug: String,
+    pub views: u64,
+    /// Requests before bots and repeat visits were filtered out
+    pub raw: u64,
+}
+
+/// Views of a post on one day
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct DailyViews {
+    pub date: NaiveDate,
+    pub views: u64,
+    pub raw: u64,
+}
+
+#[derive(Deserialize)]
+struct PushPayload {
+    #[serde(rename = "ref")]
+    git_ref: Option<String>,
+}
+
+pub fn router() -> Router<AppState> {
+    Router::new()
+        .route("/admin/reload", post(admin_reload))
+        .route("/admin/reload/status", get(admin_reload_status))
+        .route("/admin/diagnostics", get(admin_diagnostics))
+        .route("/admin/previews", get(admin_previews))
+        .route("/admin", get(dashboard::admin_dashboard))
+        .route("/admin/stats", get(admin_stats))
+        .route("/admin/views", get(admin_views))
+        .route("/admin/views/movers", get(admin_view_movers))
+        .route("/admin/views/{slug}", get(admin_post_views))
+        .route("/webhooks/push", post(push_webhook))
+        .layer(CachePolicy::NoStore.layer())
+}
+
+pub async fn admin_reload(State(state): State<AppState>, headers: HeaderMap) -> Response {
+    if let Err(status) = authorize(&state, &headers) {
+        return status.into_response();
+    }
+    let Some(reloader) = &state.reloader else {
+        return StatusCode::SERVICE_UNAVAILABLE.into_response();
+    };
+
+    reloader.request(ReloadTrigger::Admin);
+    (StatusCode::ACCEPTED, Json(reloader.status())).into_response()
+}
+
+pub async fn admin_reload_status(State(state): State<AppState>, headers: HeaderMap) -> Response {
+    if let Err(status) = authorize(&state, &headers) {
+        return status.into_response();
+    }
+    let Some(reloader) = &state.reloader else {
+        return StatusCode::SERVICE_UNAVAILABLE.into_response();
+    };
+
+    Json(reloader.status()).into_response()
+}
+
+pub async fn admin_diagnostics(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<Json<DiagnosticsReport>, StatusCode> {
+    authorize(&state, &headers)?;
+
+    let snapshot = state.snapshot();
+    Ok(Json(DiagnosticsReport {
+        commit: snapshot.commit.clone(),
+        diagnostics: snapshot.diagnostics.clone(),
+        stale: snapshot.stale.clone(),
+    }))
+}
+
+/// Preview links for every post that is not public yet; 404 while no preview secret is set
+pub async fn admin_previews(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<Json<Vec<PreviewLink>>, StatusCode> {
+    authorize(&state, &headers)?;
+    let secret = state
+        .admin
+        .preview_secret
+        .as_ref()
+        .ok_or(StatusCode::NOT_FOUND)?;
+
+    let posts = state.snapshot().db.get_unpublished_posts();
+    let links = posts
+        .into_iter()
+        .map(|post| PreviewLink {
+            url: format!(
+                "/preview/{}?token={}",
+                post.markdown.slug,
+                preview_token(secret.as_bytes(), &post.markdown.slug)
+            ),
+            slug: post.markdown.slug,
+            title: post.markdown.title,
+            date: post.markdown.date,
+            draft: post.markdown.draft,
+        })
+        .collect();
+    Ok(Json(links))
+}
+
+/// Referrer hosts and utm parameters of the views in the last `days` days
+pub async fn admin_stats(
+    Query(params): Query<ViewsQuery>,
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<Json<SourceStats>, StatusCode> {
+    authorize(&state, &headers)?;
+
+    let views = state
+        .views
+        .lock()
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    let to = views.today();
+    let from = to - Duration::days(params.days() as i64 - 1);
+    let history = views.history();
+
+    let mut sources = history.sources_by_post(from, to);
+    let mut posts: Vec<PostSources> = history
+        .range(from, to)
+        .into_iter()
+        .filter(|(_, count)| count.views > 0)
+        .map(|(slug, count)| PostSources {
+            sources: sources.remove(&slug).unwrap_or_default(),
+            slug,
+            views: count.views,
+            raw: count.raw,
+        })
+        .collect();
+    posts.sort_by(|a, b| b.views.cmp(&a.views).then_with(|| a.slug.cmp(&b.slug)));
+    if let Some(limit) = params.limit {
+        posts.truncate(limit);
+    }
+
+    Ok(Json(SourceStats {
+        from,
+        to,
+        totals: history.sources(from, to),
+        posts,
+    }))
+}
+
+/// Receives GitHub (`X-Hub-Signature-256`) and Gitea (`X-Gitea-Signature`) push events
+pub async fn push_webhook(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Response {
+    let Some(secret) = &state.admin.webhook_secret else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+
+    let signature = header_str(&headers, "x-hub-signature-256")
+        .and_then(|value| value.strip_prefix("sha256="))
+        .or_else(|| header_str(&headers, "x-gitea-signature"));
+    let Some(signature) = signature else {
+        return StatusCode::UNAUTHORIZED.into_response();
+    };
+    if !verify_signature(secret.as_bytes(), &body, signature) {
+        return StatusCode::UNAUTHORIZED.into_response();
+    }
+
+    let event =
+        header_str(&headers, "x-github-event").or_else(|| header_str(&headers, "x-gitea-event"));
+    if event.is_some_and(|event| event != "push") {
+        return (StatusCode::OK, "ignored: not a push event").into_response();
+    }
+
+    let Some(reloader) = &state.reloader else {
+        return StatusCode::SERVICE_UNAVAILABLE.into_response();
+    };
+
+    let pushed_ref = serde_json::from_slice::<PushPayload>(&body)
+        .ok()
+        .and_then(|payload| payload.git_ref);
+    if let (Some(pushed_ref), Some(branch)) = (pushed_ref, reloader.status().branch) {
+        if pushed_ref != format!("refs/heads/{branch}") {
+            return (StatusCode::OK, "ignored: push to an untracked branch").into_response();
+        }
+    }
+
+    reloader.request(ReloadTrigger::Webhook);
+    (StatusCode::ACCEPTED, Json(reloader.status())).into_response()
+}
+
+/// Posts viewed in the last `days` days, most viewed first
+pub async fn admin_views(
+    Query(params): Query<ViewsQuery>,
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<Json<Vec<PostViews>>, StatusCode> {
+    authorize(&state, &headers)?;
+
+    let views = state
+        .views
+        .lock()
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    let today = views.today();
+    let from = today - Duration::days(params.days() as i64 - 1);
+
+    let mut posts: Vec<PostViews> = views
+        .history()
+        .range(from, today)
+        .into_iter()
+        .map(|(slug, count)| PostViews {
+            slug,
+            views: count.views,
+            raw: count.raw,
+        })
+        .collect();
+    posts.sort_by(|a, b| b.views.cmp(&a.views).then_with(|| a.slug.cmp(&b.slug)));
+    if let Some(limit) = params.limit {
+        posts.truncate(limit);
+    }
+    Ok(Json(posts))
+}
+
+/// Posts whose views changed the most against the `days` days before, biggest gain first
+pub async fn admin_view_movers(
+    Query(params): Query<ViewsQuery>,
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<Json<Vec<Mover>>, StatusCode> {
+    authorize(&state, &headers)?;
+
+    let views = state
+        .views
+        .lock()
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    let limit = params.limit.unwrap_or(10);
+    Ok(Json(views.history().movers(
+        views.today(),
+        params.days(),
+        limit,
+    )))
+}
+
+/// Views of a post on each of the last `days` days, oldest first; 404 for unknown slugs
+pub async fn admin_post_views(
+    Path(slug): Path<String>,
+    Query(params): Query<ViewsQuery>,
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<Json<Vec<DailyViews>>, StatusCode> {
+    authorize(&state, &headers)?;
+
+    state
+        .snapshot()
+        .db
+        .get_by_slug(slug.clone())
+        .ok_or(StatusCode::NOT_FOUND)?;
+    let views = state
+        .views
+        .lock()
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    let today = views.today();
+    let from = today - Duration::days(params.days() as i64 - 1);
+
+    let days = views
+        .history()
+        .daily(&slug, from, today)
+        .into_iter()
+        .map(|(date, count)| DailyViews {
+            date,
+            views: count.views,
+            raw: count.raw,
+        })
+        .collect();
+    Ok(Json(days))
+}
+
+/// Admit requests carrying the admin token or basic auth login; 404 while neither is set
+pub(crate) fn authorize(state: &AppState, headers: &HeaderMap) -> Result<(), StatusCode> {
+    if state.admin.token.is_none() && !state.admin.has_basic_auth() {
+        return Err(StatusCode::NOT_FOUND);
+    }
+    let provided =
+        header_str(headers, header::AUTHORIZATION.as_str()).ok_or(StatusCode::UNAUTHORIZED)?;
+
+    if state.admin.accepts(provided) {
+        Ok(())
+    } else {
+        Err(StatusCode::UNAUTHORIZED)
+    }
+}
+
+/// Check a hex-encoded HMAC-SHA256 of `body` in constant time
+pub fn verify_signature(secret: &[u8], body: &[u8], signature_hex: &str) -> bool {
+    let Ok(signature) = hex::decode(signature_hex.trim()) else {
+        return false;
+    };
+    let Ok(mut mac) = Hmac::<Sha256>::new_from_slice(secret) else {
+        return false;
+    };
+    mac.update(body);
+    mac.verify_slice(&signature).is_ok()
+}
+
+/// Token granting access to the preview of `slug`: a hex HMAC-SHA256 of the slug
+pub fn preview_token(secret: &[u8], slug: &str) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret).expect("HMAC accepts keys of any size");
+    mac.update(slug.as_bytes());
+    hex::encode(mac.finalize().into_bytes())
+}
+
+fn header_str<'a>(headers: &'a HeaderMap, name: &str) -> Option<&'a str> {
+    headers.get(name).and_then(|value| value.to_str().ok())
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_verify_signature() {
+        // Reference value from GitHub's webhook validation documentation
+        let signature = "757107ea0eb2509fc211221cce984b8a37570b6d7586c22c46f4379c8b043e17";
+        assert!(verify_signature(
+            b"It's a Secret to Everybody",
+            b"Hello, World!",
+            signature
+        ));
+        assert!(!verify_signature(
+            b"wrong secret",
+            b"Hello, World!",
+            signature
+        ));
+        assert!(!verify_signature(
+            b"It's a Secret to Everybody",
+            b"Hello, World!",
+            "zz"
+        ));
+    }
+
+    #[test]
+    fn test_token_and_basic_auth_are_accepted() {
+        let admin = AdminCredentials {
+            token: Some("token".to_string()),
+            username: Some("editor".to_string()),
+            password: Some("hunter2".to_string()),
+            ..AdminCredentials::default()
+        };
+        assert!(admin.accepts("Bearer token"));
+        assert!(!admin.accepts("Bearer hunter2"));
+        // base64 of "editor:hunter2" and "editor:wrong"
+        assert!(admin.accepts("Basic ZWRpdG9yOmh1bnRlcjI="));
+        assert!(!admin.accepts("Basic ZWRpdG9yOndyb25n"));
+        assert!(!admin.accepts("Basic not base64"));
+
+        let token_only = AdminCredentials {
+            token: Some("token".to_string()),
+            ..AdminCredentials::default()
+        };
+        assert!(!token_only.accepts("Basic Og=="));
+    }
+
+    #[test]
+    fn test_constant_time_eq() {
+        assert!(constant_time_eq(b"token", b"token"));
+        assert!(!constant_time_eq(b"token", b"tokem"));
+        assert!(!constant_time_eq(b"token", b"token2"));
+    }
+
+    #[test]
+    fn test_preview_token_round_trips_through_verify_signature() {
+        let token = preview_token(b"secret", "upcoming-post");
+        assert!(verify_signature(b"secret", b"upcoming-post", &token));
+        assert!(!verify_signature(b"secret", b"other-post", &token));
+    }
+}
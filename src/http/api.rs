@@ -0,0 +1,310 @@
+/// Versioned JSON API mirroring the HTML listing handlers, plus the generated OpenAPI document
+///
+/// Everything here is public. View statistics are admin-only and live under `/admin/views`.
+use axum::{
+    extract::{Path, Query, State},
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+    routing::get,
+    Json, Router,
+};
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, OpenApi, ToSchema};
+
+use crate::{
+    data::{Markdown, Post, Series},
+    db::{Database, Listing},
+    http::{
+        caching::CachePolicy,
+        handlers::{view_counts, DateQuery, KeywordQuery, SeriesQuery, TagQuery},
+        state::AppState,
+    },
+    pagination::{PageLinks, PageQuery, SortOrder},
+    search::SearchHit,
+};
+
+#[derive(OpenApi)]
+#[openapi(
+    info(
+        title = "rwd.works API",
+        description = "Read-only access to blog posts"
+    ),
+    paths(
+        get_posts,
+        get_post_by_slug,
+        get_posts_by_tag,
+        get_posts_by_series,
+        get_posts_by_keyword,
+        get_posts_by_date,
+        search_posts,
+        get_tags_with_count,
+        get_dates_with_count
+    ),
+    components(schemas(Post, Markdown, Series, TagCount, DateCount, SearchHit, SortOrder))
+)]
+pub struct ApiDoc;
+
+/// Number of posts published under a single tag
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, ToSchema)]
+pub struct TagCount {
+    pub tag: String,
+    pub count: usize,
+}
+
+/// Number of posts published in a single month
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, ToSchema)]
+pub struct DateCount {
+    pub year: i32,
+    pub month: u32,
+    pub count: usize,
+}
+
+/// Full-text query; see [`search_posts`] for the syntax
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct SearchQuery {
+    /// Words, `"quoted phrases"` and `prefix*` terms, all of which must match
+    pub q: String,
+    /// Maximum number of hits to return
+    pub limit: Option<usize>,
+}
+
+/// Routes served under `/api`, ready to be merged into the main router
+pub fn router() -> Router<AppState> {
+    Router::new()
+        .route("/api/openapi.json", get(openapi_json))
+        .route("/api/v1/posts", get(get_posts))
+        .route("/api/v1/posts/by-tag", get(get_posts_by_tag))
+        .route("/api/v1/posts/by-series", get(get_posts_by_series))
+        .route("/api/v1/posts/by-keyword", get(get_posts_by_keyword))
+        .route("/api/v1/posts/by-date", get(get_posts_by_date))
+        .route("/api/v1/search", get(search_posts))
+        .route("/api/v1/posts/{slug}", get(get_post_by_slug))
+        .route("/api/v1/tags", get(get_tags_with_count))
+        .route("/api/v1/dates", get(get_dates_with_count))
+        .layer(CachePolicy::Page.layer())
+}
+
+/// The OpenAPI document, titled after the configured site
+pub async fn openapi_json(State(state): State<AppState>) -> Json<utoipa::openapi::OpenApi> {
+    let mut doc = ApiDoc::openapi();
+    doc.info.title = format!("{} API", state.site.title);
+    Json(doc)
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/posts",
+    params(PageQuery),
+    responses((status = 200, description = "One page of every published post", body = [Post], headers(
+        ("Link" = String, description = "URLs of the previous and next pages"),
+        ("X-Total-Count" = usize, description = "Number of posts across all pages")
+    )))
+)]
+pub async fn get_posts(
+    Query(page): Query<PageQuery>,
+    State(state): State<AppState>,
+) -> Result<Response, StatusCode> {
+    paged_posts(&state, Listing::All, &page, "/api/v1/posts", &[])
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/posts/{slug}",
+    params(("slug" = String, Path, description = "Post slug")),
+    responses(
+        (status = 200, description = "Post with the given slug", body = Post),
+        (status = 404, description = "No post with the given slug")
+    )
+)]
+pub async fn get_post_by_slug(
+    Path(slug): Path<String>,
+    State(state): State<AppState>,
+) -> Result<Json<Post>, StatusCode> {
+    state
+        .snapshot()
+        .db
+        .get_by_slug(slug)
+        .map(Json)
+        .ok_or(StatusCode::NOT_FOUND)
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/posts/by-tag",
+    params(TagQuery, PageQuery),
+    responses((status = 200, description = "Posts carrying the tag", body = [Post], headers(
+        ("Link" = String, description = "URLs of the previous and next pages"),
+        ("X-Total-Count" = usize, description = "Number of posts across all pages")
+    )))
+)]
+pub async fn get_posts_by_tag(
+    Query(params): Query<TagQuery>,
+    Query(page): Query<PageQuery>,
+    State(state): State<AppState>,
+) -> Result<Response, StatusCode> {
+    let query = [("tag", params.tag.clone())];
+    paged_posts(
+        &state,
+        Listing::Tag(params.tag),
+        &page,
+        "/api/v1/posts/by-tag",
+        &query,
+    )
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/posts/by-series",
+    params(SeriesQuery, PageQuery),
+    responses((status = 200, description = "Posts belonging to the series", body = [Post], headers(
+        ("Link" = String, description = "URLs of the previous and next pages"),
+        ("X-Total-Count" = usize, description = "Number of posts across all pages")
+    )))
+)]
+pub async fn get_posts_by_series(
+    Query(params): Query<SeriesQuery>,
+    Query(page): Query<PageQuery>,
+    State(state): State<AppState>,
+) -> Result<Response, StatusCode> {
+    let query = [("series", params.series.clone())];
+    paged_posts(
+        &state,
+        Listing::Series(params.series),
+        &page,
+        "/api/v1/posts/by-series",
+        &query,
+    )
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/posts/by-keyword",
+    params(KeywordQuery, PageQuery),
+    responses((status = 200, description = "Posts matching the keyword, most relevant first", body = [Post], headers(
+        ("Link" = String, description = "URLs of the previous and next pages"),
+        ("X-Total-Count" = usize, description = "Number of posts across all pages")
+    )))
+)]
+pub async fn get_posts_by_keyword(
+    Query(params): Query<KeywordQuery>,
+    Query(page): Query<PageQuery>,
+    State(state): State<AppState>,
+) -> Result<Response, StatusCode> {
+    let query = [("keyword", params.keyword.clone())];
+    paged_posts(
+        &state,
+        Listing::Keyword(params.keyword),
+        &page,
+        "/api/v1/posts/by-keyword",
+        &query,
+    )
+}
+
+/// Ranked full-text search over titles, descriptions, tags, headings and post bodies
+#[utoipa::path(
+    get,
+    path = "/api/v1/search",
+    params(SearchQuery),
+    responses((status = 200, description = "Matching posts with highlighted snippets, most relevant first", body = [SearchHit]))
+)]
+pub async fn search_posts(
+    Query(params): Query<SearchQuery>,
+    State(state): State<AppState>,
+) -> Json<Vec<SearchHit>> {
+    let mut hits = state.snapshot().db.search(&params.q);
+    if let Some(limit) = params.limit {
+        hits.truncate(limit);
+    }
+    Json(hits)
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/posts/by-date",
+    params(DateQuery, PageQuery),
+    responses((status = 200, description = "Posts published in the year or month", body = [Post], headers(
+        ("Link" = String, description = "URLs of the previous and next pages"),
+        ("X-Total-Count" = usize, description = "Number of posts across all pages")
+    )))
+)]
+pub async fn get_posts_by_date(
+    Query(params): Query<DateQuery>,
+    Query(page): Query<PageQuery>,
+    State(state): State<AppState>,
+) -> Result<Response, StatusCode> {
+    let mut query = vec![("year", params.year.to_string())];
+    if let Some(month) = params.month {
+        query.push(("month", month.to_string()));
+    }
+    let listing = Listing::Date {
+        year: params.year,
+        month: params.month,
+    };
+    paged_posts(&state, listing, &page, "/api/v1/posts/by-date", &query)
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/tags",
+    responses((status = 200, description = "Every tag with its post count, most used first", body = [TagCount]))
+)]
+pub async fn get_tags_with_count(State(state): State<AppState>) -> Json<Vec<TagCount>> {
+    let mut tags: Vec<TagCount> = state
+        .snapshot()
+        .db
+        .get_all_tags_with_count()
+        .into_iter()
+        .map(|(tag, count)| TagCount { tag, count })
+        .collect();
+    tags.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.tag.cmp(&b.tag)));
+    Json(tags)
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/dates",
+    responses((status = 200, description = "Every month with its post count, newest first", body = [DateCount]))
+)]
+pub async fn get_dates_with_count(State(state): State<AppState>) -> Json<Vec<DateCount>> {
+    let mut dates: Vec<DateCount> = state
+        .snapshot()
+        .db
+        .get_all_dates_with_count()
+        .into_iter()
+        .map(|((year, month), count)| DateCount { year, month, count })
+        .collect();
+    dates.sort_by_key(|date| std::cmp::Reverse((date.year, date.month)));
+    Json(dates)
+}
+
+/// One page of `listing` as JSON, with `Link` and `X-Total-Count` headers for navigation
+fn paged_posts(
+    state: &AppState,
+    listing: Listing,
+    page: &PageQuery,
+    path: &str,
+    query: &[(&str, String)],
+) -> Result<Response, StatusCode> {
+    let request = page.to_request(listing.sorts());
+    let views = view_counts(state, &request)?;
+    let page = state.snapshot().db.get_page(&listing, &request, &views);
+
+    let links = PageLinks::new(&page, path, query, listing.sorts());
+    let link = [(links.prev, "prev"), (links.next, "next")]
+        .into_iter()
+        .filter_map(|(url, rel)| url.map(|url| format!("<{url}>; rel=\"{rel}\"")))
+        .collect::<Vec<String>>()
+        .join(", ");
+
+    let mut response = Json(page.items).into_response();
+    let headers = response.headers_mut();
+    headers.insert("x-total-count", page.total.into());
+    if !link.is_empty() {
+        let link = link
+            .parse()
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        headers.insert(header::LINK, link);
+    }
+    Ok(response)
+}
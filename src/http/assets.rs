@@ -0,0 +1,239 @@
+/// Files under `/static`: built into the binary, optionally overridden from a directory
+///
+/// Every file is also served under a fingerprinted name, which pages link to and which is
+/// cached for good; see [`crate::resources`].
+use std::borrow::Cow;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+use std::sync::Arc;
+
+use axum::{
+    extract::{Path as UrlPath, Request, State},
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    middleware::{self, Next},
+    response::{IntoResponse, Response},
+    routing::get,
+    Router,
+};
+use rust_embed::RustEmbed;
+use tower_http::services::ServeDir;
+
+use crate::{
+    http::{
+        caching::{accepts_encoding, CachePolicy, Validators},
+        state::AppState,
+    },
+    resources::{fingerprinted_path, list_files, strip_fingerprint, RESOURCES_URL},
+};
+
+/// Precompressed siblings looked for next to an asset, best first
+const PRECOMPRESSED: [(&str, &str); 2] = [("br", ".br"), ("gzip", ".gz")];
+
+/// The stylesheets and images of the site itself, read from `static/` at compile time
+#[derive(RustEmbed)]
+#[folder = "static/"]
+struct Bundled;
+
+/// The site's own assets, as opposed to the content repository resources under `/static/misc/`
+#[derive(Debug, Clone)]
+pub struct StaticAssets {
+    /// Searched before the bundled files, so a deploy can restyle without a rebuild
+    override_dir: Option<PathBuf>,
+    /// Fingerprinted path by path, hashed when the assets are set up
+    fingerprinted: Arc<BTreeMap<String, String>>,
+}
+
+impl Default for StaticAssets {
+    fn default() -> Self {
+        Self::new(None)
+    }
+}
+
+impl StaticAssets {
+    /// Hash the bundled files and those of `override_dir`
+    pub fn new(override_dir: Option<PathBuf>) -> Self {
+        let mut assets = Self {
+            override_dir,
+            fingerprinted: Arc::default(),
+        };
+        let fingerprinted = assets
+            .paths()
+            .into_iter()
+            .filter_map(|path| {
+                let contents = assets.get(&path)?;
+                let fingerprinted = fingerprinted_path(&path, &contents);
+                Some((path, fingerprinted))
+            })
+            .collect();
+        assets.fingerprinted = Arc::new(fingerprinted);
+        assets
+    }
+
+    /// URL pages link to for the asset at `path`, fingerprinted when the asset exists
+    pub fn url(&self, path: &str) -> String {
+        let path = self.fingerprinted.get(path).map_or(path, String::as_str);
+        format!("/static/{path}")
+    }
+
+    /// Contents of the asset at `path`, relative to `/static`
+    ///
+    /// # Returns
+    /// The file from the override directory when it has one, the bundled copy otherwise, and
+    /// `None` for unknown paths and paths that try to leave the directory
+    pub fn get(&self, path: &str) -> Option<Cow<'static, [u8]>> {
+        let relative = safe_relative(path)?;
+        if let Some(dir) = &self.override_dir {
+            if let Ok(bytes) = fs::read(dir.join(relative)) {
+                return Some(Cow::Owned(bytes));
+            }
+        }
+        Bundled::get(path).map(|file| file.data)
+    }
+
+    /// Every asset path, relative to `/static`, in order
+    pub fn paths(&self) -> Vec<String> {
+        let mut paths: BTreeSet<String> = Bundled::iter().map(|path| path.into_owned()).collect();
+        if let Some(dir) = &self.override_dir {
+            match list_files(dir) {
+                Ok(files) => paths.extend(files),
+                Err(err) => eprintln!("Failed to list {}: {err}", dir.display()),
+            }
+        }
+        paths.into_iter().collect()
+    }
+}
+
+/// Serve the state's [`StaticAssets`] under `/static` and the published content resources
+/// under `/static/misc/`
+///
+/// A `.br` or `.gz` file next to an asset or resource is sent instead of it to clients that
+/// accept the encoding.
+pub fn router(resources_dir: impl AsRef<Path>) -> Router<AppState> {
+    // Resources are only published under a fingerprinted name with contents matching it
+    let resources_dir = ServeDir::new(resources_dir.as_ref())
+        .precompressed_br()
+        .precompressed_gzip();
+    let resources = Router::new()
+        .nest_service(RESOURCES_URL, resources_dir)
+        .layer(middleware::from_fn(cache_fingerprinted))
+        .layer(CachePolicy::Asset.layer());
+    Router::new().merge(resources).route(
+        "/static/{*path}",
+        get(
+            |State(state): State<AppState>,
+             UrlPath(path): UrlPath<String>,
+             headers: HeaderMap| async move { serve(&state.assets, &path, &headers) },
+        ),
+    )
+}
+
+fn serve(assets: &StaticAssets, path: &str, request_headers: &HeaderMap) -> Response {
+    let (bytes, immutable, original) = match assets.get(path) {
+        Some(bytes) => (bytes, false, path.to_string()),
+        // A fingerprint of an earlier version still gets the current file, just not for good
+        None => match strip_fingerprint(path).and_then(|original| {
+            let bytes = assets.get(&original)?;
+            let current = fingerprinted_path(&original, &bytes) == path;
+            Some((bytes, current, original))
+        }) {
+            Some(found) => found,
+            None => return StatusCode::NOT_FOUND.into_response(),
+        },
+    };
+
+    let validators = Validators::for_bytes(&bytes);
+    let siblings: Vec<_> = PRECOMPRESSED
+        .iter()
+        .filter_map(|(encoding, suffix)| {
+            let bytes = assets.get(&format!("{original}{suffix}"))?;
+            Some((*encoding, bytes))
+        })
+        .collect();
+    let vary = !siblings.is_empty();
+    let precompressed = siblings
+        .into_iter()
+        .find(|(encoding, _)| accepts_encoding(request_headers, encoding));
+
+    let mut response = match precompressed {
+        Some((encoding, bytes)) => {
+            let mut response = bytes.into_response();
+            response
+                .headers_mut()
+                .insert(header::CONTENT_ENCODING, HeaderValue::from_static(encoding));
+            response
+        }
+        None => bytes.into_response(),
+    };
+    let headers = response.headers_mut();
+    let content_type = mime_guess::from_path(path).first_or_octet_stream();
+    if let Ok(content_type) = HeaderValue::from_str(content_type.as_ref()) {
+        headers.insert(header::CONTENT_TYPE, content_type);
+    }
+    if vary {
+        headers.insert(
+            header::VARY,
+            HeaderValue::from_static(header::ACCEPT_ENCODING.as_str()),
+        );
+    }
+    validators.apply(&mut response);
+    let policy = if immutable {
+        CachePolicy::Immutable
+    } else {
+        CachePolicy::Asset
+    };
+    policy.apply(&mut response);
+    response
+}
+
+/// Cache successful responses for fingerprinted paths for good
+async fn cache_fingerprinted(request: Request, next: Next) -> Response {
+    let fingerprinted = strip_fingerprint(request.uri().path()).is_some();
+    let mut response = next.run(request).await;
+    if fingerprinted && response.status() == StatusCode::OK {
+        CachePolicy::Immutable.apply(&mut response);
+    }
+    response
+}
+
+/// `path` as a relative path made only of normal components
+fn safe_relative(path: &str) -> Option<&Path> {
+    let relative = Path::new(path);
+    let safe = !path.is_empty()
+        && relative
+            .components()
+            .all(|component| matches!(component, Component::Normal(_)));
+    safe.then_some(relative)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_override_dir_wins_over_bundled_files() {
+        let bundled = StaticAssets::default();
+        assert!(bundled.get("style.css").is_some());
+        assert!(bundled.paths().contains(&"favicon.png".to_string()));
+        assert!(bundled.get("../Cargo.toml").is_none());
+
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("style.css"), "body { color: red }").unwrap();
+        fs::write(dir.path().join("extra.js"), "").unwrap();
+        let overridden = StaticAssets::new(Some(dir.path().to_path_buf()));
+        assert_eq!(
+            overridden.get("style.css").unwrap().as_ref(),
+            b"body { color: red }"
+        );
+        assert_eq!(overridden.get("favicon.png"), bundled.get("favicon.png"));
+        assert!(overridden.paths().contains(&"extra.js".to_string()));
+        assert!(overridden.get("/etc/passwd").is_none());
+
+        assert_ne!(overridden.url("style.css"), bundled.url("style.css"));
+        assert_eq!(
+            strip_fingerprint(&overridden.url("style.css")).as_deref(),
+            Some("/static/style.css")
+        );
+        assert_eq!(overridden.url("missing.css"), "/static/missing.css");
+    }
+}
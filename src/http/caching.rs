@@ -0,0 +1,302 @@
+/// HTTP caching: per-route `Cache-Control`, validators, conditional GET and compression
+///
+/// Handlers attach an [`ETag`](Validators) derived from the content snapshot; the
+/// [`http_caching`] layers turn a matching `If-None-Match` or `If-Modified-Since` into a
+/// `304 Not Modified` and compress everything else the client accepts.
+use axum::{
+    body::Body,
+    extract::Request,
+    http::{header, HeaderMap, HeaderValue, Method, StatusCode},
+    middleware::{self, Next},
+    response::{IntoResponse, Response},
+    Router,
+};
+use chrono::{DateTime, Utc};
+use sha2::{Digest, Sha256};
+use tower_http::{compression::CompressionLayer, set_header::SetResponseHeaderLayer};
+
+use crate::snapshot::Snapshot;
+
+/// How long clients and proxies may reuse a response without asking again
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CachePolicy {
+    /// HTML pages: always revalidated, which the validators make cheap
+    Page,
+    /// Feeds and the sitemap: polled by readers, fine to be a few minutes behind
+    Feed,
+    /// Static assets under their plain names, which change with a deploy
+    Asset,
+    /// Fingerprinted files, whose contents never change under the same name
+    Immutable,
+    /// Private or live responses: admin pages, previews, event streams
+    NoStore,
+}
+
+impl CachePolicy {
+    pub fn header_value(self) -> HeaderValue {
+        HeaderValue::from_static(match self {
+            CachePolicy::Page => "public, no-cache",
+            CachePolicy::Feed => "public, max-age=900",
+            CachePolicy::Asset => "public, max-age=3600",
+            CachePolicy::Immutable => "public, max-age=31536000, immutable",
+            CachePolicy::NoStore => "no-store",
+        })
+    }
+
+    /// Set this policy on responses of a router, leaving those that chose their own alone
+    pub fn layer(self) -> SetResponseHeaderLayer<HeaderValue> {
+        SetResponseHeaderLayer::if_not_present(header::CACHE_CONTROL, self.header_value())
+    }
+
+    /// Set this policy on `response`, replacing any other
+    pub fn apply(self, response: &mut Response) {
+        response
+            .headers_mut()
+            .insert(header::CACHE_CONTROL, self.header_value());
+    }
+}
+
+/// `ETag` and `Last-Modified` of a response
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Validators {
+    /// Quoted strong entity tag
+    pub etag: String,
+    pub last_modified: Option<DateTime<Utc>>,
+}
+
+impl Validators {
+    /// Validators of a page rendered from `snapshot` alone
+    ///
+    /// The tag covers the content version and when the snapshot was last modified, so posts
+    /// going live on schedule and restarts with new templates or settings both change it.
+    pub fn for_snapshot(snapshot: &Snapshot) -> Self {
+        let last_modified = snapshot.last_modified();
+        Self {
+            etag: strong_etag(&[
+                snapshot.version.as_bytes(),
+                last_modified.to_rfc3339().as_bytes(),
+            ]),
+            last_modified: Some(last_modified),
+        }
+    }
+
+    /// Validators of a response whose contents are exactly `bytes`
+    pub fn for_bytes(bytes: &[u8]) -> Self {
+        Self {
+            etag: strong_etag(&[bytes]),
+            last_modified: None,
+        }
+    }
+
+    /// Fold something else the response depends on into the tag
+    ///
+    /// `Last-Modified` is dropped, since `part` may change without the snapshot changing.
+    pub fn varying_with(self, part: &str) -> Self {
+        Self {
+            etag: strong_etag(&[self.etag.as_bytes(), part.as_bytes()]),
+            last_modified: None,
+        }
+    }
+
+    /// Add the validators to `response`
+    pub fn apply(&self, response: &mut Response) {
+        let headers = response.headers_mut();
+        if let Ok(etag) = HeaderValue::from_str(&self.etag) {
+            headers.insert(header::ETAG, etag);
+        }
+        if let Some(last_modified) = self.last_modified {
+            if let Ok(date) = HeaderValue::from_str(&http_date(last_modified)) {
+                headers.insert(header::LAST_MODIFIED, date);
+            }
+        }
+    }
+}
+
+/// Quoted hex SHA-256 over `parts`, e.g. `"3a7b…"`
+pub fn strong_etag(parts: &[&[u8]]) -> String {
+    let mut hasher = Sha256::new();
+    for part in parts {
+        hasher.update((part.len() as u64).to_be_bytes());
+        hasher.update(part);
+    }
+    format!("\"{}\"", hex::encode(hasher.finalize()))
+}
+
+/// `date` in the IMF-fixdate format of HTTP headers
+pub fn http_date(date: DateTime<Utc>) -> String {
+    date.format("%a, %d %b %Y %H:%M:%S GMT").to_string()
+}
+
+/// Whether `encoding` is listed with a non-zero quality in the request's `Accept-Encoding`
+pub fn accepts_encoding(headers: &HeaderMap, encoding: &str) -> bool {
+    headers
+        .get_all(header::ACCEPT_ENCODING)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .any(|entry| {
+            let mut params = entry.split(';');
+            let name = params.next().unwrap_or_default().trim();
+            let quality = params
+                .filter_map(|param| param.trim().strip_prefix("q="))
+                .find_map(|q| q.trim().parse::<f32>().ok())
+                .unwrap_or(1.0);
+            name.eq_ignore_ascii_case(encoding) && quality > 0.0
+        })
+}
+
+/// Whether the request's `If-None-Match` names `etag` in any content encoding, meaning the
+/// client already holds the response and will get a `304 Not Modified`
+pub fn is_revalidation(request: &HeaderMap, etag: &str) -> bool {
+    let Some(tags) = request
+        .get(header::IF_NONE_MATCH)
+        .and_then(|value| value.to_str().ok())
+    else {
+        return false;
+    };
+    let Some(opaque) = etag.strip_suffix('"') else {
+        return false;
+    };
+    tags.split(',')
+        .map(|tag| tag.trim().trim_start_matches("W/"))
+        .any(|tag| {
+            let encoded = tag
+                .strip_prefix(opaque)
+                .and_then(|rest| rest.strip_prefix('-'))
+                .is_some_and(|encoding| encoding.len() > 1 && encoding.ends_with('"'));
+            tag == "*" || tag == etag || encoded
+        })
+}
+
+/// Wrap every route of `router` with compression and conditional GET handling
+pub fn http_caching<S>(router: Router<S>) -> Router<S>
+where
+    S: Clone + Send + Sync + 'static,
+{
+    router
+        .layer(CompressionLayer::new().br(true).gzip(true))
+        .layer(middleware::from_fn(conditional_get))
+}
+
+/// Answer `304 Not Modified` when the client's copy still matches the response
+///
+/// `If-None-Match` wins over `If-Modified-Since` as RFC 9110 asks. A compressed body is a
+/// different representation, so its tag carries the encoding, e.g. `"3a7b…-br"`.
+async fn conditional_get(request: Request, next: Next) -> Response {
+    let method = request.method().clone();
+    let conditions = request.headers().clone();
+    let mut response = next.run(request).await;
+
+    tag_encoding(&mut response);
+    if !matches!(method, Method::GET | Method::HEAD) || response.status() != StatusCode::OK {
+        return response;
+    }
+    if !is_not_modified(&conditions, response.headers()) {
+        return response;
+    }
+
+    let mut not_modified = StatusCode::NOT_MODIFIED.into_response();
+    for name in [
+        header::ETAG,
+        header::LAST_MODIFIED,
+        header::CACHE_CONTROL,
+        header::VARY,
+        header::EXPIRES,
+        header::CONTENT_LOCATION,
+    ] {
+        for value in response.headers().get_all(&name) {
+            not_modified.headers_mut().append(&name, value.clone());
+        }
+    }
+    *not_modified.body_mut() = Body::empty();
+    not_modified
+}
+
+fn tag_encoding(response: &mut Response) {
+    let Some(encoding) = response.headers().get(header::CONTENT_ENCODING).cloned() else {
+        return;
+    };
+    let Some(etag) = response.headers().get(header::ETAG) else {
+        return;
+    };
+    let (Ok(etag), Ok(encoding)) = (etag.to_str(), encoding.to_str()) else {
+        return;
+    };
+    if let Some(opaque) = etag.strip_suffix('"') {
+        if let Ok(tagged) = HeaderValue::from_str(&format!("{opaque}-{encoding}\"")) {
+            response.headers_mut().insert(header::ETAG, tagged);
+        }
+    }
+}
+
+fn is_not_modified(request: &HeaderMap, response: &HeaderMap) -> bool {
+    if let Some(if_none_match) = request.get(header::IF_NONE_MATCH) {
+        let Some(etag) = response.get(header::ETAG).and_then(|v| v.to_str().ok()) else {
+            return false;
+        };
+        return if_none_match
+            .to_str()
+            .map(|tags| etag_matches(tags, etag))
+            .unwrap_or(false);
+    }
+
+    let since = request
+        .get(header::IF_MODIFIED_SINCE)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| DateTime::parse_from_rfc2822(value).ok());
+    let last_modified = response
+        .get(header::LAST_MODIFIED)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| DateTime::parse_from_rfc2822(value).ok());
+    match (since, last_modified) {
+        (Some(since), Some(last_modified)) => last_modified <= since,
+        _ => false,
+    }
+}
+
+/// Weak comparison of `etag` against an `If-None-Match` list, `*` matching anything
+fn etag_matches(tags: &str, etag: &str) -> bool {
+    let opaque = |tag: &str| tag.trim().trim_start_matches("W/").to_string();
+    let etag = opaque(etag);
+    tags.split(',')
+        .any(|tag| tag.trim() == "*" || opaque(tag) == etag)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_if_none_match_lists() {
+        assert!(etag_matches("\"a\", \"b\"", "\"b\""));
+        assert!(etag_matches("W/\"a\"", "\"a\""));
+        assert!(etag_matches("*", "\"a\""));
+        assert!(!etag_matches("\"a-br\"", "\"a\""));
+    }
+
+    #[test]
+    fn test_revalidations_match_any_encoding() {
+        let mut headers = HeaderMap::new();
+        assert!(!is_revalidation(&headers, "\"a\""));
+        headers.insert(
+            header::IF_NONE_MATCH,
+            HeaderValue::from_static("\"b\", \"a-gzip\""),
+        );
+        assert!(is_revalidation(&headers, "\"a\""));
+        assert!(is_revalidation(&headers, "\"b\""));
+        assert!(!is_revalidation(&headers, "\"ab\""));
+        assert!(!is_revalidation(&headers, "\"c\""));
+    }
+
+    #[test]
+    fn test_accept_encoding_qualities() {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            header::ACCEPT_ENCODING,
+            HeaderValue::from_static("gzip;q=0.5, br;q=0, deflate"),
+        );
+        assert!(accepts_encoding(&headers, "gzip"));
+        assert!(!accepts_encoding(&headers, "br"));
+        assert!(!accepts_encoding(&HeaderMap::new(), "gzip"));
+    }
+}
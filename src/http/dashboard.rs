@@ -0,0 +1,293 @@
+/// Admin analytics dashboard: view totals, daily charts and traffic per tag on one page
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use askama::Template;
+use axum::{
+    extract::{Query, State},
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    response::{Html, IntoResponse, Response},
+};
+use chrono::{Duration, NaiveDate};
+
+use crate::{
+    config::SiteConfig,
+    data::Post,
+    db::Database,
+    http::{
+        admin::{authorize, ViewsQuery},
+        state::AppState,
+    },
+    view_history::{ViewCount, ViewHistory},
+};
+
+/// Posts given their own chart when the query sets no limit
+const DEFAULT_CHARTED_POSTS: usize = 20;
+/// Periods offered as links above the charts, in days
+const PERIODS: [u32; 4] = [7, 30, 90, 365];
+/// Height of the chart area in SVG user units
+const CHART_HEIGHT: u32 = 100;
+/// Width taken by each day in SVG user units, gap included
+const BAR_STEP: u32 = 10;
+
+/// One day of a chart, in SVG user units with the origin at the top left
+pub struct Bar {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+    /// Shown as a tooltip
+    pub title: String,
+}
+
+/// Bar chart of views per day, drawn as inline SVG so the page needs no scripts
+pub struct Chart {
+    pub width: u32,
+    pub height: u32,
+    /// Views on the busiest day, which fills the full height
+    pub max: u64,
+    pub bars: Vec<Bar>,
+}
+
+impl Chart {
+    pub fn daily(series: &[(NaiveDate, ViewCount)]) -> Self {
+        let max = series
+            .iter()
+            .map(|(_, count)| count.views)
+            .max()
+            .unwrap_or(0);
+        let bars = series
+            .iter()
+            .enumerate()
+            .map(|(index, (day, count))| {
+                let height = match max {
+                    0 => 0,
+                    max => (count.views * CHART_HEIGHT as u64).div_ceil(max) as u32,
+                };
+                Bar {
+                    x: index as u32 * BAR_STEP,
+                    y: CHART_HEIGHT - height,
+                    width: BAR_STEP - 2,
+                    height,
+                    title: format!("{day}: {} views, {} requests", count.views, count.raw),
+                }
+            })
+            .collect();
+        Self {
+            width: series.len().max(1) as u32 * BAR_STEP,
+            height: CHART_HEIGHT,
+            max,
+            bars,
+        }
+    }
+}
+
+/// A post with views in the period; posts no longer published have no title
+pub struct PostRow {
+    pub slug: String,
+    pub title: Option<String>,
+    pub period: ViewCount,
+    pub lifetime: ViewCount,
+    /// Only the most viewed posts get one
+    pub chart: Option<Chart>,
+}
+
+/// Views in the period of all posts carrying a tag
+pub struct TagTraffic {
+    pub tag: String,
+    pub views: u64,
+    pub posts: usize,
+}
+
+/// A published post nobody viewed in the period
+pub struct UnviewedPost {
+    pub slug: String,
+    pub title: String,
+    pub date: NaiveDate,
+    pub lifetime: u64,
+}
+
+pub struct PeriodLink {
+    pub days: u32,
+    pub selected: bool,
+}
+
+#[derive(Template)]
+#[template(path = "admin.html")]
+pub struct DashboardTemplate {
+    pub site: Arc<SiteConfig>,
+    pub from: NaiveDate,
+    pub to: NaiveDate,
+    pub periods: Vec<PeriodLink>,
+    pub total: ViewCount,
+    pub lifetime: ViewCount,
+    pub chart: Chart,
+    /// Most viewed in the period first
+    pub posts: Vec<PostRow>,
+    pub tags: Vec<TagTraffic>,
+    /// Newest first
+    pub unviewed: Vec<UnviewedPost>,
+}
+
+impl DashboardTemplate {
+    /// Gather the dashboard for the `days` up to `today`
+    ///
+    /// # Arguments
+    ///
+    /// * `site` - Names the site in the page title
+    /// * `posts` - Published posts, for titles, tags and the posts without views
+    /// * `history` - View counts to report on
+    /// * `today` - Last day of the period
+    /// * `days` - Length of the period
+    /// * `charted` - How many of the most viewed posts get a chart of their own
+    pub fn build(
+        site: Arc<SiteConfig>,
+        posts: &[Post],
+        history: &ViewHistory,
+        today: NaiveDate,
+        days: u32,
+        charted: usize,
+    ) -> Self {
+        let from = today - Duration::days(days as i64 - 1);
+        let period = history.range(from, today);
+        let lifetime = history.totals();
+        let titles: HashMap<&str, &str> = posts
+            .iter()
+            .map(|post| (post.markdown.slug.as_str(), post.markdown.title.as_str()))
+            .collect();
+        let views_of = |slug: &str| period.get(slug).map_or(0, |count| count.views);
+
+        let mut rows: Vec<PostRow> = period
+            .iter()
+            .filter(|(_, count)| count.views > 0)
+            .map(|(slug, count)| PostRow {
+                slug: slug.clone(),
+                title: titles.get(slug.as_str()).map(|title| title.to_string()),
+                period: *count,
+                lifetime: lifetime.get(slug).copied().unwrap_or_default(),
+                chart: None,
+            })
+            .collect();
+        rows.sort_by(|a, b| {
+            b.period
+                .views
+                .cmp(&a.period.views)
+                .then_with(|| a.slug.cmp(&b.slug))
+        });
+        for row in rows.iter_mut().take(charted) {
+            row.chart = Some(Chart::daily(&history.daily(&row.slug, from, today)));
+        }
+
+        let mut tags: HashMap<&str, TagTraffic> = HashMap::new();
+        for post in posts {
+            for tag in &post.markdown.tags {
+                let traffic = tags.entry(tag.as_str()).or_insert_with(|| TagTraffic {
+                    tag: tag.clone(),
+                    views: 0,
+                    posts: 0,
+                });
+                traffic.views += views_of(&post.markdown.slug);
+                traffic.posts += 1;
+            }
+        }
+        let mut tags: Vec<TagTraffic> = tags.into_values().collect();
+        tags.sort_by(|a, b| b.views.cmp(&a.views).then_with(|| a.tag.cmp(&b.tag)));
+
+        let mut unviewed: Vec<UnviewedPost> = posts
+            .iter()
+            .filter(|post| views_of(&post.markdown.slug) == 0)
+            .map(|post| UnviewedPost {
+                slug: post.markdown.slug.clone(),
+                title: post.markdown.title.clone(),
+                date: post.markdown.date,
+                lifetime: lifetime
+                    .get(&post.markdown.slug)
+                    .map_or(0, |count| count.views),
+            })
+            .collect();
+        unviewed.sort_by(|a, b| b.date.cmp(&a.date).then_with(|| a.slug.cmp(&b.slug)));
+
+        let sum = |counts: &HashMap<String, ViewCount>| {
+            counts
+                .values()
+                .fold(ViewCount::default(), |total, count| ViewCount {
+                    views: total.views + count.views,
+                    raw: total.raw + count.raw,
+                })
+        };
+        Self {
+            site,
+            from,
+            to: today,
+            periods: PERIODS
+                .into_iter()
+                .map(|period| PeriodLink {
+                    days: period,
+                    selected: period == days,
+                })
+                .collect(),
+            total: sum(&period),
+            lifetime: sum(&lifetime),
+            chart: Chart::daily(&history.daily_totals(from, today)),
+            posts: rows,
+            tags,
+            unviewed,
+        }
+    }
+}
+
+/// The dashboard over the last `days` days; asks browsers to log in when basic auth is set
+pub async fn admin_dashboard(
+    Query(params): Query<ViewsQuery>,
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Response {
+    if let Err(status) = authorize(&state, &headers) {
+        let mut response = status.into_response();
+        if status == StatusCode::UNAUTHORIZED && state.admin.has_basic_auth() {
+            response.headers_mut().insert(
+                header::WWW_AUTHENTICATE,
+                HeaderValue::from_static("Basic realm=\"admin\", charset=\"UTF-8\""),
+            );
+        }
+        return response;
+    }
+
+    let posts = state.snapshot().db.get_all_posts();
+    let template = {
+        let Ok(views) = state.views.lock() else {
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        };
+        DashboardTemplate::build(
+            state.site.clone(),
+            &posts,
+            views.history(),
+            views.today(),
+            params.days(),
+            params.limit.unwrap_or(DEFAULT_CHARTED_POSTS),
+        )
+    };
+    match template.render() {
+        Ok(html) => ([(header::CACHE_CONTROL, "no-store")], Html(html)).into_response(),
+        Err(_) => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_busiest_day_fills_the_chart() {
+        let day = NaiveDate::from_ymd_opt(2026, 3, 1).unwrap();
+        let count = |views| ViewCount { views, raw: views };
+        let chart = Chart::daily(&[(day, count(4)), (day, count(1)), (day, count(0))]);
+        let heights: Vec<u32> = chart.bars.iter().map(|bar| bar.height).collect();
+        assert_eq!(heights, vec![100, 25, 0]);
+        assert_eq!(chart.bars[1].y, 75);
+        assert_eq!((chart.width, chart.max), (30, 4));
+
+        let empty = Chart::daily(&[(day, count(0))]);
+        assert_eq!(empty.bars[0].height, 0);
+    }
+}
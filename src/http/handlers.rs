@@ -1,276 +1,545 @@
 use askama::Template;
 use axum::{
     extract::{Path, Query, State},
-    http::StatusCode,
-    response::{Html, IntoResponse, Response},
+    http::{header, HeaderMap, Method, StatusCode},
+    response::{Html, IntoResponse, Redirect, Response},
+    routing::get,
+    Router,
 };
+use chrono::Local;
 use serde::Deserialize;
 use std::collections::HashMap;
+use std::fmt;
+use std::sync::Arc;
+use utoipa::IntoParams;
+
+use crate::{
+    config::SiteConfig,
+    data::Post,
+    db::{Database, InMemDatabase, Listing},
+    feeds::{self, FeedScope},
+    http::{
+        admin::verify_signature,
+        assets::StaticAssets,
+        caching::{is_revalidation, CachePolicy, Validators},
+        page_cache::{fill_view_count, VIEW_COUNT_SLOT},
+        state::AppState,
+    },
+    pagination::{PageLinks, PageQuery, PageRequest, SortOrder},
+    snapshot::{DatesByYear, Snapshot, TagCounts},
+    visitors::ViewRequest,
+};
 
-use crate::{data::Post, db::Database, http::state::AppState};
 #[derive(Template)]
 #[template(path = "post.html")]
 pub struct PostTemplate {
+    pub site: Arc<SiteConfig>,
+    pub assets: StaticAssets,
     pub post: Post,
-    pub view_count: u64,
-    pub tags_with_count: Vec<(String, u8)>,
-    pub dates_by_year: Vec<(i32, Vec<(String, u32, u8)>)>,
+    pub view_count: ViewCount,
+    /// Rendered through a preview link: not indexed and without a view count
+    pub preview: bool,
+    pub tags_with_count: TagCounts,
+    pub dates_by_year: DatesByYear,
+}
+
+/// The view count a post page shows
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ViewCount {
+    Count(u64),
+    /// Rendered as [`VIEW_COUNT_SLOT`], for pages cached across views
+    Slot,
+}
+
+impl fmt::Display for ViewCount {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ViewCount::Count(count) => write!(f, "{count}"),
+            ViewCount::Slot => f.write_str(VIEW_COUNT_SLOT),
+        }
+    }
 }
 
 #[derive(Template)]
 #[template(path = "posts_list.html")]
 pub struct PostsListTemplate {
+    pub site: Arc<SiteConfig>,
+    pub assets: StaticAssets,
     pub posts: Vec<Post>,
-    pub tags_with_count: Vec<(String, u8)>,
-    pub dates_by_year: Vec<(i32, Vec<(String, u32, u8)>)>,
+    /// Highlighted search excerpts keyed by slug; empty outside search results
+    pub snippets: HashMap<String, String>,
+    pub pagination: PageLinks,
+    pub tags_with_count: TagCounts,
+    pub dates_by_year: DatesByYear,
 }
 
 #[derive(Template)]
 #[template(path = "index.html")]
 pub struct IndexTemplate {
+    pub site: Arc<SiteConfig>,
+    /// Linked by their fingerprinted URLs
+    pub assets: StaticAssets,
     pub posts: Vec<Post>,
-    pub tags_with_count: Vec<(String, u8)>,
-    pub dates_by_year: Vec<(i32, Vec<(String, u32, u8)>)>,
+    pub pagination: PageLinks,
+    pub tags_with_count: TagCounts,
+    pub dates_by_year: DatesByYear,
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
 pub struct TagQuery {
     pub tag: String,
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
 pub struct SeriesQuery {
     pub series: String,
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
 pub struct KeywordQuery {
     pub keyword: String,
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
 pub struct DateQuery {
     pub year: i32,
     pub month: Option<u32>,
 }
 
+#[derive(Deserialize)]
+pub struct PreviewQuery {
+    pub token: String,
+}
+
+/// Optional scope for syndication feeds; when both are given the tag wins
+#[derive(Deserialize, Default)]
+pub struct FeedQuery {
+    pub tag: Option<String>,
+    pub series: Option<String>,
+}
+
+pub fn router() -> Router<AppState> {
+    let feeds = Router::new()
+        .route("/robots.txt", get(robots_txt))
+        .route("/sitemap.xml", get(sitemap_xml))
+        .route("/feed.atom", get(feed_atom))
+        .route("/feed.rss", get(feed_rss))
+        .route("/feed.json", get(feed_json))
+        .layer(CachePolicy::Feed.layer());
+    let preview = Router::new()
+        .route("/preview/{slug}", get(html_preview_post))
+        .layer(CachePolicy::NoStore.layer());
+    Router::new()
+        .route("/", get(html_index))
+        .route("/posts/{slug}", get(html_get_post_by_slug))
+        .route("/posts/by-tag", get(html_get_posts_by_tag))
+        .route("/posts/by-series", get(html_get_posts_by_series))
+        .route("/posts/by-keyword", get(html_get_posts_by_keyword))
+        .route("/posts/by-date", get(html_get_posts_by_date))
+        .route("/tags/{tag}", get(html_tag_listing))
+        .route("/series/{series}", get(html_series_listing))
+        .route("/archive/{year}", get(html_year_listing))
+        .route("/archive/{year}/{month}", get(html_month_listing))
+        .layer(CachePolicy::Page.layer())
+        .merge(feeds)
+        .merge(preview)
+}
+
+/// Render a published post, counting the request as a view if the filter lets it through
+///
+/// Only a `GET` that gets the page is a request at all: `HEAD` requests and revalidations
+/// answered with `304 Not Modified` leave the counts alone.
 pub async fn html_get_post_by_slug(
     Path(slug): Path<String>,
     State(state): State<AppState>,
-) -> Result<Html<String>, StatusCode> {
-    let (post, tags_with_count, dates_by_year) = {
-        let db = state
-            .db
-            .lock()
-            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
-        let post = db.get_by_slug(slug.clone()).ok_or(StatusCode::NOT_FOUND)?;
-        let (tags_with_count, dates_by_year) = prepare_nav_data(&*db);
-        (post, tags_with_count, dates_by_year)
-    };
+    method: Method,
+    headers: HeaderMap,
+    visitor: ViewRequest,
+) -> Result<Response, StatusCode> {
+    let snapshot = state.snapshot();
+    let post = snapshot
+        .db
+        .get_by_slug(slug.clone())
+        .ok_or(StatusCode::NOT_FOUND)?;
 
-    let view_count = {
+    // The view count is left out so validators survive new views; clients revalidating a
+    // page show the count it had when they fetched it
+    let generation = Validators::for_snapshot(&snapshot);
+    let validators = generation.clone().varying_with(&post.content_hash());
+    let fetched = method == Method::GET && !is_revalidation(&headers, &validators.etag);
+
+    let view_count = if fetched {
+        let verdict = state
+            .view_filter
+            .lock()
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+            .check(&slug, &visitor, Local::now());
         let mut views = state
             .views
             .lock()
             .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
         views
-            .increment(&slug)
+            .record_from(&slug, verdict.is_counted(), &visitor.source)
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+    } else {
+        state
+            .views
+            .lock()
             .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+            .get(&slug)
     };
 
+    // Cached with a slot for the view count, which changes on every counted view
+    let html = state
+        .pages
+        .get_or_render(&generation.etag, &format!("/posts/{slug}"), || {
+            let nav = snapshot.nav();
+            PostTemplate {
+                site: state.site.clone(),
+                assets: state.assets.clone(),
+                post,
+                view_count: ViewCount::Slot,
+                preview: false,
+                tags_with_count: nav.tags_with_count.clone(),
+                dates_by_year: nav.dates_by_year.clone(),
+            }
+            .render()
+        })
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    Ok(page_response(
+        fill_view_count(&html, view_count),
+        Some(validators),
+    ))
+}
+
+/// Render a draft or scheduled post for reviewers holding its preview token
+///
+/// Unknown slugs and wrong tokens both answer 404 so previews can't be probed for.
+/// Once the post is public the link redirects to its permanent URL.
+pub async fn html_preview_post(
+    Path(slug): Path<String>,
+    Query(params): Query<PreviewQuery>,
+    State(state): State<AppState>,
+) -> Result<Response, StatusCode> {
+    let secret = state
+        .admin
+        .preview_secret
+        .as_ref()
+        .ok_or(StatusCode::NOT_FOUND)?;
+    if !verify_signature(secret.as_bytes(), slug.as_bytes(), &params.token) {
+        return Err(StatusCode::NOT_FOUND);
+    }
+
+    let snapshot = state.snapshot();
+    if snapshot.db.get_by_slug(slug.clone()).is_some() {
+        return Ok(Redirect::to(&format!("/posts/{slug}")).into_response());
+    }
+    let post = snapshot
+        .db
+        .get_unpublished_by_slug(slug)
+        .ok_or(StatusCode::NOT_FOUND)?;
+
+    let nav = snapshot.nav();
     let template = PostTemplate {
+        site: state.site.clone(),
+        assets: state.assets.clone(),
         post,
-        view_count,
-        tags_with_count,
-        dates_by_year,
+        view_count: ViewCount::Count(0),
+        preview: true,
+        tags_with_count: nav.tags_with_count.clone(),
+        dates_by_year: nav.dates_by_year.clone(),
     };
     let html = template
         .render()
         .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
-    Ok(Html(html))
+    Ok(Html(html).into_response())
 }
 
 pub async fn html_get_posts_by_tag(
     Query(params): Query<TagQuery>,
+    Query(page): Query<PageQuery>,
     State(state): State<AppState>,
-) -> Result<Html<String>, StatusCode> {
-    let db = state
-        .db
-        .lock()
-        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
-    let posts = db.get_by_tag(params.tag);
-    let (tags_with_count, dates_by_year) = prepare_nav_data(&*db);
-    let template = PostsListTemplate {
-        posts,
-        tags_with_count,
-        dates_by_year,
-    };
-    let html = template
-        .render()
-        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
-    Ok(Html(html))
+) -> Result<Response, StatusCode> {
+    let query = [("tag", params.tag.clone())];
+    render_listing(
+        &state,
+        Listing::Tag(params.tag),
+        &page,
+        "/posts/by-tag",
+        &query,
+    )
 }
 
 pub async fn html_get_posts_by_series(
     Query(params): Query<SeriesQuery>,
+    Query(page): Query<PageQuery>,
     State(state): State<AppState>,
-) -> Result<Html<String>, StatusCode> {
-    let db = state
-        .db
-        .lock()
-        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
-    let posts = db.get_by_series(params.series);
-    let (tags_with_count, dates_by_year) = prepare_nav_data(&*db);
-    let template = PostsListTemplate {
-        posts,
-        tags_with_count,
-        dates_by_year,
-    };
-    let html = template
-        .render()
-        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
-    Ok(Html(html))
+) -> Result<Response, StatusCode> {
+    let query = [("series", params.series.clone())];
+    render_listing(
+        &state,
+        Listing::Series(params.series),
+        &page,
+        "/posts/by-series",
+        &query,
+    )
 }
 
 pub async fn html_get_posts_by_keyword(
     Query(params): Query<KeywordQuery>,
+    Query(page): Query<PageQuery>,
     State(state): State<AppState>,
-) -> Result<Html<String>, StatusCode> {
-    let db = state
-        .db
-        .lock()
-        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
-    let posts = db.get_by_keyword(params.keyword);
-    let (tags_with_count, dates_by_year) = prepare_nav_data(&*db);
-    let template = PostsListTemplate {
-        posts,
-        tags_with_count,
-        dates_by_year,
-    };
-    let html = template
-        .render()
-        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
-    Ok(Html(html))
+) -> Result<Response, StatusCode> {
+    let query = [("keyword", params.keyword.clone())];
+    render_listing(
+        &state,
+        Listing::Keyword(params.keyword),
+        &page,
+        "/posts/by-keyword",
+        &query,
+    )
 }
 
 pub async fn html_get_posts_by_date(
     Query(params): Query<DateQuery>,
+    Query(page): Query<PageQuery>,
     State(state): State<AppState>,
-) -> Result<Html<String>, StatusCode> {
-    let db = state
-        .db
-        .lock()
-        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
-    let posts = db.get_by_year_month(params.year, params.month);
-    let (tags_with_count, dates_by_year) = prepare_nav_data(&*db);
-    let template = PostsListTemplate {
-        posts,
-        tags_with_count,
-        dates_by_year,
+) -> Result<Response, StatusCode> {
+    let mut query = vec![("year", params.year.to_string())];
+    if let Some(month) = params.month {
+        query.push(("month", month.to_string()));
+    }
+    let listing = Listing::Date {
+        year: params.year,
+        month: params.month,
     };
-    let html = template
-        .render()
-        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
-    Ok(Html(html))
+    render_listing(&state, listing, &page, "/posts/by-date", &query)
 }
 
-pub async fn html_index(State(state): State<AppState>) -> Result<Html<String>, StatusCode> {
-    let db = state
-        .db
-        .lock()
-        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
-    let posts = db.get_last_n_posts(10);
-    let (tags_with_count, dates_by_year) = prepare_nav_data(&*db);
-    let template = IndexTemplate {
-        posts,
-        tags_with_count,
-        dates_by_year,
+/// `/tags/{tag}`, the address pages link to; `/posts/by-tag` keeps working for old links
+pub async fn html_tag_listing(
+    Path(tag): Path<String>,
+    Query(page): Query<PageQuery>,
+    State(state): State<AppState>,
+) -> Result<Response, StatusCode> {
+    render_path_listing(&state, Listing::Tag(tag), &page)
+}
+
+pub async fn html_series_listing(
+    Path(series): Path<String>,
+    Query(page): Query<PageQuery>,
+    State(state): State<AppState>,
+) -> Result<Response, StatusCode> {
+    render_path_listing(&state, Listing::Series(series), &page)
+}
+
+pub async fn html_year_listing(
+    Path(year): Path<i32>,
+    Query(page): Query<PageQuery>,
+    State(state): State<AppState>,
+) -> Result<Response, StatusCode> {
+    render_path_listing(&state, Listing::Date { year, month: None }, &page)
+}
+
+pub async fn html_month_listing(
+    Path((year, month)): Path<(i32, u32)>,
+    Query(page): Query<PageQuery>,
+    State(state): State<AppState>,
+) -> Result<Response, StatusCode> {
+    let listing = Listing::Date {
+        year,
+        month: Some(month),
     };
-    let html = template
+    render_path_listing(&state, listing, &page)
+}
+
+fn render_path_listing(
+    state: &AppState,
+    listing: Listing,
+    page: &PageQuery,
+) -> Result<Response, StatusCode> {
+    let path = listing.path();
+    render_listing(state, listing, page, &path, &[])
+}
+
+/// Render one page of `listing` with `posts_list.html`
+///
+/// Keyword listings go through full-text search so each post carries its snippet.
+fn render_listing(
+    state: &AppState,
+    listing: Listing,
+    page: &PageQuery,
+    path: &str,
+    query: &[(&str, String)],
+) -> Result<Response, StatusCode> {
+    let request = page.to_request(listing.sorts());
+    let views = view_counts(state, &request)?;
+    let snapshot = state.snapshot();
+    let validators = snapshot_validators(&snapshot, &request);
+    let key = listing_key(path, query, &request);
+    cached_page(state, validators, &key, || {
+        let db = &snapshot.db;
+        let (page, snippets) = match &listing {
+            Listing::Keyword(keyword) => {
+                let hits = db.search(keyword);
+                let snippets = hits
+                    .iter()
+                    .map(|hit| (hit.post.markdown.slug.clone(), hit.snippet.clone()))
+                    .collect();
+                let posts = hits.into_iter().map(|hit| hit.post).collect();
+                (request.paginate(posts, &views), snippets)
+            }
+            _ => (db.get_page(&listing, &request, &views), HashMap::new()),
+        };
+        let nav = snapshot.nav();
+        PostsListTemplate {
+            site: state.site.clone(),
+            assets: state.assets.clone(),
+            pagination: PageLinks::new(&page, path, query, listing.sorts()),
+            posts: page.items,
+            snippets,
+            tags_with_count: nav.tags_with_count.clone(),
+            dates_by_year: nav.dates_by_year.clone(),
+        }
         .render()
-        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
-    Ok(Html(html))
-}
-
-fn prepare_nav_data(
-    db: &crate::db::InMemDatabase,
-) -> (Vec<(String, u8)>, Vec<(i32, Vec<(String, u32, u8)>)>) {
-    let mut tags_with_count = db.get_all_tags_with_count();
-    tags_with_count.sort_by(|a, b| b.1.cmp(&a.1));
-    let dates_with_count = db.get_all_dates_with_count();
-    let mut dates_by_year: HashMap<i32, Vec<(String, u32, u8)>> = HashMap::new();
-
-    for ((year, month), count) in dates_with_count {
-        dates_by_year.entry(year).or_insert_with(Vec::new).push((
-            month_name(month).to_string(),
-            month,
-            count,
-        ));
+    })
+}
+
+/// Snapshot validators of a listing page, unless its order depends on live view counts
+fn snapshot_validators(snapshot: &Snapshot, request: &PageRequest) -> Option<Validators> {
+    (request.sort != SortOrder::Views).then(|| Validators::for_snapshot(snapshot))
+}
+
+/// Page cache key of a listing, built from the parameters it was rendered with so unknown
+/// parameters and clamped values share an entry
+fn listing_key(path: &str, query: &[(&str, String)], request: &PageRequest) -> String {
+    let mut key = format!("{path}?");
+    for (name, value) in query {
+        key.push_str(&format!("{name}={value:?}&"));
     }
+    key.push_str(&format!(
+        "page={}&per_page={}&sort={:?}",
+        request.page, request.per_page, request.sort
+    ));
+    key
+}
 
-    let mut dates_by_year: Vec<(i32, Vec<(String, u32, u8)>)> = dates_by_year.into_iter().collect();
-    dates_by_year.sort_by(|a, b| b.0.cmp(&a.0));
+/// Respond with the page `render` makes, taken from the page cache when the page has
+/// validators to tell its version by
+fn cached_page(
+    state: &AppState,
+    validators: Option<Validators>,
+    key: &str,
+    render: impl FnOnce() -> askama::Result<String>,
+) -> Result<Response, StatusCode> {
+    let html = match &validators {
+        Some(validators) => state
+            .pages
+            .get_or_render(&validators.etag, key, render)
+            .map(|html| html.to_string()),
+        None => render(),
+    }
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    Ok(page_response(html, validators))
+}
 
-    for (_, months) in &mut dates_by_year {
-        months.sort_by(|a, b| a.1.cmp(&b.1));
+fn page_response(html: String, validators: Option<Validators>) -> Response {
+    let mut response = Html(html).into_response();
+    if let Some(validators) = validators {
+        validators.apply(&mut response);
     }
+    response
+}
 
-    (tags_with_count, dates_by_year)
-}
-
-fn month_name(month: u32) -> &'static str {
-    match month {
-        1 => "January",
-        2 => "February",
-        3 => "March",
-        4 => "April",
-        5 => "May",
-        6 => "June",
-        7 => "July",
-        8 => "August",
-        9 => "September",
-        10 => "October",
-        11 => "November",
-        12 => "December",
-        _ => "Unknown",
+/// View counts by slug when the page is sorted by popularity, otherwise empty
+pub(crate) fn view_counts(
+    state: &AppState,
+    request: &PageRequest,
+) -> Result<HashMap<String, u64>, StatusCode> {
+    if request.sort != SortOrder::Views {
+        return Ok(HashMap::new());
     }
+    let views = state
+        .views
+        .lock()
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    Ok(views.counts().clone())
+}
+
+pub async fn html_index(
+    Query(page): Query<PageQuery>,
+    State(state): State<AppState>,
+) -> Result<Response, StatusCode> {
+    let listing = Listing::All;
+    let request = page.to_request(listing.sorts());
+    let views = view_counts(&state, &request)?;
+    let snapshot = state.snapshot();
+    let validators = snapshot_validators(&snapshot, &request);
+    let key = listing_key("/", &[], &request);
+    cached_page(&state, validators, &key, || {
+        let page = snapshot.db.get_page(&listing, &request, &views);
+        let nav = snapshot.nav();
+        IndexTemplate {
+            site: state.site.clone(),
+            assets: state.assets.clone(),
+            pagination: PageLinks::new(&page, "/", &[], listing.sorts()),
+            posts: page.items,
+            tags_with_count: nav.tags_with_count.clone(),
+            dates_by_year: nav.dates_by_year.clone(),
+        }
+        .render()
+    })
 }
 
-pub async fn robots_txt() -> Response {
-    let content = "User-agent: *\nAllow: /\n\nSitemap: https://rwd.works/sitemap.xml\n";
+pub async fn robots_txt(State(state): State<AppState>) -> Response {
     (
-        axum::http::StatusCode::OK,
-        [(
-            axum::http::header::CONTENT_TYPE,
-            "text/plain; charset=utf-8",
-        )],
-        content,
+        StatusCode::OK,
+        [(header::CONTENT_TYPE, "text/plain; charset=utf-8")],
+        render_robots(&state.site),
     )
         .into_response()
 }
 
 pub async fn sitemap_xml(State(state): State<AppState>) -> Response {
-    let db = match state.db.lock() {
-        Ok(guard) => guard,
-        Err(_) => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
-    };
+    let snapshot = state.snapshot();
+    let mut response = (
+        StatusCode::OK,
+        [(header::CONTENT_TYPE, "application/xml; charset=utf-8")],
+        render_sitemap(&state.site, &snapshot.db),
+    )
+        .into_response();
+    Validators::for_snapshot(&snapshot).apply(&mut response);
+    response
+}
 
+/// robots.txt allowing everything and pointing crawlers at the sitemap
+pub fn render_robots(site: &SiteConfig) -> String {
+    format!(
+        "User-agent: *\nAllow: /\n\nSitemap: {}\n",
+        site.url("/sitemap.xml")
+    )
+}
+
+/// Sitemap of the index and the 500 newest posts
+pub fn render_sitemap(site: &SiteConfig, db: &InMemDatabase) -> String {
     let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
     xml.push_str("<urlset xmlns=\"http://www.sitemaps.org/schemas/sitemap/0.9\">\n");
 
     xml.push_str("  <url>\n");
-    xml.push_str("    <loc>https://rwd.works/</loc>\n");
+    xml.push_str(&format!("    <loc>{}</loc>\n", site.url("/")));
     xml.push_str("    <priority>1.0</priority>\n");
     xml.push_str("  </url>\n");
 
-    let mut posts = db.get_all_posts();
-    posts.sort_by(|a, b| b.markdown.date.cmp(&a.markdown.date));
-
-    for post in posts.iter().take(500) {
+    for post in db.get_last_n_posts(500) {
         xml.push_str("  <url>\n");
         xml.push_str(&format!(
-            "    <loc>https://rwd.works/posts/{}</loc>\n",
-            post.markdown.slug
+            "    <loc>{}</loc>\n",
+            site.url(&format!("/posts/{}", post.markdown.slug))
         ));
         xml.push_str(&format!("    <lastmod>{}</lastmod>\n", post.markdown.date));
         xml.push_str("    <priority>0.8</priority>\n");
@@ -278,14 +547,48 @@ pub async fn sitemap_xml(State(state): State<AppState>) -> Response {
     }
 
     xml.push_str("</urlset>");
+    xml
+}
 
-    (
-        axum::http::StatusCode::OK,
-        [(
-            axum::http::header::CONTENT_TYPE,
-            "application/xml; charset=utf-8",
-        )],
-        xml,
+pub async fn feed_atom(Query(params): Query<FeedQuery>, State(state): State<AppState>) -> Response {
+    feed_response(&state, params, feeds::ATOM_CONTENT_TYPE, feeds::atom)
+}
+
+pub async fn feed_rss(Query(params): Query<FeedQuery>, State(state): State<AppState>) -> Response {
+    feed_response(&state, params, feeds::RSS_CONTENT_TYPE, feeds::rss)
+}
+
+pub async fn feed_json(Query(params): Query<FeedQuery>, State(state): State<AppState>) -> Response {
+    feed_response(
+        &state,
+        params,
+        feeds::JSON_FEED_CONTENT_TYPE,
+        feeds::json_feed,
     )
-        .into_response()
+}
+
+fn feed_response(
+    state: &AppState,
+    params: FeedQuery,
+    content_type: &'static str,
+    build: fn(&SiteConfig, &FeedScope, &[Post]) -> String,
+) -> Response {
+    let scope = match (params.tag, params.series) {
+        (Some(tag), _) => FeedScope::Tag(tag),
+        (None, Some(series)) => FeedScope::Series(series),
+        (None, None) => FeedScope::All,
+    };
+    let snapshot = state.snapshot();
+    let Some(posts) = feeds::scoped_posts(&snapshot.db, &scope) else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+
+    let mut response = (
+        StatusCode::OK,
+        [(header::CONTENT_TYPE, content_type)],
+        build(&state.site, &scope, &posts),
+    )
+        .into_response();
+    Validators::for_snapshot(&snapshot).apply(&mut response);
+    response
 }
@@ -0,0 +1,86 @@
+/// Server-sent events telling open pages to refresh after local content changes
+use std::convert::Infallible;
+
+use axum::{
+    body::{to_bytes, Body},
+    extract::{Request, State},
+    http::{header, StatusCode},
+    middleware::Next,
+    response::{
+        sse::{Event, KeepAlive, Sse},
+        IntoResponse, Response,
+    },
+    routing::get,
+    Router,
+};
+use tokio_stream::{wrappers::BroadcastStream, StreamExt};
+
+use crate::http::{caching::CachePolicy, state::AppState};
+
+pub const LIVE_RELOAD_PATH: &str = "/__livereload";
+
+/// Largest HTML page the script injection will buffer
+const MAX_INJECTED_BODY: usize = 8 * 1024 * 1024;
+
+const LIVE_RELOAD_SCRIPT: &str = r#"<script>
+        (function () {
+            const source = new EventSource('/__livereload');
+            source.addEventListener('reload', () => window.location.reload());
+        })();
+    </script>
+"#;
+
+pub fn router() -> Router<AppState> {
+    Router::new()
+        .route(LIVE_RELOAD_PATH, get(live_reload_events))
+        .layer(CachePolicy::NoStore.layer())
+}
+
+pub async fn live_reload_events(State(state): State<AppState>) -> Response {
+    let Some(sender) = &state.live_reload else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+
+    let events = BroadcastStream::new(sender.subscribe()).filter_map(|message| {
+        message
+            .ok()
+            .map(|_| Ok::<_, Infallible>(Event::default().event("reload").data("reload")))
+    });
+    Sse::new(events)
+        .keep_alive(KeepAlive::default())
+        .into_response()
+}
+
+/// Middleware appending the live reload client to HTML pages while live reload is enabled
+pub async fn inject_live_reload_script(
+    State(state): State<AppState>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let response = next.run(request).await;
+    if state.live_reload.is_none() {
+        return response;
+    }
+
+    let is_html = response
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.starts_with("text/html"));
+    if !is_html {
+        return response;
+    }
+
+    let (mut parts, body) = response.into_parts();
+    let Ok(bytes) = to_bytes(body, MAX_INJECTED_BODY).await else {
+        return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+    };
+    let mut html = String::from_utf8_lossy(&bytes).into_owned();
+    match html.rfind("</body>") {
+        Some(index) => html.insert_str(index, LIVE_RELOAD_SCRIPT),
+        None => html.push_str(LIVE_RELOAD_SCRIPT),
+    }
+
+    parts.headers.remove(header::CONTENT_LENGTH);
+    Response::from_parts(parts, Body::from(html))
+}
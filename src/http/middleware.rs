@@ -25,7 +25,7 @@ pub async fn security_headers_middleware(
     // Content Security Policy - Improve security and SEO
     headers.insert(
         "Content-Security-Policy",
-        "default-src 'self'; script-src 'self' 'unsafe-inline' https://cdn.tailwindcss.com https://cdnjs.cloudflare.com; style-src 'self' 'unsafe-inline' https://cdnjs.cloudflare.com; img-src 'self' data: https:; font-src 'self' https:; connect-src 'self'; frame-ancestors 'none';"
+        "default-src 'self'; script-src 'self' 'unsafe-inline' https://cdn.tailwindcss.com; style-src 'self' 'unsafe-inline'; img-src 'self' data: https:; font-src 'self' https:; connect-src 'self'; frame-ancestors 'none';"
             .parse()
             .unwrap(),
     );
@@ -48,8 +48,7 @@ pub async fn security_headers_middleware(
         "geolocation=(), microphone=(), camera=(), payment=()".parse().unwrap(),
     );
 
-    // Cache-Control header for static assets (set per route as needed)
-    // Don't override existing Cache-Control
+    // Cache-Control is set per route, see crate::http::caching
 
     response
 }
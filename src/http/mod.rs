@@ -1,3 +1,10 @@
+pub mod admin;
+pub mod api;
+pub mod assets;
+pub mod caching;
+pub mod dashboard;
 pub mod handlers;
+pub mod live_reload;
 pub mod middleware;
+pub mod page_cache;
 pub mod state;
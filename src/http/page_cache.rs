@@ -0,0 +1,211 @@
+/// Rendered HTML pages kept in memory until the content they were rendered from changes
+///
+/// Pages are stored under a generation, the snapshot's entity tag, and the request route. A
+/// page from any other generation is never returned, and the first page stored for a new one
+/// drops everything else, so reloads invalidate the cache without being told about it.
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, Mutex, MutexGuard};
+
+/// Memory kept for pages when no limit is configured
+pub const DEFAULT_MAX_BYTES: usize = 32 * 1024 * 1024;
+
+/// Where a post page shows its view count; filled in per request by [`fill_view_count`]
+pub const VIEW_COUNT_SLOT: &str = "<!--view-count-->";
+
+/// Least recently used pages of the current generation, up to a number of bytes
+#[derive(Debug, Clone)]
+pub struct PageCache {
+    max_bytes: usize,
+    pages: Arc<Mutex<Pages>>,
+}
+
+#[derive(Debug, Default)]
+struct Pages {
+    generation: String,
+    entries: HashMap<String, Entry>,
+    /// Keys by the tick they were last used at, oldest first
+    by_use: BTreeMap<u64, String>,
+    tick: u64,
+    bytes: usize,
+}
+
+#[derive(Debug)]
+struct Entry {
+    html: Arc<str>,
+    used: u64,
+}
+
+impl Default for PageCache {
+    fn default() -> Self {
+        Self::new(DEFAULT_MAX_BYTES)
+    }
+}
+
+impl PageCache {
+    /// A cache holding at most `max_bytes` of pages and keys; 0 disables it
+    pub fn new(max_bytes: usize) -> Self {
+        Self {
+            max_bytes,
+            pages: Arc::default(),
+        }
+    }
+
+    /// The page stored under `key` for `generation`, or the one `render` makes, which is then
+    /// stored
+    ///
+    /// Rendering happens outside the lock, so concurrent misses may render the same page.
+    pub fn get_or_render<E>(
+        &self,
+        generation: &str,
+        key: &str,
+        render: impl FnOnce() -> Result<String, E>,
+    ) -> Result<Arc<str>, E> {
+        if let Some(html) = self.get(generation, key) {
+            return Ok(html);
+        }
+        let html: Arc<str> = render()?.into();
+        self.insert(generation, key, html.clone());
+        Ok(html)
+    }
+
+    pub fn get(&self, generation: &str, key: &str) -> Option<Arc<str>> {
+        let mut pages = self.lock();
+        if pages.generation != generation {
+            return None;
+        }
+        pages.touch(key)
+    }
+
+    /// Store `html` under `key`, dropping the pages of any other generation and then the least
+    /// recently used ones until everything fits
+    pub fn insert(&self, generation: &str, key: &str, html: Arc<str>) {
+        let size = key.len() + html.len();
+        if size > self.max_bytes {
+            return;
+        }
+        let mut pages = self.lock();
+        if pages.generation != generation {
+            *pages = Pages {
+                generation: generation.to_string(),
+                ..Pages::default()
+            };
+        }
+        pages.remove(key);
+        pages.tick += 1;
+        let used = pages.tick;
+        pages.by_use.insert(used, key.to_string());
+        pages.entries.insert(key.to_string(), Entry { html, used });
+        pages.bytes += size;
+        while pages.bytes > self.max_bytes {
+            let Some((_, oldest)) = pages.by_use.pop_first() else {
+                break;
+            };
+            pages.remove(&oldest);
+        }
+    }
+
+    /// Number of pages stored
+    pub fn len(&self) -> usize {
+        self.lock().entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Bytes of pages and keys stored
+    pub fn bytes(&self) -> usize {
+        self.lock().bytes
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Pages> {
+        // A panic mid-update leaves at worst a stale size count; the pages themselves are whole
+        match self.pages.lock() {
+            Ok(guard) => guard,
+            Err(poisoned) => poisoned.into_inner(),
+        }
+    }
+}
+
+impl Pages {
+    fn touch(&mut self, key: &str) -> Option<Arc<str>> {
+        self.tick += 1;
+        let tick = self.tick;
+        let entry = self.entries.get_mut(key)?;
+        let previous = std::mem::replace(&mut entry.used, tick);
+        let html = entry.html.clone();
+        if let Some(key) = self.by_use.remove(&previous) {
+            self.by_use.insert(tick, key);
+        }
+        Some(html)
+    }
+
+    fn remove(&mut self, key: &str) {
+        if let Some(entry) = self.entries.remove(key) {
+            self.by_use.remove(&entry.used);
+            self.bytes -= key.len() + entry.html.len();
+        }
+    }
+}
+
+/// `html` with the first [`VIEW_COUNT_SLOT`] replaced by `count`
+///
+/// The slot sits in the page header, ahead of the post body, so a post quoting it is left
+/// alone.
+pub fn fill_view_count(html: &str, count: u64) -> String {
+    html.replacen(VIEW_COUNT_SLOT, &count.to_string(), 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn page(len: usize) -> Arc<str> {
+        "x".repeat(len).into()
+    }
+
+    #[test]
+    fn test_least_recently_used_pages_go_first() {
+        let cache = PageCache::new(300);
+        cache.insert("v1", "/a", page(99));
+        cache.insert("v1", "/b", page(99));
+        assert!(cache.get("v1", "/a").is_some());
+        cache.insert("v1", "/c", page(99));
+        assert_eq!(cache.len(), 2);
+        assert!(cache.get("v1", "/b").is_none());
+        assert!(cache.get("v1", "/a").is_some());
+        assert!(cache.bytes() <= 300);
+
+        cache.insert("v1", "/huge", page(400));
+        assert!(cache.get("v1", "/huge").is_none());
+        assert_eq!(cache.len(), 2);
+    }
+
+    #[test]
+    fn test_new_generation_replaces_the_old_one() {
+        let cache = PageCache::default();
+        cache.insert("v1", "/", page(10));
+        assert!(cache.get("v2", "/").is_none());
+
+        let rendered = cache
+            .get_or_render("v2", "/", || Ok::<_, ()>("new".to_string()))
+            .unwrap();
+        assert_eq!(&*rendered, "new");
+        assert_eq!(cache.len(), 1);
+        assert!(cache.get("v1", "/").is_none());
+
+        let cached = cache
+            .get_or_render("v2", "/", || Err("rendered twice"))
+            .unwrap();
+        assert_eq!(&*cached, "new");
+    }
+
+    #[test]
+    fn test_view_count_fills_the_first_slot_only() {
+        let html = format!("<b>{VIEW_COUNT_SLOT}</b><p>{VIEW_COUNT_SLOT}</p>");
+        assert_eq!(
+            fill_view_count(&html, 42),
+            format!("<b>42</b><p>{VIEW_COUNT_SLOT}</p>")
+        );
+    }
+}
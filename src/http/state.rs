@@ -1,19 +1,101 @@
+use std::net::IpAddr;
 use std::sync::{Arc, Mutex};
 
+use tokio::sync::broadcast;
+
+use crate::config::SiteConfig;
 use crate::db::InMemDatabase;
+use crate::http::admin::AdminCredentials;
+use crate::http::assets::StaticAssets;
+use crate::http::page_cache::PageCache;
+use crate::reload::Reloader;
+use crate::snapshot::{Snapshot, SnapshotCell};
 use crate::views::ViewCounterStore;
+use crate::visitors::ViewFilter;
 
 #[derive(Clone)]
 pub struct AppState {
-    pub db: Arc<Mutex<InMemDatabase>>,
+    /// Content currently served; replaced as a whole on every reload
+    pub content: SnapshotCell,
+    /// Public URL, title and author used by pages, feeds and the sitemap
+    pub site: Arc<SiteConfig>,
+    /// Files under `/static`, which pages link to by fingerprint
+    pub assets: StaticAssets,
+    /// Rendered pages of the current snapshot
+    pub pages: PageCache,
     pub views: Arc<Mutex<ViewCounterStore>>,
+    /// Decides which post requests count as views
+    pub view_filter: Arc<Mutex<ViewFilter>>,
+    /// Peers trusted to name the client in proxy headers
+    pub trusted_proxies: Arc<Vec<IpAddr>>,
+    pub admin: Arc<AdminCredentials>,
+    pub reloader: Option<Reloader>,
+    /// Notifies open pages to refresh, only set while watching a local path
+    pub live_reload: Option<broadcast::Sender<()>>,
 }
 
 impl AppState {
     pub fn new(db: InMemDatabase, views: ViewCounterStore) -> Self {
+        Self::from_snapshot(Snapshot::new(db), views)
+    }
+
+    pub fn from_snapshot(snapshot: Snapshot, views: ViewCounterStore) -> Self {
         Self {
-            db: Arc::new(Mutex::new(db)),
+            content: SnapshotCell::new(snapshot),
+            site: Arc::new(SiteConfig::default()),
+            assets: StaticAssets::default(),
+            pages: PageCache::default(),
             views: Arc::new(Mutex::new(views)),
+            view_filter: Arc::new(Mutex::new(ViewFilter::default())),
+            trusted_proxies: Arc::default(),
+            admin: Arc::new(AdminCredentials::default()),
+            reloader: None,
+            live_reload: None,
         }
     }
+
+    /// The snapshot to serve the current request from
+    pub fn snapshot(&self) -> Arc<Snapshot> {
+        self.content.load()
+    }
+
+    pub fn with_site(mut self, site: SiteConfig) -> Self {
+        self.site = Arc::new(site);
+        self
+    }
+
+    pub fn with_static_assets(mut self, assets: StaticAssets) -> Self {
+        self.assets = assets;
+        self
+    }
+
+    pub fn with_page_cache(mut self, pages: PageCache) -> Self {
+        self.pages = pages;
+        self
+    }
+
+    pub fn with_view_filter(mut self, view_filter: ViewFilter) -> Self {
+        self.view_filter = Arc::new(Mutex::new(view_filter));
+        self
+    }
+
+    pub fn with_trusted_proxies(mut self, trusted_proxies: Vec<IpAddr>) -> Self {
+        self.trusted_proxies = Arc::new(trusted_proxies);
+        self
+    }
+
+    pub fn with_admin_credentials(mut self, admin: AdminCredentials) -> Self {
+        self.admin = Arc::new(admin);
+        self
+    }
+
+    pub fn with_reloader(mut self, reloader: Reloader) -> Self {
+        self.reloader = Some(reloader);
+        self
+    }
+
+    pub fn with_live_reload(mut self, live_reload: broadcast::Sender<()>) -> Self {
+        self.live_reload = Some(live_reload);
+        self
+    }
 }
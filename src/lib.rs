@@ -1,7 +1,23 @@
+pub mod cli;
+pub mod config;
 pub mod data;
 pub mod db;
+pub mod diagnostics;
 pub mod error;
+pub mod export;
+pub mod feeds;
+pub mod highlight;
 pub mod http;
+pub mod pagination;
+pub mod reload;
 pub mod repo_utils;
+pub mod resources;
+pub mod search;
 pub mod seo;
+pub mod snapshot;
+pub mod sqlite;
+pub mod url;
+pub mod view_history;
 pub mod views;
+pub mod visitors;
+pub mod watch;
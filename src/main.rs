@@ -1,122 +1,280 @@
-use axum::{middleware, routing::get, Router};
+use axum::{middleware, Router};
 use chrono::{Duration as ChronoDuration, Local, TimeZone};
-use personal::db::{Database, InMemDatabase};
+use clap::Parser;
 use personal::{
+    cli::{self, Cli, Command, EXIT_ERROR},
+    config::Config,
     error::ApplicationError,
-    http::{handlers, middleware::security_headers_middleware, state::AppState},
-    repo_utils::clone_and_ingest_repository,
-    views::ViewCounterStore,
+    http::{
+        admin::{self, AdminCredentials},
+        api,
+        assets::{self, StaticAssets},
+        caching, handlers,
+        live_reload::{self, inject_live_reload_script},
+        middleware::security_headers_middleware,
+        page_cache::PageCache,
+        state::AppState,
+    },
+    reload::{build_database, ContentSnapshot, ContentSource, ReloadTrigger, Reloader},
+    repo_utils::RepositorySync,
+    snapshot::Snapshot,
+    sqlite::SqliteDatabase,
+    views::{spawn_flusher, ViewCounterStore},
+    visitors::ViewFilter,
+    watch::{watch_local_path, LocalWatch},
 };
+use std::net::SocketAddr;
 use std::path::PathBuf;
-use tower_http::services::ServeDir;
+use std::process::ExitCode;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::broadcast;
 
 #[tokio::main]
-async fn main() {
-    let args: Vec<String> = std::env::args().collect();
-
-    // Check if a local path was provided as argument
-    let (repo_source, is_local_path) = if args.len() > 1 {
-        (args[1].clone(), true)
-    } else {
-        ("https://github.com/softwarecowboy/blog".to_string(), false)
+async fn main() -> ExitCode {
+    let cli = Cli::parse();
+    let mut config = match Config::load_from(cli.config.as_deref()) {
+        Ok(config) => config,
+        Err(err) => {
+            eprintln!("{err}");
+            return ExitCode::from(EXIT_ERROR);
+        }
     };
 
-    let db = if is_local_path {
-        build_database_from_local_path(&repo_source)
-            .await
-            .expect("Failed to load posts from local path")
-    } else {
-        build_database(&repo_source)
-            .await
-            .expect("Failed to load posts")
+    match cli.command.unwrap_or_default() {
+        Command::Serve(args) => {
+            args.apply(&mut config);
+            match serve(config).await {
+                Ok(()) => ExitCode::SUCCESS,
+                Err(err) => {
+                    eprintln!("{err}");
+                    ExitCode::from(EXIT_ERROR)
+                }
+            }
+        }
+        Command::Check(args) => cli::check(&args, &config).await,
+        Command::Export(args) => cli::export(&args, &config).await,
+        Command::NewPost(args) => match cli::new_post(&args) {
+            Ok(path) => {
+                println!("Created {}", path.display());
+                ExitCode::SUCCESS
+            }
+            Err(err) => {
+                eprintln!("{err}");
+                ExitCode::from(EXIT_ERROR)
+            }
+        },
+    }
+}
+
+/// Run the server until Ctrl+C or SIGTERM
+///
+/// # Returns
+/// An error when the content, the view counts or the listening socket can't be set up
+async fn serve(config: Config) -> Result<(), ApplicationError> {
+    let local_path = config.content.local_path().map(PathBuf::from);
+    let is_local_path = local_path.is_some();
+
+    let resources_dir = config.resources_dir();
+    // Local content is parsed once, then shared by the reloader and the filesystem watcher
+    let local_watch = match &local_path {
+        Some(path) => Some(Arc::new(tokio::sync::Mutex::new(
+            LocalWatch::load(path, &resources_dir).await?,
+        ))),
+        None => None,
     };
-    println!("Loaded {} posts", db.by_slug.len());
-
-    let views_file_path = std::env::var("VIEW_COUNTS_FILE")
-        .map(PathBuf::from)
-        .unwrap_or_else(|_| {
-            if is_local_path {
-                PathBuf::from("tmp/views.tsv")
-            } else {
-                PathBuf::from("/opt/personal/runtime/views.tsv")
+    let mut source = match &local_watch {
+        Some(local_watch) => ContentSource::Local(local_watch.clone()),
+        None => {
+            let mut sync = RepositorySync::new(
+                config.content.source.clone(),
+                config.content.mirror_dir.clone(),
+            )
+            .with_resources_dir(resources_dir.clone());
+            if let Some(branch) = &config.content.branch {
+                sync = sync.with_branch(branch.clone());
             }
-        });
-    let views_store = ViewCounterStore::load(views_file_path.clone())
-        .expect("Failed to initialize view counters storage");
+            ContentSource::Git(Box::new(sync))
+        }
+    };
 
-    println!("Using views file at {}", views_file_path.display());
+    // Remote content is also kept in SQLite so a restart can serve it before the sync
+    let mut store = (!is_local_path)
+        .then(|| {
+            let path = config.content.database_file.clone();
+            println!("Using content database at {}", path.display());
+            SqliteDatabase::open(path)
+        })
+        .transpose()?;
+    let cached = store
+        .as_ref()
+        .and_then(|store| match (store.load_in_memory(), store.commit()) {
+            (Ok(db), Ok(commit)) if db.posts_including_unpublished().next().is_some() => {
+                Some(ContentSnapshot {
+                    db,
+                    commit,
+                    diagnostics: Vec::new(),
+                })
+            }
+            (Err(err), _) | (_, Err(err)) => {
+                eprintln!("Ignoring unreadable content database: {err}");
+                None
+            }
+            _ => None,
+        });
+    let serving_cached = cached.is_some();
 
-    let state = AppState::new(db, views_store);
-    let repo_source_clone = repo_source.clone();
-    let db_handle = state.db.clone();
-
-    // Only spawn the background reload task if using remote repo (not local path)
-    if !is_local_path {
-        tokio::spawn(async move {
-            loop {
-                let sleep_for = duration_until_next_midnight();
-                tokio::time::sleep(sleep_for).await;
-                match build_database(&repo_source_clone).await {
-                    Ok(new_db) => {
-                        let mut guard = db_handle.lock().expect("the database should be lockable");
-                        *guard = new_db;
-                    }
-                    Err(err) => {
-                        eprintln!("Failed to reload posts: {err}");
-                    }
+    let snapshot = match (cached, &local_watch) {
+        (Some(snapshot), _) => {
+            println!("Serving saved content until the repository is synced");
+            snapshot
+        }
+        (None, Some(local_watch)) => {
+            let local_watch = local_watch.lock().await;
+            ContentSnapshot {
+                db: local_watch.build_database()?,
+                commit: None,
+                diagnostics: local_watch.diagnostics(),
+            }
+        }
+        (None, None) => {
+            let snapshot = build_database(&mut source).await?;
+            if let Some(store) = store.as_mut() {
+                if let Err(err) = store.replace_content(&snapshot.db, snapshot.commit.as_deref()) {
+                    eprintln!("Failed to save content to the database: {err}");
                 }
             }
-        });
-    } else {
-        println!("Running in debug mode with local path - automatic reloading disabled");
+            snapshot
+        }
+    };
+    println!(
+        "Loaded {} posts, skipped {}",
+        snapshot.db.by_slug.len(),
+        snapshot.diagnostics.len()
+    );
+    if let Some(commit) = &snapshot.commit {
+        println!("Serving content commit {commit}");
+    }
+
+    let views_file_path = config.views_file();
+    let views_store =
+        ViewCounterStore::load(views_file_path.clone())?.with_retention(config.views.retention());
+
+    println!("Using views file at {}", views_file_path.display());
+
+    let reload_debounce = Duration::from_secs(config.reload.debounce_seconds);
+    let view_window = ChronoDuration::minutes(config.views.dedup_minutes as i64);
+
+    let state = AppState::from_snapshot(
+        Snapshot::new(snapshot.db)
+            .with_commit(snapshot.commit)
+            .with_diagnostics(snapshot.diagnostics),
+        views_store,
+    )
+    .with_site(config.site.clone())
+    .with_static_assets(StaticAssets::new(config.server.static_dir.clone()))
+    .with_page_cache(PageCache::new(config.server.page_cache_mb * 1024 * 1024))
+    .with_view_filter(ViewFilter::new(view_window))
+    .with_trusted_proxies(config.server.trusted_proxies.clone())
+    .with_admin_credentials(AdminCredentials::from_config(&config.admin));
+    let reloader = Reloader::spawn_with_store(source, &state, reload_debounce, store);
+    if serving_cached {
+        reloader.request(ReloadTrigger::Startup);
     }
+    let state = state.with_reloader(reloader.clone());
+
+    let flush_interval = Duration::from_secs(config.views.flush_seconds);
+    let views = state.views.clone();
+    spawn_flusher(views.clone(), flush_interval);
+
+    // Local paths are watched for edits; remote content is reloaded on a schedule
+    let (state, _watcher) = if let Some(local_watch) = local_watch {
+        let (live_reload_tx, _) = broadcast::channel(16);
+        let state = state.with_live_reload(live_reload_tx.clone());
+        let watcher = watch_local_path(local_watch, state.clone(), Some(live_reload_tx)).await?;
+        println!("Running in debug mode with local path - reloading on file changes");
+        (state, Some(watcher))
+    } else {
+        let interval = config.reload.interval_minutes;
+        if interval != Some(0) {
+            tokio::spawn(async move {
+                loop {
+                    let sleep_for = match interval {
+                        Some(minutes) => Duration::from_secs(minutes * 60),
+                        None => duration_until_next_midnight(),
+                    };
+                    tokio::time::sleep(sleep_for).await;
+                    reloader.request(ReloadTrigger::Schedule);
+                }
+            });
+        }
+        (state, None)
+    };
 
     let app = Router::new()
-        .route("/", get(handlers::html_index))
-        .route("/robots.txt", get(handlers::robots_txt))
-        .route("/sitemap.xml", get(handlers::sitemap_xml))
-        .route("/posts/{slug}", get(handlers::html_get_post_by_slug))
-        .route("/posts/by-tag", get(handlers::html_get_posts_by_tag))
-        .route("/posts/by-series", get(handlers::html_get_posts_by_series))
-        .route(
-            "/posts/by-keyword",
-            get(handlers::html_get_posts_by_keyword),
-        )
-        .route("/posts/by-date", get(handlers::html_get_posts_by_date))
-        .nest_service("/static", ServeDir::new("static"))
+        .merge(handlers::router())
+        .merge(api::router())
+        .merge(admin::router())
+        .merge(live_reload::router())
+        .merge(assets::router(&resources_dir))
+        .layer(middleware::from_fn_with_state(
+            state.clone(),
+            inject_live_reload_script,
+        ));
+    let app = caching::http_caching(app)
         .layer(middleware::from_fn(security_headers_middleware))
         .with_state(state);
 
-    let listener = tokio::net::TcpListener::bind("127.0.0.1:3000")
-        .await
-        .expect("Failed to bind to port");
+    let listener = tokio::net::TcpListener::bind(config.server.bind).await?;
 
-    println!("Server running on http://127.0.0.1:3000");
+    println!(
+        "Server running on http://{} for {}",
+        config.server.bind, config.site.base_url
+    );
 
-    axum::serve(listener, app)
-        .await
-        .expect("Server failed to start");
-}
+    // The peer address identifies visitors unless it is a trusted proxy naming them
+    axum::serve(
+        listener,
+        app.into_make_service_with_connect_info::<SocketAddr>(),
+    )
+    .with_graceful_shutdown(shutdown_signal())
+    .await?;
 
-async fn build_database(repo_url: &str) -> Result<InMemDatabase, ApplicationError> {
-    let posts = clone_and_ingest_repository(repo_url).await?;
-    let mut db = InMemDatabase::new();
-    for post in posts {
-        db.insert_parsed_to_database(post)?;
+    // Fold the view log into the counts file so the next start has nothing to replay
+    let mut views = match views.lock() {
+        Ok(guard) => guard,
+        Err(poisoned) => poisoned.into_inner(),
+    };
+    match views.flush() {
+        Ok(()) => println!("Saved view counts"),
+        Err(err) => eprintln!("Failed to save view counts: {err}"),
     }
-    Ok(db)
+    Ok(())
 }
 
-async fn build_database_from_local_path(
-    local_path: &str,
-) -> Result<InMemDatabase, ApplicationError> {
-    use personal::repo_utils::load_from_local_path;
-    let posts = load_from_local_path(local_path).await?;
-    let mut db = InMemDatabase::new();
-    for post in posts {
-        db.insert_parsed_to_database(post)?;
+/// Resolves on Ctrl+C, or on SIGTERM where signals exist
+async fn shutdown_signal() {
+    let ctrl_c = async {
+        tokio::signal::ctrl_c()
+            .await
+            .expect("Failed to listen for Ctrl+C");
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())
+            .expect("Failed to listen for SIGTERM")
+            .recv()
+            .await;
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {},
+        _ = terminate => {},
     }
-    Ok(db)
+    println!("Shutting down");
 }
 
 fn duration_until_next_midnight() -> std::time::Duration {
@@ -0,0 +1,310 @@
+/// Page selection, sort orders and prev/next links shared by every post listing
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+
+use crate::data::Post;
+
+/// Posts per page when the request does not ask for a size
+pub const DEFAULT_PER_PAGE: usize = 10;
+/// Largest page a client may request
+pub const MAX_PER_PAGE: usize = 100;
+
+/// Order of the posts in a listing; ties always fall back to newest first, then slug
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum SortOrder {
+    Newest,
+    Oldest,
+    Title,
+    /// Grouped by series in episode order; posts outside a series come last
+    Episode,
+    /// Most viewed first
+    Views,
+    /// Search rank; keeps the order the listing produced
+    Relevance,
+}
+
+impl SortOrder {
+    /// Value of the `sort` query parameter
+    pub fn as_str(self) -> &'static str {
+        match self {
+            SortOrder::Newest => "newest",
+            SortOrder::Oldest => "oldest",
+            SortOrder::Title => "title",
+            SortOrder::Episode => "episode",
+            SortOrder::Views => "views",
+            SortOrder::Relevance => "relevance",
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            SortOrder::Newest => "Newest",
+            SortOrder::Oldest => "Oldest",
+            SortOrder::Title => "Title",
+            SortOrder::Episode => "Episode",
+            SortOrder::Views => "Most viewed",
+            SortOrder::Relevance => "Relevance",
+        }
+    }
+}
+
+/// Page selection as it arrives in the query string
+#[derive(Debug, Clone, Default, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct PageQuery {
+    /// 1-based page number
+    pub page: Option<usize>,
+    /// Posts per page, at most 100
+    pub per_page: Option<usize>,
+    /// One of `newest`, `oldest`, `title`, `episode`, `views` or `relevance`
+    pub sort: Option<SortOrder>,
+}
+
+impl PageQuery {
+    /// Clamp the query into a valid request
+    ///
+    /// # Arguments
+    /// * `sorts` - Orders the listing offers; the first is its default
+    pub fn to_request(&self, sorts: &[SortOrder]) -> PageRequest {
+        let default_sort = sorts.first().copied().unwrap_or(SortOrder::Newest);
+        PageRequest {
+            page: self.page.unwrap_or(1).max(1),
+            per_page: self
+                .per_page
+                .unwrap_or(DEFAULT_PER_PAGE)
+                .clamp(1, MAX_PER_PAGE),
+            sort: self
+                .sort
+                .filter(|sort| sorts.contains(sort))
+                .unwrap_or(default_sort),
+        }
+    }
+}
+
+/// A validated page selection
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PageRequest {
+    pub page: usize,
+    pub per_page: usize,
+    pub sort: SortOrder,
+}
+
+impl Default for PageRequest {
+    fn default() -> Self {
+        Self {
+            page: 1,
+            per_page: DEFAULT_PER_PAGE,
+            sort: SortOrder::Newest,
+        }
+    }
+}
+
+impl PageRequest {
+    /// Sort `posts` and cut out the requested page
+    ///
+    /// # Arguments
+    /// * `posts` - Every post of the listing
+    /// * `views` - View counts by slug; only read for [`SortOrder::Views`]
+    pub fn paginate(&self, mut posts: Vec<Post>, views: &HashMap<String, u64>) -> Page<Post> {
+        sort_posts(&mut posts, self.sort, views);
+        let total = posts.len();
+        let items = posts
+            .into_iter()
+            .skip((self.page - 1).saturating_mul(self.per_page))
+            .take(self.per_page)
+            .collect();
+        Page {
+            items,
+            page: self.page,
+            per_page: self.per_page,
+            total,
+            sort: self.sort,
+        }
+    }
+}
+
+/// One page of a listing together with the size of the whole listing
+#[derive(Debug, Clone)]
+pub struct Page<T> {
+    pub items: Vec<T>,
+    pub page: usize,
+    pub per_page: usize,
+    pub total: usize,
+    pub sort: SortOrder,
+}
+
+impl<T> Page<T> {
+    pub fn total_pages(&self) -> usize {
+        self.total.div_ceil(self.per_page).max(1)
+    }
+
+    pub fn has_prev(&self) -> bool {
+        self.page > 1
+    }
+
+    pub fn has_next(&self) -> bool {
+        self.page < self.total_pages()
+    }
+}
+
+/// Order `posts` in place; [`SortOrder::Relevance`] leaves them untouched
+pub fn sort_posts(posts: &mut [Post], sort: SortOrder, views: &HashMap<String, u64>) {
+    let newest = |a: &Post, b: &Post| {
+        b.markdown
+            .date
+            .cmp(&a.markdown.date)
+            .then_with(|| a.markdown.slug.cmp(&b.markdown.slug))
+    };
+    match sort {
+        SortOrder::Newest => posts.sort_by(newest),
+        SortOrder::Oldest => posts.sort_by(|a, b| newest(b, a)),
+        SortOrder::Title => posts.sort_by(|a, b| {
+            a.markdown
+                .title
+                .to_lowercase()
+                .cmp(&b.markdown.title.to_lowercase())
+                .then_with(|| newest(a, b))
+        }),
+        SortOrder::Episode => posts.sort_by(|a, b| {
+            let key = |post: &Post| {
+                post.markdown
+                    .series
+                    .as_ref()
+                    .map(|series| (series.title.clone(), series.ep))
+            };
+            match (key(a), key(b)) {
+                (Some(a_key), Some(b_key)) => a_key.cmp(&b_key).then_with(|| newest(a, b)),
+                (Some(_), None) => std::cmp::Ordering::Less,
+                (None, Some(_)) => std::cmp::Ordering::Greater,
+                (None, None) => newest(a, b),
+            }
+        }),
+        SortOrder::Views => posts.sort_by(|a, b| {
+            let count = |post: &Post| views.get(&post.markdown.slug).copied().unwrap_or(0);
+            count(b).cmp(&count(a)).then_with(|| newest(a, b))
+        }),
+        SortOrder::Relevance => {}
+    }
+}
+
+/// Navigation for a rendered page: prev/next URLs and a link per available sort
+pub struct PageLinks {
+    pub page: usize,
+    pub total_pages: usize,
+    pub total: usize,
+    pub prev: Option<String>,
+    pub next: Option<String>,
+    /// One link for every sort the listing offers
+    pub sorts: Vec<SortLink>,
+}
+
+pub struct SortLink {
+    pub label: &'static str,
+    pub url: String,
+    pub selected: bool,
+}
+
+impl PageLinks {
+    /// Build links that keep the listing's own query parameters
+    ///
+    /// # Arguments
+    /// * `page` - The page being rendered
+    /// * `path` - Path of the listing, e.g. `/posts/by-tag`
+    /// * `params` - Query parameters selecting the listing, e.g. `[("tag", "rust")]`
+    /// * `sorts` - Orders the listing offers; the first is its default and is left out of URLs
+    pub fn new<T>(
+        page: &Page<T>,
+        path: &str,
+        params: &[(&str, String)],
+        sorts: &[SortOrder],
+    ) -> Self {
+        let default_sort = sorts.first().copied().unwrap_or(SortOrder::Newest);
+        let url = |number: usize, sort: SortOrder| {
+            let mut query: Vec<(&str, String)> = params.to_vec();
+            if number > 1 {
+                query.push(("page", number.to_string()));
+            }
+            if page.per_page != DEFAULT_PER_PAGE {
+                query.push(("per_page", page.per_page.to_string()));
+            }
+            if sort != default_sort {
+                query.push(("sort", sort.as_str().to_string()));
+            }
+            let query = serde_urlencoded::to_string(&query).unwrap_or_default();
+            if query.is_empty() {
+                path.to_string()
+            } else {
+                format!("{path}?{query}")
+            }
+        };
+
+        Self {
+            page: page.page,
+            total_pages: page.total_pages(),
+            total: page.total,
+            prev: page.has_prev().then(|| url(page.page - 1, page.sort)),
+            next: page.has_next().then(|| url(page.page + 1, page.sort)),
+            sorts: sorts
+                .iter()
+                .map(|sort| SortLink {
+                    label: sort.label(),
+                    url: url(1, *sort),
+                    selected: *sort == page.sort,
+                })
+                .collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_to_request_clamps_and_rejects_unoffered_sorts() {
+        let query = PageQuery {
+            page: Some(0),
+            per_page: Some(1000),
+            sort: Some(SortOrder::Relevance),
+        };
+        assert_eq!(
+            query.to_request(&[SortOrder::Newest, SortOrder::Title]),
+            PageRequest {
+                page: 1,
+                per_page: MAX_PER_PAGE,
+                sort: SortOrder::Newest,
+            }
+        );
+    }
+
+    #[test]
+    fn test_links_keep_listing_params() {
+        let page = Page::<()> {
+            items: Vec::new(),
+            page: 2,
+            per_page: DEFAULT_PER_PAGE,
+            total: 25,
+            sort: SortOrder::Title,
+        };
+        let links = PageLinks::new(
+            &page,
+            "/posts/by-tag",
+            &[("tag", "c++".to_string())],
+            &[SortOrder::Newest, SortOrder::Title],
+        );
+        assert_eq!(links.total_pages, 3);
+        assert_eq!(
+            links.prev.as_deref(),
+            Some("/posts/by-tag?tag=c%2B%2B&sort=title")
+        );
+        assert_eq!(
+            links.next.as_deref(),
+            Some("/posts/by-tag?tag=c%2B%2B&page=3&sort=title")
+        );
+        assert_eq!(links.sorts[0].url, "/posts/by-tag?tag=c%2B%2B");
+        assert!(links.sorts[1].selected);
+    }
+}
@@ -0,0 +1,271 @@
+/// On-demand and scheduled content reloads with debouncing and status reporting
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use chrono::{DateTime, Local};
+use serde::Serialize;
+use tokio::sync::mpsc;
+
+use crate::{
+    data::Post,
+    db::{Database, InMemDatabase},
+    diagnostics::Diagnostic,
+    error::ApplicationError,
+    http::state::AppState,
+    repo_utils::RepositorySync,
+    snapshot::Snapshot,
+    sqlite::SqliteDatabase,
+    watch::LocalWatch,
+};
+
+/// Where the blog content is loaded from
+pub enum ContentSource {
+    /// A remote git repository kept in a local mirror
+    Git(Box<RepositorySync>),
+    /// A plain directory on disk, shared with the filesystem watcher applying edits to it and
+    /// re-read in full on every reload
+    Local(Arc<tokio::sync::Mutex<LocalWatch>>),
+}
+
+/// Everything a single [`ContentSource::load`] produced
+pub struct LoadedContent {
+    pub posts: Vec<Post>,
+    /// Commit the posts came from, for git sources
+    pub commit: Option<String>,
+    /// Post files that were skipped
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+/// A database built from a source, with what is needed to describe it
+pub struct ContentSnapshot {
+    pub db: InMemDatabase,
+    pub commit: Option<String>,
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+impl ContentSource {
+    /// Load every valid post from the source, along with the commit and skipped files
+    pub async fn load(&mut self) -> Result<LoadedContent, ApplicationError> {
+        match self {
+            ContentSource::Git(repo_sync) => {
+                let outcome = repo_sync.sync().await?;
+                if !outcome.is_unchanged() {
+                    println!(
+                        "Synced content to commit {} ({} posts, {} resources changed)",
+                        outcome.commit,
+                        outcome.changed_posts.len(),
+                        outcome.changed_resources.len()
+                    );
+                }
+                Ok(LoadedContent {
+                    posts: repo_sync.posts(),
+                    commit: repo_sync.commit(),
+                    diagnostics: repo_sync.diagnostics(),
+                })
+            }
+            ContentSource::Local(local_watch) => {
+                let mut local_watch = local_watch.lock().await;
+                local_watch.rescan().await?;
+                Ok(LoadedContent {
+                    posts: local_watch.posts(),
+                    commit: None,
+                    diagnostics: local_watch.diagnostics(),
+                })
+            }
+        }
+    }
+
+    /// Branch tracked by a git source, once known
+    pub fn branch(&self) -> Option<String> {
+        match self {
+            ContentSource::Git(repo_sync) => repo_sync.branch().map(str::to_string),
+            ContentSource::Local(_) => None,
+        }
+    }
+}
+
+/// Build a fresh database from everything the source currently holds
+pub async fn build_database(
+    source: &mut ContentSource,
+) -> Result<ContentSnapshot, ApplicationError> {
+    let content = source.load().await?;
+    let mut db = InMemDatabase::new();
+    for post in content.posts {
+        db.insert_parsed_to_database(post)?;
+    }
+    Ok(ContentSnapshot {
+        db,
+        commit: content.commit,
+        diagnostics: content.diagnostics,
+    })
+}
+
+/// What asked for a reload
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ReloadTrigger {
+    Startup,
+    Schedule,
+    Admin,
+    Webhook,
+}
+
+/// Outcome of the most recent reload, as reported by the admin status endpoint
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ReloadStatus {
+    /// A reload has been requested and is waiting out the debounce window or running
+    pub pending: bool,
+    pub last_trigger: Option<ReloadTrigger>,
+    pub last_attempt: Option<DateTime<Local>>,
+    pub last_success: Option<DateTime<Local>>,
+    pub last_error: Option<String>,
+    pub commit: Option<String>,
+    pub branch: Option<String>,
+    pub posts: usize,
+    /// Post files skipped by the last successful reload, listed by `/admin/diagnostics`
+    pub diagnostics: usize,
+}
+
+/// Handle to the background task that rebuilds the database and swaps it into [`AppState`]
+///
+/// Requests arriving within the debounce window of each other are coalesced into a
+/// single reload, so a burst of pushes only re-syncs the content once.
+#[derive(Clone)]
+pub struct Reloader {
+    requests: mpsc::UnboundedSender<ReloadTrigger>,
+    status: Arc<Mutex<ReloadStatus>>,
+}
+
+impl Reloader {
+    /// Spawn the reload task for `source`, publishing into the database held by `state`
+    pub fn spawn(source: ContentSource, state: &AppState, debounce: Duration) -> Self {
+        Self::spawn_with_store(source, state, debounce, None)
+    }
+
+    /// Like [`Reloader::spawn`], also saving every successful reload into `store`
+    ///
+    /// The store holds the last good content so a restart can serve it before syncing.
+    pub fn spawn_with_store(
+        source: ContentSource,
+        state: &AppState,
+        debounce: Duration,
+        store: Option<SqliteDatabase>,
+    ) -> Self {
+        let (requests, receiver) = mpsc::unbounded_channel();
+        let status = Arc::new(Mutex::new(ReloadStatus {
+            commit: state.snapshot().commit.clone(),
+            branch: source.branch(),
+            posts: state.snapshot().db.by_slug.len(),
+            diagnostics: state.snapshot().diagnostics.len(),
+            // The initial load happens before the reloader exists and counts as the first reload
+            last_trigger: Some(ReloadTrigger::Startup),
+            last_attempt: Some(Local::now()),
+            last_success: Some(Local::now()),
+            ..ReloadStatus::default()
+        }));
+
+        tokio::spawn(run_reloads(
+            source,
+            state.clone(),
+            receiver,
+            status.clone(),
+            debounce,
+            store,
+        ));
+
+        Self { requests, status }
+    }
+
+    /// Ask for a reload; returns immediately and never blocks on the reload itself
+    pub fn request(&self, trigger: ReloadTrigger) {
+        if let Ok(mut status) = self.status.lock() {
+            status.pending = true;
+            status.last_trigger = Some(trigger);
+        }
+        if self.requests.send(trigger).is_err() {
+            eprintln!("Reload task is no longer running, ignoring {trigger:?} request");
+        }
+    }
+
+    pub fn status(&self) -> ReloadStatus {
+        self.status
+            .lock()
+            .map(|status| status.clone())
+            .unwrap_or_default()
+    }
+}
+
+async fn run_reloads(
+    mut source: ContentSource,
+    state: AppState,
+    mut receiver: mpsc::UnboundedReceiver<ReloadTrigger>,
+    status: Arc<Mutex<ReloadStatus>>,
+    debounce: Duration,
+    mut store: Option<SqliteDatabase>,
+) {
+    while let Some(mut trigger) = receiver.recv().await {
+        // Coalesce every request that arrives while the debounce window is open
+        while let Ok(Some(next)) = tokio::time::timeout(debounce, receiver.recv()).await {
+            trigger = next;
+        }
+
+        println!("Reloading content ({trigger:?})");
+        let result = build_database(&mut source).await;
+        let now = Local::now();
+
+        // Publish and save before taking the status lock, so status reads and new requests
+        // never wait on the database write
+        let published = result.map(|snapshot| {
+            state.content.publish(
+                Snapshot::new(snapshot.db)
+                    .with_commit(snapshot.commit)
+                    .with_diagnostics(snapshot.diagnostics),
+            )
+        });
+        if let (Ok(published), Some(mut content_store)) = (&published, store.take()) {
+            let published = published.clone();
+            let saved = tokio::task::spawn_blocking(move || {
+                let saved =
+                    content_store.replace_content(&published.db, published.commit.as_deref());
+                (content_store, saved)
+            })
+            .await;
+            match saved {
+                Ok((content_store, saved)) => {
+                    store = Some(content_store);
+                    if let Err(err) = saved {
+                        eprintln!("Failed to save content to the database: {err}");
+                    }
+                }
+                Err(err) => eprintln!("Stopped saving content to the database: {err}"),
+            }
+        }
+
+        let mut status = match status.lock() {
+            Ok(guard) => guard,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        status.last_attempt = Some(now);
+        status.last_trigger = Some(trigger);
+        status.branch = source.branch();
+        status.pending = !receiver.is_empty();
+
+        match published {
+            Ok(snapshot) => {
+                status.posts = snapshot.db.by_slug.len();
+                status.commit = snapshot.commit.clone();
+                status.diagnostics = snapshot.diagnostics.len();
+                status.last_success = Some(now);
+                status.last_error = None;
+                println!(
+                    "Reloaded {} posts, skipped {}",
+                    status.posts, status.diagnostics
+                );
+            }
+            Err(err) => {
+                eprintln!("Failed to reload posts: {err}");
+                status.last_error = Some(err.to_string());
+            }
+        }
+    }
+}
@@ -1,11 +1,14 @@
+use std::collections::{BTreeMap, BTreeSet};
 use std::fs;
 use std::path::{Path, PathBuf};
 
-use git2::Repository as GitRepository;
+use git2::{build::CheckoutBuilder, Delta, Oid, Repository as GitRepository, ResetType};
 
 use crate::{
-    data::{parse_to_data, Post},
+    data::{parse_with_links, parse_with_resources, Post},
+    diagnostics::{duplicate_slugs, log_diagnostics, Diagnostic},
     error::ApplicationError,
+    resources::ResourceManifest,
 };
 
 pub struct Repository {
@@ -20,41 +23,105 @@ impl TryFrom<&Path> for Repository {
         if value.is_dir() {
             let posts = value.join("posts");
             let resources = value.join("resources");
-            return Ok(Repository { posts, resources });
+            Ok(Repository { posts, resources })
         } else {
-            return Err(std::io::Error::new(
+            Err(std::io::Error::new(
                 std::io::ErrorKind::InvalidInput,
                 "given path isn't a dir",
-            ));
+            ))
         }
     }
 }
 
-pub async fn get_posts_from_repository(repo: Repository) -> Result<Vec<Post>, ApplicationError> {
-    let dir_entry = std::fs::read_dir(repo.posts)?;
-    let mut result: Vec<Post> = Vec::new();
-    for entry in dir_entry {
-        let entry = entry.expect("should be file");
-        let path = entry.path();
+/// Posts that parsed, plus a diagnostic for every post file that did not
+#[derive(Debug, Clone, Default)]
+pub struct Ingestion {
+    pub posts: Vec<Post>,
+    pub diagnostics: Vec<Diagnostic>,
+    /// The repository resources the posts link to
+    pub resources: ResourceManifest,
+}
+
+/// Parse every post in the repository, skipping the ones that fail
+///
+/// # Arguments
+/// * `repo` - The repository to read posts from
+///
+/// # Returns
+/// The valid posts in path order, a diagnostic for each skipped file and the fingerprinted
+/// resources the posts link to
+pub async fn ingest_repository(repo: &Repository) -> Result<Ingestion, ApplicationError> {
+    let root = repo.posts.parent().unwrap_or(&repo.posts);
+    let resources = ResourceManifest::build(&repo.resources)?;
+    let mut posts = BTreeMap::new();
+    let mut diagnostics = Vec::new();
+
+    for entry in fs::read_dir(&repo.posts)? {
+        let path = match entry {
+            Ok(entry) => entry.path(),
+            Err(err) => {
+                let relative = repo.posts.strip_prefix(root).unwrap_or(&repo.posts);
+                diagnostics.push(Diagnostic::new(relative, err.to_string()));
+                continue;
+            }
+        };
+        if !path.is_file() {
+            continue;
+        }
+
+        let relative = path.strip_prefix(root).unwrap_or(&path).to_path_buf();
+        match parse_with_resources(&path, &resources).await {
+            Ok(post) => {
+                posts.insert(relative, post);
+            }
+            Err(err) => diagnostics.push(Diagnostic::from_error(relative, &err)),
+        }
+    }
 
-        let post = parse_to_data(&path).await?;
-        result.push(post);
+    for duplicate in duplicate_slugs(&posts) {
+        posts.remove(&duplicate.path);
+        diagnostics.push(duplicate);
     }
-    Ok(result)
+    diagnostics.sort_by(|a, b| a.path.cmp(&b.path));
+
+    Ok(Ingestion {
+        posts: posts.into_values().collect(),
+        diagnostics,
+        resources,
+    })
+}
+
+/// Parse every post in the repository, skipping and logging the ones that fail
+pub async fn get_posts_from_repository(repo: Repository) -> Result<Vec<Post>, ApplicationError> {
+    let ingestion = ingest_repository(&repo).await?;
+    log_diagnostics(&ingestion.diagnostics);
+    Ok(ingestion.posts)
 }
 
 /// Load posts from a local repository path (for debugging)
 ///
 /// # Arguments
 /// * `local_path` - The path to a local repository directory
+/// * `resources_dir` - Where the repository resources are published for `/static/misc/`
 ///
 /// # Returns
 /// A vector of parsed posts from the local repository
 ///
 /// # Effects
-/// - Copies all resources to static/misc/
+/// - Copies all resources to `resources_dir`, under their plain and fingerprinted names
 /// - Returns parsed posts ready for database insertion
-pub async fn load_from_local_path(local_path: &str) -> Result<Vec<Post>, ApplicationError> {
+pub async fn load_from_local_path(
+    local_path: &str,
+    resources_dir: &Path,
+) -> Result<Vec<Post>, ApplicationError> {
+    Ok(ingest_local_path(local_path, resources_dir).await?.posts)
+}
+
+/// Like [`load_from_local_path`], also returning diagnostics for the skipped posts
+pub async fn ingest_local_path(
+    local_path: &str,
+    resources_dir: &Path,
+) -> Result<Ingestion, ApplicationError> {
     let repo_path = Path::new(local_path);
 
     if !repo_path.exists() {
@@ -69,46 +136,37 @@ pub async fn load_from_local_path(local_path: &str) -> Result<Vec<Post>, Applica
     // Create Repository structure from local path
     let repo = Repository::try_from(repo_path)?;
 
-    // Copy resources to static/misc/
-    let static_misc = Path::new("static/misc");
-    if !static_misc.exists() {
-        fs::create_dir_all(static_misc)?;
-        println!("Created directory: static/misc/");
-    }
-
-    // Copy all files from resources directory
-    if repo.resources.exists() {
-        println!(
-            "Copying resources from {:?} to {:?}",
-            repo.resources, static_misc
-        );
-        copy_dir_all(&repo.resources, static_misc)?;
-        println!("Resources copied successfully");
-    } else {
-        println!("Warning: No resources directory found in repository");
-    }
-
     // Get all posts from the repository
-    let posts = get_posts_from_repository(repo).await?;
+    let ingestion = ingest_repository(&repo).await?;
+    log_diagnostics(&ingestion.diagnostics);
+    publish_resources(&repo, &ingestion.resources, resources_dir)?;
 
-    println!("Loaded {} posts from local repository", posts.len());
+    println!(
+        "Loaded {} posts from local repository, skipped {}",
+        ingestion.posts.len(),
+        ingestion.diagnostics.len()
+    );
 
-    Ok(posts)
+    Ok(ingestion)
 }
 
 /// Clone a GitHub repository and ingest posts into the database
 ///
 /// # Arguments
 /// * `repo_url` - The GitHub repository URL (e.g., "https://github.com/user/repo")
+/// * `resources_dir` - Where the repository resources are published for `/static/misc/`
 ///
 /// # Returns
 /// A vector of parsed posts from the repository
 ///
 /// # Effects
 /// - Clones the repository to a temporary directory
-/// - Copies all resources to static/misc/
+/// - Copies all resources to `resources_dir`, under their plain and fingerprinted names
 /// - Returns parsed posts ready for database insertion
-pub async fn clone_and_ingest_repository(repo_url: &str) -> Result<Vec<Post>, ApplicationError> {
+pub async fn clone_and_ingest_repository(
+    repo_url: &str,
+    resources_dir: &Path,
+) -> Result<Vec<Post>, ApplicationError> {
     // Create a temporary directory for cloning
     let temp_dir = tempfile::tempdir()?;
     let clone_path = temp_dir.path();
@@ -123,52 +181,437 @@ pub async fn clone_and_ingest_repository(repo_url: &str) -> Result<Vec<Post>, Ap
     // Create Repository structure from cloned path
     let repo = Repository::try_from(clone_path)?;
 
-    // Copy resources to static/misc/
-    let static_misc = Path::new("static/misc");
-    if !static_misc.exists() {
-        fs::create_dir_all(static_misc)?;
-        println!("Created directory: static/misc/");
+    // Get all posts from the repository
+    let ingestion = ingest_repository(&repo).await?;
+    log_diagnostics(&ingestion.diagnostics);
+    publish_resources(&repo, &ingestion.resources, resources_dir)?;
+    let posts = ingestion.posts;
+
+    println!("Loaded {} posts from repository", posts.len());
+
+    Ok(posts)
+}
+
+/// Copy the repository `resources/` into `resources_dir`, which is served as `/static/misc/`
+fn publish_resources(
+    repo: &Repository,
+    resources: &ResourceManifest,
+    resources_dir: &Path,
+) -> Result<(), ApplicationError> {
+    if !resources_dir.exists() {
+        fs::create_dir_all(resources_dir)?;
+        println!("Created directory: {}", resources_dir.display());
     }
 
-    // Copy all files from resources directory
     if repo.resources.exists() {
         println!(
             "Copying resources from {:?} to {:?}",
-            repo.resources, static_misc
+            repo.resources, resources_dir
         );
-        copy_dir_all(&repo.resources, static_misc)?;
-        println!("Resources copied successfully");
+        resources.publish(&repo.resources, resources_dir)?;
+        println!("Copied {} resources", resources.len());
     } else {
         println!("Warning: No resources directory found in repository");
     }
+    Ok(())
+}
 
-    // Get all posts from the repository
-    let posts = get_posts_from_repository(repo).await?;
+/// Runtime directory the repository `resources/` of remote content are published into
+pub const DEFAULT_RESOURCES_DIR: &str = "/opt/personal/runtime/resources";
 
-    println!("Loaded {} posts from repository", posts.len());
+/// A persistent local mirror of the content repository.
+///
+/// The first [`RepositorySync::sync`] clones the remote (or reuses a mirror left by a
+/// previous run) and parses every post. Later calls fetch and fast-forward the mirror
+/// and only re-parse the files under `posts/` and `resources/` that changed between
+/// the previously served commit and the new one.
+pub struct RepositorySync {
+    remote_url: String,
+    mirror_path: PathBuf,
+    branch: Option<String>,
+    resources_dir: PathBuf,
+    commit: Option<Oid>,
+    /// Resources of the served commit, which posts link to by fingerprint
+    resources: ResourceManifest,
+    posts: BTreeMap<PathBuf, Post>,
+    /// Posts in the served commit that failed to parse
+    failures: BTreeMap<PathBuf, Diagnostic>,
+    links: ResourceLinks,
+}
 
-    Ok(posts)
+/// Summary of a single [`RepositorySync::sync`] run
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SyncOutcome {
+    /// Commit the mirror points at after the sync
+    pub commit: String,
+    /// Whether every post was parsed rather than only the changed ones
+    pub full_reload: bool,
+    /// Paths under `posts/` that were re-parsed or dropped
+    pub changed_posts: Vec<PathBuf>,
+    /// Paths under `resources/` that were copied or removed
+    pub changed_resources: Vec<PathBuf>,
+}
+
+impl SyncOutcome {
+    pub fn is_unchanged(&self) -> bool {
+        !self.full_reload && self.changed_posts.is_empty() && self.changed_resources.is_empty()
+    }
 }
 
-/// Recursively copy all contents from source directory to destination directory
-fn copy_dir_all(src: &Path, dst: &Path) -> std::io::Result<()> {
-    if !dst.exists() {
-        fs::create_dir_all(dst)?;
+impl RepositorySync {
+    /// Create a sync handle for `remote_url`, mirrored into `mirror_path`
+    pub fn new(remote_url: impl Into<String>, mirror_path: impl Into<PathBuf>) -> Self {
+        Self {
+            remote_url: remote_url.into(),
+            mirror_path: mirror_path.into(),
+            branch: None,
+            resources_dir: PathBuf::from(DEFAULT_RESOURCES_DIR),
+            commit: None,
+            resources: ResourceManifest::default(),
+            posts: BTreeMap::new(),
+            failures: BTreeMap::new(),
+            links: ResourceLinks::default(),
+        }
+    }
+
+    /// Track `branch` instead of the remote's default branch
+    pub fn with_branch(mut self, branch: impl Into<String>) -> Self {
+        self.branch = Some(branch.into());
+        self
+    }
+
+    /// Publish resources into `resources_dir` instead of [`DEFAULT_RESOURCES_DIR`]
+    pub fn with_resources_dir(mut self, resources_dir: impl Into<PathBuf>) -> Self {
+        self.resources_dir = resources_dir.into();
+        self
+    }
+
+    /// Commit currently served, as a hex string
+    pub fn commit(&self) -> Option<String> {
+        self.commit.map(|oid| oid.to_string())
+    }
+
+    /// Branch being tracked, once set explicitly or discovered from the first clone
+    pub fn branch(&self) -> Option<&str> {
+        self.branch.as_deref()
+    }
+
+    /// All posts parsed from the served commit
+    pub fn posts(&self) -> Vec<Post> {
+        self.posts.values().cloned().collect()
+    }
+
+    /// Posts in the served commit that were skipped, and why
+    pub fn diagnostics(&self) -> Vec<Diagnostic> {
+        let mut diagnostics: Vec<Diagnostic> = self.failures.values().cloned().collect();
+        diagnostics.extend(duplicate_slugs(&self.posts));
+        diagnostics.sort_by(|a, b| a.path.cmp(&b.path));
+        diagnostics
+    }
+
+    /// Parse a post of the served commit, recording a diagnostic instead of failing
+    async fn parse_post(&mut self, relative: PathBuf) {
+        let absolute = self.mirror_path.join(&relative);
+        match parse_with_links(&absolute, &self.resources).await {
+            Ok((post, links)) => {
+                self.failures.remove(&relative);
+                self.links.insert(relative.clone(), links);
+                self.posts.insert(relative, post);
+            }
+            Err(err) => {
+                let diagnostic = Diagnostic::from_error(relative.clone(), &err);
+                log_diagnostics(std::slice::from_ref(&diagnostic));
+                self.posts.remove(&relative);
+                self.links.remove(&relative);
+                self.failures.insert(relative, diagnostic);
+            }
+        }
+    }
+
+    /// Bring the mirror up to date with the remote and refresh the parsed posts
+    ///
+    /// # Returns
+    /// A [`SyncOutcome`] describing the served commit and which files were reprocessed
+    ///
+    /// # Effects
+    /// - Clones the remote into the mirror path if no mirror exists yet
+    /// - Fetches and fast-forwards the tracked branch otherwise
+    /// - Copies added or modified resources to the resources directory and removes deleted ones
+    pub async fn sync(&mut self) -> Result<SyncOutcome, ApplicationError> {
+        // libgit2 handles are not `Send`, so all git work happens before the first await
+        let (new_commit, changes) = {
+            let git_repo = self.open_or_clone()?;
+            let new_commit = self.fetch_and_fast_forward(&git_repo)?;
+            let changes = match self.commit {
+                Some(old_commit) => Some(changed_paths(&git_repo, old_commit, new_commit)?),
+                None => None,
+            };
+            (new_commit, changes)
+        };
+
+        let outcome = match changes {
+            Some((removed, updated)) => self.apply_diff(new_commit, removed, updated).await?,
+            None => self.full_reload(new_commit).await?,
+        };
+
+        self.commit = Some(new_commit);
+        Ok(outcome)
+    }
+
+    fn open_or_clone(&mut self) -> Result<GitRepository, ApplicationError> {
+        if self.mirror_path.join(".git").exists() {
+            let git_repo = GitRepository::open(&self.mirror_path)?;
+            git_repo.remote_set_url("origin", &self.remote_url)?;
+            return Ok(git_repo);
+        }
+
+        if self.mirror_path.exists() {
+            fs::remove_dir_all(&self.mirror_path)?;
+        }
+        fs::create_dir_all(&self.mirror_path)?;
+
+        println!(
+            "Cloning repository from {} to {:?}",
+            self.remote_url, self.mirror_path
+        );
+        let mut builder = git2::build::RepoBuilder::new();
+        if let Some(branch) = &self.branch {
+            builder.branch(branch);
+        }
+        Ok(builder.clone(&self.remote_url, &self.mirror_path)?)
+    }
+
+    fn fetch_and_fast_forward(
+        &mut self,
+        git_repo: &GitRepository,
+    ) -> Result<Oid, ApplicationError> {
+        let branch = match &self.branch {
+            Some(branch) => branch.clone(),
+            None => {
+                let head = git_repo.head()?;
+                let branch = head.shorthand().unwrap_or("main").to_string();
+                self.branch = Some(branch.clone());
+                branch
+            }
+        };
+
+        let mut remote = git_repo.find_remote("origin")?;
+        let refspec = format!("+refs/heads/{branch}:refs/remotes/origin/{branch}");
+        remote.fetch(&[refspec.as_str()], None, None)?;
+
+        let remote_ref = git_repo.find_reference(&format!("refs/remotes/origin/{branch}"))?;
+        let target = remote_ref.peel_to_commit()?;
+        let annotated = git_repo.find_annotated_commit(target.id())?;
+        let (analysis, _) = git_repo.merge_analysis(&[&annotated])?;
+
+        let local_ref_name = format!("refs/heads/{branch}");
+        if analysis.is_up_to_date() && git_repo.find_reference(&local_ref_name).is_ok() {
+            return Ok(git_repo.refname_to_id(&local_ref_name)?);
+        }
+
+        if analysis.is_fast_forward() || analysis.is_up_to_date() {
+            match git_repo.find_reference(&local_ref_name) {
+                Ok(mut local_ref) => {
+                    local_ref.set_target(target.id(), "fast-forward")?;
+                }
+                Err(_) => {
+                    git_repo.reference(&local_ref_name, target.id(), true, "track remote")?;
+                }
+            }
+            git_repo.set_head(&local_ref_name)?;
+            git_repo.checkout_head(Some(CheckoutBuilder::default().force()))?;
+        } else {
+            println!("Branch {branch} diverged from the remote, resetting the mirror");
+            git_repo.branch(&branch, &target, true)?;
+            git_repo.set_head(&local_ref_name)?;
+            git_repo.reset(target.as_object(), ResetType::Hard, None)?;
+        }
+
+        Ok(target.id())
     }
 
-    for entry in fs::read_dir(src)? {
-        let entry = entry?;
-        let file_type = entry.file_type()?;
-        let src_path = entry.path();
-        let dst_path = dst.join(entry.file_name());
+    async fn full_reload(&mut self, commit: Oid) -> Result<SyncOutcome, ApplicationError> {
+        let repo = Repository::try_from(self.mirror_path.as_path())?;
 
-        if file_type.is_dir() {
-            copy_dir_all(&src_path, &dst_path)?;
+        self.resources = ResourceManifest::build(&repo.resources)?;
+        if repo.resources.exists() {
+            self.resources
+                .publish(&repo.resources, &self.resources_dir)?;
         } else {
-            fs::copy(&src_path, &dst_path)?;
-            println!("  Copied: {:?}", entry.file_name());
+            println!("Warning: No resources directory found in repository");
+        }
+
+        self.posts.clear();
+        self.failures.clear();
+        self.links = ResourceLinks::default();
+        let mut changed_posts = Vec::new();
+        for entry in fs::read_dir(&repo.posts)? {
+            let path = entry?.path();
+            if !path.is_file() {
+                continue;
+            }
+            let relative = path
+                .strip_prefix(&self.mirror_path)
+                .unwrap_or(&path)
+                .to_path_buf();
+            changed_posts.push(relative.clone());
+            self.parse_post(relative).await;
         }
+        changed_posts.sort();
+
+        println!(
+            "Loaded {} posts from commit {}, skipped {}",
+            self.posts.len(),
+            commit,
+            self.failures.len()
+        );
+
+        Ok(SyncOutcome {
+            commit: commit.to_string(),
+            full_reload: true,
+            changed_posts,
+            changed_resources: Vec::new(),
+        })
     }
 
-    Ok(())
+    async fn apply_diff(
+        &mut self,
+        new_commit: Oid,
+        removed: Vec<PathBuf>,
+        updated: Vec<PathBuf>,
+    ) -> Result<SyncOutcome, ApplicationError> {
+        let mut outcome = SyncOutcome {
+            commit: new_commit.to_string(),
+            full_reload: false,
+            changed_posts: Vec::new(),
+            changed_resources: Vec::new(),
+        };
+
+        // Resources first, so the posts parsed below link to their new fingerprints
+        for path in &removed {
+            if let Some(resource) = resource_path(path) {
+                let target = self.resources_dir.join(resource);
+                if target.exists() {
+                    fs::remove_file(&target)?;
+                }
+                self.resources.remove(&resource.to_string_lossy());
+                outcome.changed_resources.push(path.clone());
+            }
+        }
+        for path in &updated {
+            if let Some(resource) = resource_path(path) {
+                self.resources.publish_one(
+                    &self.mirror_path.join(path),
+                    &self.resources_dir,
+                    &resource.to_string_lossy(),
+                )?;
+                outcome.changed_resources.push(path.clone());
+            }
+        }
+
+        for path in removed {
+            if is_post_path(&path) {
+                self.posts.remove(&path);
+                self.failures.remove(&path);
+                self.links.remove(&path);
+                outcome.changed_posts.push(path);
+            }
+        }
+
+        let mut reparsed: BTreeSet<PathBuf> = updated
+            .into_iter()
+            .filter(|path| is_post_path(path))
+            .collect();
+        reparsed.extend(self.links.posts_linking_to(&outcome.changed_resources));
+        for path in reparsed {
+            self.parse_post(path.clone()).await;
+            outcome.changed_posts.push(path);
+        }
+
+        if outcome.is_unchanged() {
+            return Ok(outcome);
+        }
+        println!(
+            "Updated to commit {}: {} posts and {} resources changed",
+            new_commit,
+            outcome.changed_posts.len(),
+            outcome.changed_resources.len()
+        );
+
+        Ok(outcome)
+    }
+}
+
+/// The resources each parsed post links to, so a changed resource only re-renders the posts
+/// that embed its fingerprint
+#[derive(Debug, Clone, Default)]
+pub(crate) struct ResourceLinks {
+    /// Resource paths relative to `resources/`, by post path relative to the content root
+    by_post: BTreeMap<PathBuf, BTreeSet<String>>,
+}
+
+impl ResourceLinks {
+    pub(crate) fn insert(&mut self, post: PathBuf, resources: BTreeSet<String>) {
+        self.by_post.insert(post, resources);
+    }
+
+    pub(crate) fn remove(&mut self, post: &Path) {
+        self.by_post.remove(post);
+    }
+
+    /// Posts to re-parse after the resources at `changed` were edited, added or removed
+    ///
+    /// # Arguments
+    /// * `changed` - Resource paths relative to the content root, e.g. `resources/image.png`
+    pub(crate) fn posts_linking_to(&self, changed: &[PathBuf]) -> BTreeSet<PathBuf> {
+        let changed: BTreeSet<String> = changed
+            .iter()
+            .filter_map(|path| resource_path(path))
+            .map(|resource| resource.to_string_lossy().into_owned())
+            .collect();
+        self.by_post
+            .iter()
+            .filter(|(_, resources)| !resources.is_disjoint(&changed))
+            .map(|(post, _)| post.clone())
+            .collect()
+    }
+}
+
+/// Paths removed and paths added or modified between two commits
+fn changed_paths(
+    git_repo: &GitRepository,
+    old_commit: Oid,
+    new_commit: Oid,
+) -> Result<(Vec<PathBuf>, Vec<PathBuf>), ApplicationError> {
+    let mut removed: Vec<PathBuf> = Vec::new();
+    let mut updated: Vec<PathBuf> = Vec::new();
+    if old_commit == new_commit {
+        return Ok((removed, updated));
+    }
+
+    let old_tree = git_repo.find_commit(old_commit)?.tree()?;
+    let new_tree = git_repo.find_commit(new_commit)?.tree()?;
+    let diff = git_repo.diff_tree_to_tree(Some(&old_tree), Some(&new_tree), None)?;
+
+    for delta in diff.deltas() {
+        match delta.status() {
+            Delta::Deleted => removed.extend(delta.old_file().path().map(Path::to_path_buf)),
+            Delta::Renamed => {
+                removed.extend(delta.old_file().path().map(Path::to_path_buf));
+                updated.extend(delta.new_file().path().map(Path::to_path_buf));
+            }
+            _ => updated.extend(delta.new_file().path().map(Path::to_path_buf)),
+        }
+    }
+
+    Ok((removed, updated))
+}
+
+/// Posts live directly under `posts/`, mirroring [`get_posts_from_repository`]
+pub(crate) fn is_post_path(path: &Path) -> bool {
+    path.parent() == Some(Path::new("posts"))
+}
+
+pub(crate) fn resource_path(path: &Path) -> Option<&Path> {
+    path.strip_prefix("resources").ok()
 }
@@ -0,0 +1,219 @@
+/// Content-hashed file names, so assets can be cached forever and still change with every edit
+///
+/// A file is published under its own name and under a fingerprinted one carrying part of the
+/// hash of its contents, e.g. `diagram.png` and `diagram.3a7bd3e2360a.png`. Pages link to the
+/// fingerprinted name; the plain one stays available for links written before it existed.
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use sha2::{Digest, Sha256};
+
+/// URL prefix the repository `resources/` are served under
+pub const RESOURCES_URL: &str = "/static/misc";
+
+/// Hex digits of the content hash put into fingerprinted file names
+const FINGERPRINT_LENGTH: usize = 12;
+
+/// `style.css` becomes `style.<hash>.css`, keeping any leading directories
+pub fn fingerprinted_path(path: &str, contents: &[u8]) -> String {
+    let hash = hex::encode(Sha256::digest(contents));
+    let hash = &hash[..FINGERPRINT_LENGTH];
+    let (dir, name) = match path.rsplit_once('/') {
+        Some((dir, name)) => (format!("{dir}/"), name),
+        None => (String::new(), path),
+    };
+    match name.rsplit_once('.') {
+        Some((stem, extension)) if !stem.is_empty() => {
+            format!("{dir}{stem}.{hash}.{extension}")
+        }
+        _ => format!("{dir}{name}.{hash}"),
+    }
+}
+
+/// The path a fingerprinted path was made from, or `None` when it carries no fingerprint
+///
+/// Only the shape is checked; whether the hash matches any contents is up to the caller.
+pub fn strip_fingerprint(path: &str) -> Option<String> {
+    let (dir, name) = match path.rsplit_once('/') {
+        Some((dir, name)) => (format!("{dir}/"), name),
+        None => (String::new(), path),
+    };
+    let (rest, last) = name.rsplit_once('.')?;
+    if let Some((stem, hash)) = rest.rsplit_once('.') {
+        if !stem.is_empty() && is_fingerprint(hash) {
+            return Some(format!("{dir}{stem}.{last}"));
+        }
+    }
+    (!rest.is_empty() && is_fingerprint(last)).then(|| format!("{dir}{rest}"))
+}
+
+fn is_fingerprint(value: &str) -> bool {
+    value.len() == FINGERPRINT_LENGTH
+        && value
+            .bytes()
+            .all(|byte| byte.is_ascii_digit() || (b'a'..=b'f').contains(&byte))
+}
+
+/// Key of the resource a relative link points at, as listed in a [`ResourceManifest`]
+pub fn resource_name(path: &str) -> &str {
+    path.strip_prefix("./").unwrap_or(path)
+}
+
+/// Paths of every file below `dir`, relative to it and joined with `/`
+pub fn list_files(dir: &Path) -> io::Result<Vec<String>> {
+    let mut files = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let Some(name) = entry.file_name().to_str().map(str::to_string) else {
+            eprintln!("Skipping file with a non UTF-8 name in {}", dir.display());
+            continue;
+        };
+        if entry.file_type()?.is_dir() {
+            let nested = list_files(&entry.path())?;
+            files.extend(nested.into_iter().map(|file| format!("{name}/{file}")));
+        } else {
+            files.push(name);
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+/// Fingerprinted name of every file in a repository's `resources/`
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ResourceManifest {
+    /// Fingerprinted path by path, both relative to `resources/`
+    fingerprinted: BTreeMap<String, String>,
+}
+
+impl ResourceManifest {
+    /// Hash every file under `dir`; a missing directory has no resources
+    pub fn build(dir: &Path) -> io::Result<Self> {
+        let mut manifest = Self::default();
+        if !dir.is_dir() {
+            return Ok(manifest);
+        }
+        for path in list_files(dir)? {
+            let contents = fs::read(dir.join(&path))?;
+            manifest.insert(&path, &contents);
+        }
+        Ok(manifest)
+    }
+
+    pub fn len(&self) -> usize {
+        self.fingerprinted.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.fingerprinted.is_empty()
+    }
+
+    /// Record the current contents of the resource at `path`
+    pub fn insert(&mut self, path: &str, contents: &[u8]) {
+        self.fingerprinted
+            .insert(path.to_string(), fingerprinted_path(path, contents));
+    }
+
+    pub fn remove(&mut self, path: &str) {
+        self.fingerprinted.remove(path);
+    }
+
+    /// Fingerprinted path of the resource at `path`, relative to `resources/`
+    pub fn fingerprinted(&self, path: &str) -> Option<&str> {
+        self.fingerprinted
+            .get(resource_name(path))
+            .map(String::as_str)
+    }
+
+    /// URL a post links to for the relative `path`: fingerprinted when the resource exists,
+    /// as written otherwise
+    pub fn url(&self, path: &str) -> String {
+        let path = self.fingerprinted(path).unwrap_or(path);
+        format!("{RESOURCES_URL}/{path}")
+    }
+
+    /// Copy every resource from `source` into `resources_dir` under both of its names
+    ///
+    /// # Effects
+    /// - Overwrites the plain names; fingerprinted copies of earlier versions are kept, so
+    ///   pages cached before an edit keep their images
+    pub fn publish(&self, source: &Path, resources_dir: &Path) -> io::Result<()> {
+        for (path, fingerprinted) in &self.fingerprinted {
+            publish_file(&source.join(path), resources_dir, path, fingerprinted)?;
+        }
+        Ok(())
+    }
+
+    /// Record and publish the resource at `path`, read from `source`
+    pub fn publish_one(
+        &mut self,
+        source: &Path,
+        resources_dir: &Path,
+        path: &str,
+    ) -> io::Result<()> {
+        let contents = fs::read(source)?;
+        self.insert(path, &contents);
+        publish_file(source, resources_dir, path, &self.fingerprinted[path])
+    }
+}
+
+fn publish_file(
+    source: &Path,
+    resources_dir: &Path,
+    path: &str,
+    fingerprinted: &str,
+) -> io::Result<()> {
+    let target = resources_dir.join(path);
+    if let Some(parent) = target.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::copy(source, &target)?;
+    let hashed = resources_dir.join(fingerprinted);
+    if !hashed.exists() {
+        fs::copy(source, &hashed)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fingerprints_round_trip() {
+        let css = fingerprinted_path("/static/style.css", b"body {}");
+        assert!(css.starts_with("/static/style.") && css.ends_with(".css"));
+        assert_eq!(
+            css.len(),
+            "/static/style.css".len() + FINGERPRINT_LENGTH + 1
+        );
+        assert_eq!(
+            strip_fingerprint(&css).as_deref(),
+            Some("/static/style.css")
+        );
+
+        let bare = fingerprinted_path("LICENSE", b"MIT");
+        assert_eq!(strip_fingerprint(&bare).as_deref(), Some("LICENSE"));
+        let dotfile = fingerprinted_path("a/.env", b"");
+        assert_eq!(strip_fingerprint(&dotfile).as_deref(), Some("a/.env"));
+
+        assert_eq!(strip_fingerprint("/static/style.css"), None);
+        assert_eq!(strip_fingerprint("archive.tar.gz"), None);
+        assert_eq!(strip_fingerprint("photo.ABCDEF123456.jpg"), None);
+    }
+
+    #[test]
+    fn test_manifest_links_known_resources_by_hash() {
+        let mut manifest = ResourceManifest::default();
+        manifest.insert("img/diagram.png", &[1, 2, 3]);
+        let url = manifest.url("./img/diagram.png");
+        assert_ne!(url, "/static/misc/img/diagram.png");
+        assert_eq!(
+            strip_fingerprint(&url).as_deref(),
+            Some("/static/misc/img/diagram.png")
+        );
+        assert_eq!(manifest.url("missing.png"), "/static/misc/missing.png");
+    }
+}
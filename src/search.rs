@@ -0,0 +1,430 @@
+/// Inverted full-text index over posts with BM25 ranking, phrase and prefix queries
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::sync::LazyLock;
+
+use regex::Regex;
+use serde::Serialize;
+use utoipa::ToSchema;
+
+use crate::data::Post;
+
+/// BM25 term frequency saturation
+const K1: f32 = 1.2;
+/// BM25 document length normalisation
+const B: f32 = 0.75;
+/// Words shown around the first match in a snippet
+const SNIPPET_WORDS: usize = 30;
+/// Words shown before the first match in a snippet
+const SNIPPET_LEAD: usize = 8;
+
+static HEADING_RE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?s)<h[1-6][^>]*>(.*?)</h[1-6]>").unwrap());
+static TAG_RE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"<[^>]+>").unwrap());
+/// Heading permalinks added by `data::add_heading_anchors`, which are not content
+static HEADING_HASH_RE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r#"<a class="heading-hash"[^>]*>#</a>"#).unwrap());
+
+/// Parts of a post that are indexed, each weighted by [`Field::boost`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Field {
+    Title,
+    Description,
+    Headings,
+    Tags,
+    Body,
+}
+
+impl Field {
+    fn boost(self) -> f32 {
+        match self {
+            Field::Title => 3.0,
+            Field::Description | Field::Headings | Field::Tags => 2.0,
+            Field::Body => 1.0,
+        }
+    }
+}
+
+/// A post ranked against a query
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct SearchHit {
+    pub post: Post,
+    /// BM25 relevance; only meaningful relative to other hits of the same query
+    pub score: f32,
+    /// Escaped excerpt of the post with the matched words wrapped in `<mark>`
+    pub snippet: String,
+}
+
+struct Document {
+    slug: String,
+    /// Boost-weighted token count
+    length: f32,
+    /// Plain text the snippet is cut from
+    text: String,
+}
+
+/// Occurrences of a term within one document
+type Occurrences = Vec<(Field, u32)>;
+
+/// Inverted index from terms to the documents and positions they occur at
+#[derive(Default)]
+pub struct SearchIndex {
+    documents: Vec<Document>,
+    total_length: f32,
+    /// Sorted so prefix queries are a range scan
+    terms: BTreeMap<String, BTreeMap<usize, Occurrences>>,
+}
+
+/// One part of a parsed query; every clause must match for a document to be returned
+#[derive(Debug, PartialEq, Eq)]
+enum Clause {
+    Term(String),
+    /// `rus*`
+    Prefix(String),
+    /// `"zero cost"`
+    Phrase(Vec<String>),
+}
+
+impl SearchIndex {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Index a post's title, description, tags, series, headings and body text
+    pub fn insert(&mut self, post: &Post) {
+        let doc = self.documents.len();
+        let text = PostText::extract(post);
+
+        let mut length = 0.0;
+        for (field, text) in [
+            (Field::Title, text.title.as_str()),
+            (Field::Description, &text.description),
+            (Field::Headings, &text.headings),
+            (Field::Tags, &text.tags),
+            (Field::Body, &text.body),
+        ] {
+            for (position, token) in tokenize(text).into_iter().enumerate() {
+                length += field.boost();
+                self.terms
+                    .entry(token)
+                    .or_default()
+                    .entry(doc)
+                    .or_default()
+                    .push((field, position as u32));
+            }
+        }
+
+        self.total_length += length;
+        self.documents.push(Document {
+            slug: post.markdown.slug.clone(),
+            length,
+            text: text.body,
+        });
+    }
+
+    /// Rank documents against `query`
+    ///
+    /// # Arguments
+    /// * `query` - Words, `"quoted phrases"` and `prefix*` terms, all of which must match
+    ///
+    /// # Returns
+    /// `(slug, score, snippet)` for every matching document, best first
+    pub fn search(&self, query: &str) -> Vec<(String, f32, String)> {
+        let clauses = parse_query(query);
+        if clauses.is_empty() || self.documents.is_empty() {
+            return Vec::new();
+        }
+
+        let mut scores: Option<HashMap<usize, f32>> = None;
+        let mut matched_terms: HashMap<usize, BTreeSet<String>> = HashMap::new();
+        for clause in &clauses {
+            let matches = self.match_clause(clause);
+            let idf = self.idf(matches.len());
+            let mut clause_scores = HashMap::new();
+            for (doc, (frequency, terms)) in matches {
+                if scores
+                    .as_ref()
+                    .is_some_and(|scores| !scores.contains_key(&doc))
+                {
+                    continue;
+                }
+                clause_scores.insert(doc, idf * self.saturate(frequency, doc));
+                matched_terms.entry(doc).or_default().extend(terms);
+            }
+            scores = Some(match scores {
+                None => clause_scores,
+                Some(previous) => previous
+                    .into_iter()
+                    .filter_map(|(doc, score)| clause_scores.get(&doc).map(|s| (doc, score + s)))
+                    .collect(),
+            });
+        }
+
+        let mut hits: Vec<(String, f32, String)> = scores
+            .unwrap_or_default()
+            .into_iter()
+            .map(|(doc, score)| {
+                let document = &self.documents[doc];
+                let terms = matched_terms.remove(&doc).unwrap_or_default();
+                (
+                    document.slug.clone(),
+                    score,
+                    snippet(&document.text, |token| terms.contains(token)),
+                )
+            })
+            .collect();
+        hits.sort_by(|a, b| b.1.total_cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        hits
+    }
+
+    /// Boost-weighted frequency and matched terms of `clause` in every document it matches
+    fn match_clause(&self, clause: &Clause) -> HashMap<usize, (f32, Vec<String>)> {
+        let mut matches: HashMap<usize, (f32, Vec<String>)> = HashMap::new();
+        match clause {
+            Clause::Term(term) => {
+                for (doc, occurrences) in self.terms.get(term).into_iter().flatten() {
+                    let frequency = occurrences.iter().map(|(field, _)| field.boost()).sum();
+                    matches.insert(*doc, (frequency, vec![term.clone()]));
+                }
+            }
+            Clause::Prefix(prefix) => {
+                let expansions = self
+                    .terms
+                    .range(prefix.clone()..)
+                    .take_while(|(term, _)| term.starts_with(prefix.as_str()));
+                for (term, postings) in expansions {
+                    for (doc, occurrences) in postings {
+                        let entry = matches.entry(*doc).or_default();
+                        entry.0 += occurrences
+                            .iter()
+                            .map(|(field, _)| field.boost())
+                            .sum::<f32>();
+                        entry.1.push(term.clone());
+                    }
+                }
+            }
+            Clause::Phrase(words) => {
+                let Some(first) = words.first().and_then(|word| self.terms.get(word)) else {
+                    return matches;
+                };
+                for (doc, starts) in first {
+                    let frequency: f32 = starts
+                        .iter()
+                        .filter(|(field, position)| {
+                            words.iter().enumerate().skip(1).all(|(offset, word)| {
+                                self.terms
+                                    .get(word)
+                                    .and_then(|postings| postings.get(doc))
+                                    .is_some_and(|occurrences| {
+                                        occurrences.contains(&(*field, position + offset as u32))
+                                    })
+                            })
+                        })
+                        .map(|(field, _)| field.boost())
+                        .sum();
+                    if frequency > 0.0 {
+                        matches.insert(*doc, (frequency, words.clone()));
+                    }
+                }
+            }
+        }
+        matches
+    }
+
+    fn idf(&self, document_frequency: usize) -> f32 {
+        let n = self.documents.len() as f32;
+        let df = document_frequency as f32;
+        (1.0 + (n - df + 0.5) / (df + 0.5)).ln()
+    }
+
+    fn saturate(&self, frequency: f32, doc: usize) -> f32 {
+        let average_length = self.total_length / self.documents.len() as f32;
+        let length = self.documents[doc].length;
+        frequency * (K1 + 1.0) / (frequency + K1 * (1.0 - B + B * length / average_length.max(1.0)))
+    }
+}
+
+/// Plain text of the indexed parts of a post
+pub struct PostText {
+    pub title: String,
+    pub description: String,
+    pub headings: String,
+    /// Tags followed by the series title
+    pub tags: String,
+    /// The whole body with markup removed; snippets are cut from it
+    pub body: String,
+}
+
+impl PostText {
+    pub fn extract(post: &Post) -> Self {
+        let html = HEADING_HASH_RE.replace_all(&post.data, "");
+        let headings: Vec<String> = HEADING_RE
+            .captures_iter(&html)
+            .map(|capture| strip_html(&capture[1]))
+            .collect();
+        let mut tags = post.markdown.tags.join(" ");
+        if let Some(series) = &post.markdown.series {
+            tags.push(' ');
+            tags.push_str(&series.title);
+        }
+        Self {
+            title: post.markdown.title.clone(),
+            description: post.markdown.description.clone(),
+            headings: headings.join(" "),
+            tags,
+            body: strip_html(&html),
+        }
+    }
+}
+
+/// Translate a query into SQLite FTS5 syntax with the same meaning
+///
+/// # Returns
+/// `None` when the query has no searchable words
+pub fn fts5_query(query: &str) -> Option<String> {
+    let clauses = parse_query(query);
+    if clauses.is_empty() {
+        return None;
+    }
+    let parts: Vec<String> = clauses
+        .iter()
+        .map(|clause| match clause {
+            Clause::Term(term) => format!("\"{term}\""),
+            Clause::Prefix(prefix) => format!("\"{prefix}\"*"),
+            Clause::Phrase(words) => format!("\"{}\"", words.join(" ")),
+        })
+        .collect();
+    Some(parts.join(" "))
+}
+
+/// Cut a snippet from `text` marking every word that `query` matches
+pub fn query_snippet(text: &str, query: &str) -> String {
+    let clauses = parse_query(query);
+    snippet(text, |token| {
+        clauses.iter().any(|clause| match clause {
+            Clause::Term(term) => token == term,
+            Clause::Prefix(prefix) => token.starts_with(prefix.as_str()),
+            Clause::Phrase(words) => words.iter().any(|word| word == token),
+        })
+    })
+}
+
+/// Split a query into clauses: `"quoted phrases"`, `prefix*` and plain terms
+fn parse_query(query: &str) -> Vec<Clause> {
+    let mut clauses = Vec::new();
+    for (index, part) in query.split('"').enumerate() {
+        // Odd parts sit between a pair of quotes
+        if index % 2 == 1 {
+            let words = tokenize(part);
+            match words.len() {
+                0 => {}
+                1 => clauses.extend(words.into_iter().map(Clause::Term)),
+                _ => clauses.push(Clause::Phrase(words)),
+            }
+            continue;
+        }
+        for word in part.split_whitespace() {
+            let is_prefix = word.ends_with('*');
+            let tokens = tokenize(word);
+            let last = tokens.len().saturating_sub(1);
+            for (position, token) in tokens.into_iter().enumerate() {
+                if is_prefix && position == last {
+                    clauses.push(Clause::Prefix(token));
+                } else {
+                    clauses.push(Clause::Term(token));
+                }
+            }
+        }
+    }
+    clauses.dedup();
+    clauses
+}
+
+/// Lowercased alphanumeric runs
+fn tokenize(text: &str) -> Vec<String> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|token| !token.is_empty())
+        .map(str::to_lowercase)
+        .collect()
+}
+
+fn strip_html(html: &str) -> String {
+    let text = TAG_RE.replace_all(html, " ");
+    let text = text
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&amp;", "&");
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Cut a window of words around the first matched token, marking every match
+fn snippet(text: &str, matches: impl Fn(&str) -> bool) -> String {
+    let words: Vec<&str> = text.split_whitespace().collect();
+    let is_match = |word: &str| tokenize(word).iter().any(|token| matches(token));
+    let first = words.iter().position(|word| is_match(word)).unwrap_or(0);
+    let start = first.saturating_sub(SNIPPET_LEAD);
+    let end = (start + SNIPPET_WORDS).min(words.len());
+
+    let mut snippet = String::new();
+    if start > 0 {
+        snippet.push('…');
+    }
+    for (index, word) in words[start..end].iter().enumerate() {
+        if index > 0 || start > 0 {
+            snippet.push(' ');
+        }
+        if is_match(word) {
+            snippet.push_str(&format!("<mark>{}</mark>", escape_html(word)));
+        } else {
+            snippet.push_str(&escape_html(word));
+        }
+    }
+    if end < words.len() {
+        snippet.push_str(" …");
+    }
+    snippet
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_query() {
+        assert_eq!(
+            parse_query(r#"Rust "zero cost" abs*"#),
+            vec![
+                Clause::Term("rust".to_string()),
+                Clause::Phrase(vec!["zero".to_string(), "cost".to_string()]),
+                Clause::Prefix("abs".to_string()),
+            ]
+        );
+        assert!(parse_query(r#" "" * "#).is_empty());
+    }
+
+    #[test]
+    fn test_fts5_query() {
+        assert_eq!(
+            fts5_query(r#"Rust "zero cost" abs* OR"#).as_deref(),
+            Some(r#""rust" "zero cost" "abs"* "or""#)
+        );
+        assert_eq!(fts5_query("  *  "), None);
+    }
+
+    #[test]
+    fn test_snippet_marks_and_escapes() {
+        let terms = BTreeSet::from(["vec".to_string()]);
+        assert_eq!(
+            snippet("a <Vec> of things", |token| terms.contains(token)),
+            "a <mark>&lt;Vec&gt;</mark> of things"
+        );
+    }
+}
@@ -55,11 +55,11 @@ impl ImageOptimization {
         let mut html = String::new();
 
         // Use picture element if WebP is available for better browser support
-        if self.webp_src.is_some() {
+        if let Some(webp_src) = &self.webp_src {
             html.push_str("<picture>");
             html.push_str(&format!(
                 r#"<source srcset="{}" type="image/webp">"#,
-                self.webp_src.as_ref().unwrap()
+                webp_src
             ));
         }
 
@@ -79,7 +79,7 @@ impl ImageOptimization {
             html.push_str(&format!(r#" height="{}""#, height));
         }
 
-        html.push_str(">");
+        html.push('>');
 
         if self.webp_src.is_some() {
             html.push_str("</picture>");
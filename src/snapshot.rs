@@ -0,0 +1,188 @@
+/// Immutable content snapshots that are swapped atomically on reload
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+use arc_swap::ArcSwap;
+use chrono::{DateTime, Local, NaiveDate, TimeZone, Timelike, Utc};
+use sha2::{Digest, Sha256};
+
+use crate::{
+    db::{Database, InMemDatabase},
+    diagnostics::Diagnostic,
+};
+
+/// Tag name paired with the number of posts carrying it.
+pub type TagCounts = Vec<(String, usize)>;
+/// Archive navigation: each year with its `(month name, month number, count)` entries.
+pub type DatesByYear = Vec<(i32, Vec<(String, u32, usize)>)>;
+
+/// Sidebar navigation shared by every page
+pub struct NavData {
+    /// Most used first
+    pub tags_with_count: TagCounts,
+    /// Newest year first, months in calendar order
+    pub dates_by_year: DatesByYear,
+    /// Date of the next scheduled post, when the counts go stale
+    valid_until: Option<NaiveDate>,
+}
+
+impl NavData {
+    fn compute(db: &InMemDatabase) -> Self {
+        let mut tags_with_count = db.get_all_tags_with_count();
+        tags_with_count.sort_by_key(|tag| std::cmp::Reverse(tag.1));
+
+        let mut months_by_year: BTreeMap<i32, Vec<(String, u32, usize)>> = BTreeMap::new();
+        for ((year, month), count) in db.get_all_dates_with_count() {
+            months_by_year.entry(year).or_default().push((
+                month_name(month).to_string(),
+                month,
+                count,
+            ));
+        }
+        let dates_by_year = months_by_year
+            .into_iter()
+            .rev()
+            .map(|(year, mut months)| {
+                months.sort_by_key(|month| month.1);
+                (year, months)
+            })
+            .collect();
+
+        Self {
+            tags_with_count,
+            dates_by_year,
+            valid_until: db.next_scheduled_date(),
+        }
+    }
+}
+
+/// The content served at one point in time: a database and everything derived from it
+///
+/// Readers hold an `Arc<Snapshot>` for as long as they need it, so a reload never waits
+/// for them and they never see a half-built database.
+pub struct Snapshot {
+    pub db: InMemDatabase,
+    /// Commit of the content repository the posts came from, if loaded from git
+    pub commit: Option<String>,
+    /// Hex hash over the commit and every post, drafts included
+    pub version: String,
+    /// When the snapshot was built, to the second, as HTTP dates have no finer precision
+    pub loaded_at: DateTime<Utc>,
+    /// Post files skipped while loading this content
+    pub diagnostics: Vec<Diagnostic>,
+    /// Post files that fail to parse but keep serving their previous version
+    pub stale: Vec<Diagnostic>,
+    nav: ArcSwap<NavData>,
+}
+
+impl Snapshot {
+    pub fn new(db: InMemDatabase) -> Self {
+        let nav = ArcSwap::from_pointee(NavData::compute(&db));
+        let loaded_at = Utc::now().with_nanosecond(0).unwrap_or_else(Utc::now);
+        Self {
+            version: content_version(&db, None),
+            db,
+            commit: None,
+            loaded_at,
+            diagnostics: Vec::new(),
+            stale: Vec::new(),
+            nav,
+        }
+    }
+
+    pub fn with_commit(mut self, commit: Option<String>) -> Self {
+        self.version = content_version(&self.db, commit.as_deref());
+        self.commit = commit;
+        self
+    }
+
+    pub fn with_diagnostics(mut self, diagnostics: Vec<Diagnostic>) -> Self {
+        self.diagnostics = diagnostics;
+        self
+    }
+
+    pub fn with_stale(mut self, stale: Vec<Diagnostic>) -> Self {
+        self.stale = stale;
+        self
+    }
+
+    /// When the published content last changed: when it was loaded, or when a scheduled post
+    /// went live since
+    pub fn last_modified(&self) -> DateTime<Utc> {
+        let went_live = self
+            .db
+            .get_last_n_posts(1)
+            .first()
+            .and_then(|post| post.markdown.date.and_hms_opt(0, 0, 0))
+            .and_then(|midnight| Local.from_local_datetime(&midnight).earliest())
+            .map(|midnight| midnight.with_timezone(&Utc));
+        went_live.map_or(self.loaded_at, |went_live| went_live.max(self.loaded_at))
+    }
+
+    /// Navigation data, recomputed only once a scheduled post goes live
+    pub fn nav(&self) -> Arc<NavData> {
+        let nav = self.nav.load_full();
+        if nav.valid_until.is_some_and(|date| self.db.today() >= date) {
+            let fresh = Arc::new(NavData::compute(&self.db));
+            self.nav.store(fresh.clone());
+            return fresh;
+        }
+        nav
+    }
+}
+
+/// Shared slot holding the current [`Snapshot`]
+#[derive(Clone)]
+pub struct SnapshotCell(Arc<ArcSwap<Snapshot>>);
+
+impl SnapshotCell {
+    pub fn new(snapshot: Snapshot) -> Self {
+        Self(Arc::new(ArcSwap::from_pointee(snapshot)))
+    }
+
+    /// The snapshot currently served; never blocks
+    pub fn load(&self) -> Arc<Snapshot> {
+        self.0.load_full()
+    }
+
+    /// Replace the served snapshot; requests already holding the old one finish with it
+    pub fn publish(&self, snapshot: Snapshot) -> Arc<Snapshot> {
+        let snapshot = Arc::new(snapshot);
+        self.0.store(snapshot.clone());
+        snapshot
+    }
+}
+
+fn content_version(db: &InMemDatabase, commit: Option<&str>) -> String {
+    let mut posts: Vec<(&str, String)> = db
+        .posts_including_unpublished()
+        .map(|post| (post.markdown.slug.as_str(), post.content_hash()))
+        .collect();
+    posts.sort();
+
+    let mut hasher = Sha256::new();
+    hasher.update(commit.unwrap_or_default().as_bytes());
+    for (slug, hash) in posts {
+        hasher.update(slug.as_bytes());
+        hasher.update(hash.as_bytes());
+    }
+    hex::encode(hasher.finalize())
+}
+
+fn month_name(month: u32) -> &'static str {
+    match month {
+        1 => "January",
+        2 => "February",
+        3 => "March",
+        4 => "April",
+        5 => "May",
+        6 => "June",
+        7 => "July",
+        8 => "August",
+        9 => "September",
+        10 => "October",
+        11 => "November",
+        12 => "December",
+        _ => "Unknown",
+    }
+}
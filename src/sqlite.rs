@@ -0,0 +1,419 @@
+/// `Database` implementation on an embedded SQLite file, with FTS5 keyword search
+use std::path::Path;
+
+use chrono::{Datelike, NaiveDate};
+use rusqlite::{params, params_from_iter, Connection, OptionalExtension, Row, ToSql};
+
+use crate::{
+    data::{Markdown, Post, Series},
+    db::{local_today, Clock, Database, InMemDatabase},
+    error::ApplicationError,
+    search::{fts5_query, query_snippet, PostText, SearchHit},
+};
+
+/// Schema changes in order; `PRAGMA user_version` records how many have been applied
+const MIGRATIONS: &[&str] = &[
+    // 1: posts with their tags, series and dates, plus the full-text index
+    "CREATE TABLE posts (
+        slug TEXT PRIMARY KEY,
+        title TEXT NOT NULL,
+        description TEXT NOT NULL,
+        html TEXT NOT NULL,
+        draft INTEGER NOT NULL
+    );
+    CREATE TABLE dates (
+        slug TEXT PRIMARY KEY REFERENCES posts(slug) ON DELETE CASCADE,
+        date TEXT NOT NULL,
+        year INTEGER NOT NULL,
+        month INTEGER NOT NULL
+    );
+    CREATE INDEX dates_by_month ON dates(year, month, date);
+    CREATE INDEX dates_by_date ON dates(date);
+    CREATE TABLE tags (
+        slug TEXT NOT NULL REFERENCES posts(slug) ON DELETE CASCADE,
+        tag TEXT NOT NULL,
+        position INTEGER NOT NULL,
+        PRIMARY KEY (slug, tag)
+    );
+    CREATE INDEX tags_by_tag ON tags(tag);
+    CREATE TABLE series (
+        slug TEXT PRIMARY KEY REFERENCES posts(slug) ON DELETE CASCADE,
+        title TEXT NOT NULL,
+        ep INTEGER NOT NULL
+    );
+    CREATE INDEX series_by_title ON series(title, ep);
+    CREATE VIRTUAL TABLE posts_fts USING fts5(
+        slug UNINDEXED, title, description, headings, tags, body
+    );",
+    // 2: metadata about the stored content, such as the commit it came from
+    "CREATE TABLE meta (
+        key TEXT PRIMARY KEY,
+        value TEXT NOT NULL
+    );",
+];
+
+/// Column list shared by every post query; `dates` is aliased `d` and `series` `s`
+const POST_COLUMNS: &str = "p.slug, p.title, p.description, p.html, p.draft, d.date, \
+    s.title AS series_title, s.ep";
+const POST_JOINS: &str =
+    "FROM posts p JOIN dates d ON d.slug = p.slug LEFT JOIN series s ON s.slug = p.slug";
+/// Matches `InMemDatabase`: newest first, ties by slug
+const NEWEST_FIRST: &str = "d.date DESC, p.slug ASC";
+
+/// Posts stored in SQLite, queried directly on every call
+///
+/// Like [`InMemDatabase`], scheduled posts stay hidden until their date and drafts are
+/// only reachable through the unpublished lookups.
+pub struct SqliteDatabase {
+    connection: Connection,
+    clock: Clock,
+}
+
+impl SqliteDatabase {
+    /// Open or create the database file at `path` and bring its schema up to date
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, ApplicationError> {
+        if let Some(parent) = path.as_ref().parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        Self::from_connection(Connection::open(path)?)
+    }
+
+    /// A database that lives only as long as the value, mostly for tests
+    pub fn open_in_memory() -> Result<Self, ApplicationError> {
+        Self::from_connection(Connection::open_in_memory()?)
+    }
+
+    fn from_connection(mut connection: Connection) -> Result<Self, ApplicationError> {
+        connection.pragma_update(None, "foreign_keys", true)?;
+        migrate(&mut connection)?;
+        Ok(Self {
+            connection,
+            clock: local_today,
+        })
+    }
+
+    /// Decide what is scheduled against `clock` instead of the local date
+    pub fn with_clock(mut self, clock: Clock) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Replace everything stored with `db`'s posts in one transaction
+    ///
+    /// # Arguments
+    /// * `db` - The content to keep, including drafts and scheduled posts
+    /// * `commit` - Commit the content came from, returned by [`SqliteDatabase::commit`]
+    pub fn replace_content(
+        &mut self,
+        db: &InMemDatabase,
+        commit: Option<&str>,
+    ) -> Result<(), ApplicationError> {
+        let transaction = self.connection.transaction()?;
+        transaction.execute_batch("DELETE FROM posts; DELETE FROM posts_fts; DELETE FROM meta;")?;
+        for post in db.posts_including_unpublished() {
+            insert_post(&transaction, post)?;
+        }
+        if let Some(commit) = commit {
+            transaction.execute(
+                "INSERT INTO meta (key, value) VALUES ('commit', ?1)",
+                params![commit],
+            )?;
+        }
+        transaction.commit()?;
+        Ok(())
+    }
+
+    /// Load everything stored into an [`InMemDatabase`], for serving before a sync
+    pub fn load_in_memory(&self) -> Result<InMemDatabase, ApplicationError> {
+        let mut db = InMemDatabase::new().with_clock(self.clock);
+        for post in self.query_posts("1 = 1", &[], NEWEST_FIRST)? {
+            db.insert_parsed_to_database(post)?;
+        }
+        Ok(db)
+    }
+
+    /// Commit recorded by the last [`SqliteDatabase::replace_content`]
+    pub fn commit(&self) -> Result<Option<String>, ApplicationError> {
+        Ok(self
+            .connection
+            .query_row("SELECT value FROM meta WHERE key = 'commit'", [], |row| {
+                row.get(0)
+            })
+            .optional()?)
+    }
+
+    /// Number of stored posts, drafts included
+    pub fn post_count(&self) -> Result<usize, ApplicationError> {
+        let count: i64 = self
+            .connection
+            .query_row("SELECT COUNT(*) FROM posts", [], |row| row.get(0))?;
+        Ok(count as usize)
+    }
+
+    fn today(&self) -> String {
+        (self.clock)().to_string()
+    }
+
+    /// Posts matching `condition`, which may refer to `p`, `d` and `s` and the `?` params
+    fn query_posts(
+        &self,
+        condition: &str,
+        params: &[&dyn ToSql],
+        order: &str,
+    ) -> rusqlite::Result<Vec<Post>> {
+        let sql = format!("SELECT {POST_COLUMNS} {POST_JOINS} WHERE {condition} ORDER BY {order}");
+        let mut statement = self.connection.prepare_cached(&sql)?;
+        let rows = statement.query_map(params_from_iter(params.iter()), post_from_row)?;
+        let mut posts = rows.collect::<rusqlite::Result<Vec<Post>>>()?;
+        let mut tags = self
+            .connection
+            .prepare_cached("SELECT tag FROM tags WHERE slug = ?1 ORDER BY position")?;
+        for post in &mut posts {
+            post.markdown.tags = tags
+                .query_map(params![post.markdown.slug], |row| row.get(0))?
+                .collect::<rusqlite::Result<Vec<String>>>()?;
+        }
+        Ok(posts)
+    }
+
+    /// Published posts matching `condition`
+    fn published(&self, condition: &str, params: &[&dyn ToSql], order: &str) -> Vec<Post> {
+        let today = self.today();
+        let condition = format!("p.draft = 0 AND d.date <= ? AND ({condition})");
+        let mut all_params: Vec<&dyn ToSql> = vec![&today];
+        all_params.extend_from_slice(params);
+        logged(self.query_posts(&condition, &all_params, order))
+    }
+}
+
+impl Database for SqliteDatabase {
+    fn get_by_slug(&self, slug: String) -> Option<Post> {
+        self.published("p.slug = ?", &[&slug], NEWEST_FIRST)
+            .into_iter()
+            .next()
+    }
+
+    fn get_by_tag(&self, tag: String) -> Vec<Post> {
+        self.published(
+            "p.slug IN (SELECT slug FROM tags WHERE tag = ?)",
+            &[&tag],
+            NEWEST_FIRST,
+        )
+    }
+
+    fn get_by_series(&self, series: String) -> Vec<Post> {
+        self.published("s.title = ?", &[&series], "s.ep, d.date, p.slug")
+    }
+
+    fn get_by_keyword(&self, keyword: String) -> Vec<Post> {
+        self.search(&keyword)
+            .into_iter()
+            .map(|hit| hit.post)
+            .collect()
+    }
+
+    fn search(&self, query: &str) -> Vec<SearchHit> {
+        let Some(fts_query) = fts5_query(query) else {
+            return Vec::new();
+        };
+        // Column weights mirror the field boosts of the in-memory index
+        let ranked: rusqlite::Result<Vec<(String, f64, String)>> = (|| {
+            let mut statement = self.connection.prepare_cached(
+                "SELECT slug, bm25(posts_fts, 0.0, 3.0, 2.0, 2.0, 2.0, 1.0) AS rank, body
+                 FROM posts_fts WHERE posts_fts MATCH ?1 ORDER BY rank, slug",
+            )?;
+            let rows = statement.query_map(params![fts_query], |row| {
+                Ok((row.get(0)?, row.get(1)?, row.get(2)?))
+            })?;
+            rows.collect()
+        })();
+
+        logged(ranked)
+            .into_iter()
+            .filter_map(|(slug, rank, body)| {
+                let post = self.get_by_slug(slug)?;
+                Some(SearchHit {
+                    post,
+                    // bm25() is lower for better matches
+                    score: -rank as f32,
+                    snippet: query_snippet(&body, query),
+                })
+            })
+            .collect()
+    }
+
+    fn get_by_year_month(&self, year: i32, month: Option<u32>) -> Vec<Post> {
+        match month {
+            Some(month) => {
+                self.published("d.year = ? AND d.month = ?", &[&year, &month], NEWEST_FIRST)
+            }
+            None => self.published("d.year = ?", &[&year], NEWEST_FIRST),
+        }
+    }
+
+    fn get_last_n_posts(&self, n: usize) -> Vec<Post> {
+        let order = format!("{NEWEST_FIRST} LIMIT {n}");
+        self.published("1 = 1", &[], &order)
+    }
+
+    fn get_all_posts(&self) -> Vec<Post> {
+        self.published("1 = 1", &[], NEWEST_FIRST)
+    }
+
+    fn get_all_tags_with_count(&self) -> Vec<(String, usize)> {
+        let today = self.today();
+        let counts: rusqlite::Result<Vec<(String, usize)>> = (|| {
+            let mut statement = self.connection.prepare_cached(
+                "SELECT t.tag, COUNT(*) FROM tags t
+                 JOIN posts p ON p.slug = t.slug JOIN dates d ON d.slug = t.slug
+                 WHERE p.draft = 0 AND d.date <= ?1
+                 GROUP BY t.tag ORDER BY t.tag",
+            )?;
+            let rows = statement.query_map(params![today], |row| {
+                Ok((row.get(0)?, row.get::<_, i64>(1)? as usize))
+            })?;
+            rows.collect()
+        })();
+        logged(counts)
+    }
+
+    fn get_all_dates_with_count(&self) -> Vec<((i32, u32), usize)> {
+        let today = self.today();
+        let counts: rusqlite::Result<Vec<((i32, u32), usize)>> = (|| {
+            let mut statement = self.connection.prepare_cached(
+                "SELECT d.year, d.month, COUNT(*) FROM dates d JOIN posts p ON p.slug = d.slug
+                 WHERE p.draft = 0 AND d.date <= ?1
+                 GROUP BY d.year, d.month ORDER BY d.year DESC, d.month DESC",
+            )?;
+            let rows = statement.query_map(params![today], |row| {
+                Ok(((row.get(0)?, row.get(1)?), row.get::<_, i64>(2)? as usize))
+            })?;
+            rows.collect()
+        })();
+        logged(counts)
+    }
+
+    fn get_unpublished_by_slug(&self, slug: String) -> Option<Post> {
+        self.get_unpublished_posts()
+            .into_iter()
+            .find(|post| post.markdown.slug == slug)
+    }
+
+    fn get_unpublished_posts(&self) -> Vec<Post> {
+        let today = self.today();
+        logged(self.query_posts("p.draft = 1 OR d.date > ?", &[&today], "p.slug"))
+    }
+
+    fn insert_parsed_to_database(&mut self, post: Post) -> Result<(), ApplicationError> {
+        let transaction = self.connection.transaction()?;
+        insert_post(&transaction, &post)?;
+        transaction.commit()?;
+        Ok(())
+    }
+}
+
+/// Apply every migration newer than the database's `user_version`
+fn migrate(connection: &mut Connection) -> rusqlite::Result<()> {
+    let version: usize =
+        connection.query_row("PRAGMA user_version", [], |row| row.get::<_, i64>(0))? as usize;
+    for (index, migration) in MIGRATIONS.iter().enumerate().skip(version) {
+        let transaction = connection.transaction()?;
+        transaction.execute_batch(migration)?;
+        transaction.pragma_update(None, "user_version", (index + 1) as i64)?;
+        transaction.commit()?;
+    }
+    Ok(())
+}
+
+/// Store `post` unless its slug is taken, matching the first-wins rule of `InMemDatabase`
+fn insert_post(connection: &Connection, post: &Post) -> rusqlite::Result<()> {
+    let markdown = &post.markdown;
+    let inserted = connection.execute(
+        "INSERT OR IGNORE INTO posts (slug, title, description, html, draft)
+         VALUES (?1, ?2, ?3, ?4, ?5)",
+        params![
+            markdown.slug,
+            markdown.title,
+            markdown.description,
+            post.data,
+            markdown.draft
+        ],
+    )?;
+    if inserted == 0 {
+        return Ok(());
+    }
+
+    connection.execute(
+        "INSERT INTO dates (slug, date, year, month) VALUES (?1, ?2, ?3, ?4)",
+        params![
+            markdown.slug,
+            markdown.date.to_string(),
+            markdown.date.year(),
+            markdown.date.month()
+        ],
+    )?;
+    for (position, tag) in markdown.tags.iter().enumerate() {
+        connection.execute(
+            "INSERT OR IGNORE INTO tags (slug, tag, position) VALUES (?1, ?2, ?3)",
+            params![markdown.slug, tag, position as i64],
+        )?;
+    }
+    if let Some(series) = &markdown.series {
+        connection.execute(
+            "INSERT INTO series (slug, title, ep) VALUES (?1, ?2, ?3)",
+            params![markdown.slug, series.title, series.ep],
+        )?;
+    }
+    // Drafts are never searchable, so they stay out of the full-text index
+    if !markdown.draft {
+        let text = PostText::extract(post);
+        connection.execute(
+            "INSERT INTO posts_fts (slug, title, description, headings, tags, body)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            params![
+                markdown.slug,
+                text.title,
+                text.description,
+                text.headings,
+                text.tags,
+                text.body
+            ],
+        )?;
+    }
+    Ok(())
+}
+
+/// Build a post from the columns of [`POST_COLUMNS`]; tags are filled in separately
+fn post_from_row(row: &Row) -> rusqlite::Result<Post> {
+    let date: String = row.get("date")?;
+    let date = date.parse::<NaiveDate>().map_err(|err| {
+        rusqlite::Error::FromSqlConversionFailure(5, rusqlite::types::Type::Text, Box::new(err))
+    })?;
+    let series = match row.get::<_, Option<String>>("series_title")? {
+        Some(title) => Some(Series {
+            title,
+            ep: row.get("ep")?,
+        }),
+        None => None,
+    };
+    Ok(Post {
+        data: row.get("html")?,
+        markdown: Markdown {
+            title: row.get("title")?,
+            description: row.get("description")?,
+            slug: row.get("slug")?,
+            tags: Vec::new(),
+            date,
+            series,
+            draft: row.get("draft")?,
+        },
+    })
+}
+
+/// Read paths of the `Database` trait cannot fail, so query errors are logged and read as empty
+fn logged<T: Default>(result: rusqlite::Result<T>) -> T {
+    result.unwrap_or_else(|err| {
+        eprintln!("SQLite query failed: {err}");
+        T::default()
+    })
+}
@@ -0,0 +1,654 @@
+/// Daily and monthly view buckets per post, with retention and range queries
+use std::collections::{BTreeMap, HashMap};
+use std::hash::Hash;
+
+use chrono::{Datelike, Duration, NaiveDate};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+/// Filtered views and unfiltered requests over some period
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq, ToSchema)]
+pub struct ViewCount {
+    /// Requests that passed the view filter
+    pub views: u64,
+    /// Every request, bots and repeat visits included
+    pub raw: u64,
+}
+
+/// A count that buckets can be summed into
+trait Tally: Copy + Default {
+    fn add(&mut self, other: Self);
+}
+
+impl Tally for ViewCount {
+    fn add(&mut self, other: ViewCount) {
+        self.views += other.views;
+        self.raw += other.raw;
+    }
+}
+
+impl Tally for u64 {
+    fn add(&mut self, other: u64) {
+        *self += other;
+    }
+}
+
+/// How a post's views changed between two equally long periods
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, ToSchema)]
+pub struct Mover {
+    pub slug: String,
+    /// Views in the most recent period
+    pub current: u64,
+    /// Views in the period right before it
+    pub previous: u64,
+    pub change: i64,
+}
+
+/// What a view's source is made of
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[serde(rename_all = "snake_case")]
+pub enum SourceKind {
+    /// Host of the page that linked to the post
+    Referrer,
+    UtmSource,
+    UtmMedium,
+    UtmCampaign,
+}
+
+impl SourceKind {
+    pub const ALL: [SourceKind; 4] = [
+        SourceKind::Referrer,
+        SourceKind::UtmSource,
+        SourceKind::UtmMedium,
+        SourceKind::UtmCampaign,
+    ];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            SourceKind::Referrer => "referrer",
+            SourceKind::UtmSource => "utm_source",
+            SourceKind::UtmMedium => "utm_medium",
+            SourceKind::UtmCampaign => "utm_campaign",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        SourceKind::ALL
+            .into_iter()
+            .find(|kind| kind.as_str() == value)
+    }
+}
+
+/// Where a counted view came from; every part is optional
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ViewSource {
+    /// Only the host, never the full referring URL
+    pub referrer: Option<String>,
+    pub utm_source: Option<String>,
+    pub utm_medium: Option<String>,
+    pub utm_campaign: Option<String>,
+}
+
+impl ViewSource {
+    /// The parts that are set, in [`SourceKind::ALL`] order
+    pub fn parts(&self) -> Vec<(SourceKind, &str)> {
+        [
+            &self.referrer,
+            &self.utm_source,
+            &self.utm_medium,
+            &self.utm_campaign,
+        ]
+        .into_iter()
+        .zip(SourceKind::ALL)
+        .filter_map(|(value, kind)| Some((kind, value.as_deref()?)))
+        .collect()
+    }
+
+    pub fn set(&mut self, kind: SourceKind, value: String) {
+        *self.part_mut(kind) = Some(value);
+    }
+
+    pub fn clear(&mut self, kind: SourceKind) {
+        *self.part_mut(kind) = None;
+    }
+
+    fn part_mut(&mut self, kind: SourceKind) -> &mut Option<String> {
+        match kind {
+            SourceKind::Referrer => &mut self.referrer,
+            SourceKind::UtmSource => &mut self.utm_source,
+            SourceKind::UtmMedium => &mut self.utm_medium,
+            SourceKind::UtmCampaign => &mut self.utm_campaign,
+        }
+    }
+}
+
+/// A source value with the number of views it brought
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, ToSchema)]
+pub struct SourceCount {
+    pub value: String,
+    pub views: u64,
+}
+
+/// Views per source over a period, each list most views first
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq, ToSchema)]
+pub struct SourceBreakdown {
+    pub referrers: Vec<SourceCount>,
+    pub utm_sources: Vec<SourceCount>,
+    pub utm_mediums: Vec<SourceCount>,
+    pub utm_campaigns: Vec<SourceCount>,
+}
+
+impl SourceBreakdown {
+    fn from_counts(counts: HashMap<(SourceKind, &str), u64>) -> Self {
+        let mut breakdown = SourceBreakdown::default();
+        for ((kind, value), views) in counts {
+            breakdown.list_mut(kind).push(SourceCount {
+                value: value.to_string(),
+                views,
+            });
+        }
+        for kind in SourceKind::ALL {
+            breakdown
+                .list_mut(kind)
+                .sort_by(|a, b| b.views.cmp(&a.views).then_with(|| a.value.cmp(&b.value)));
+        }
+        breakdown
+    }
+
+    pub fn list(&self, kind: SourceKind) -> &[SourceCount] {
+        match kind {
+            SourceKind::Referrer => &self.referrers,
+            SourceKind::UtmSource => &self.utm_sources,
+            SourceKind::UtmMedium => &self.utm_mediums,
+            SourceKind::UtmCampaign => &self.utm_campaigns,
+        }
+    }
+
+    fn list_mut(&mut self, kind: SourceKind) -> &mut Vec<SourceCount> {
+        match kind {
+            SourceKind::Referrer => &mut self.referrers,
+            SourceKind::UtmSource => &mut self.utm_sources,
+            SourceKind::UtmMedium => &mut self.utm_mediums,
+            SourceKind::UtmCampaign => &mut self.utm_campaigns,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        SourceKind::ALL
+            .into_iter()
+            .all(|kind| self.list(kind).is_empty())
+    }
+}
+
+/// A post and one part of the source of its views
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+struct SourceKey {
+    slug: String,
+    kind: SourceKind,
+    value: String,
+}
+
+/// Marks snapshot lines holding source counts rather than view counts
+const SOURCE_LINE: char = '@';
+/// Period of source lines that are no longer bucketed
+const NO_PERIOD: &str = "-";
+
+/// How long buckets are kept at each resolution
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Retention {
+    /// Days kept as daily buckets before they are merged into their month
+    pub days: u32,
+    /// Months kept as monthly buckets before they only count towards the totals
+    pub months: u32,
+}
+
+impl Default for Retention {
+    fn default() -> Self {
+        Self {
+            days: 90,
+            months: 24,
+        }
+    }
+}
+
+/// Counts keyed by `K`, bucketed per day, then per month, then not at all
+#[derive(Debug, Clone)]
+struct Tiers<K, V> {
+    days: BTreeMap<NaiveDate, HashMap<K, V>>,
+    /// Keyed by the first day of the month
+    months: BTreeMap<NaiveDate, HashMap<K, V>>,
+    earlier: HashMap<K, V>,
+}
+
+impl<K, V> Default for Tiers<K, V> {
+    fn default() -> Self {
+        Self {
+            days: BTreeMap::new(),
+            months: BTreeMap::new(),
+            earlier: HashMap::new(),
+        }
+    }
+}
+
+impl<K: Clone + Eq + Hash + Ord, V: Tally> Tiers<K, V> {
+    fn add(&mut self, period: Option<Period>, key: K, value: V) {
+        let buckets = match period {
+            None => &mut self.earlier,
+            Some(Period::Day(day)) => self.days.entry(day).or_default(),
+            Some(Period::Month(month)) => self.months.entry(month).or_default(),
+        };
+        buckets.entry(key).or_default().add(value);
+    }
+
+    fn get(&self, day: NaiveDate, key: &K) -> V {
+        self.days
+            .get(&day)
+            .and_then(|buckets| buckets.get(key))
+            .copied()
+            .unwrap_or_default()
+    }
+
+    /// Sum of every key's count on `day`
+    fn day_total(&self, day: NaiveDate) -> V {
+        let mut total = V::default();
+        for value in self.days.get(&day).into_iter().flat_map(HashMap::values) {
+            total.add(*value);
+        }
+        total
+    }
+
+    fn totals(&self) -> HashMap<K, V> {
+        let mut totals = self.earlier.clone();
+        for buckets in self.months.values().chain(self.days.values()) {
+            merge(&mut totals, buckets);
+        }
+        totals
+    }
+
+    fn range(&self, from: NaiveDate, to: NaiveDate) -> HashMap<K, V> {
+        let mut counts = HashMap::new();
+        if from > to {
+            return counts;
+        }
+        for (month, buckets) in self.months.range(from..=to) {
+            if last_day_of_month(*month) <= to {
+                merge(&mut counts, buckets);
+            }
+        }
+        for buckets in self.days.range(from..=to).map(|(_, buckets)| buckets) {
+            merge(&mut counts, buckets);
+        }
+        counts
+    }
+
+    fn compact(&mut self, today: NaiveDate, retention: Retention) -> bool {
+        let mut changed = false;
+
+        let keep_days_from = today - Duration::days(retention.days as i64);
+        let kept_days = self.days.split_off(&keep_days_from);
+        for (day, buckets) in std::mem::replace(&mut self.days, kept_days) {
+            merge(
+                self.months.entry(first_day_of_month(day)).or_default(),
+                &buckets,
+            );
+            changed = true;
+        }
+
+        let keep_months_from = first_day_of_month(today)
+            .checked_sub_months(chrono::Months::new(retention.months))
+            .unwrap_or(NaiveDate::MIN);
+        let kept_months = self.months.split_off(&keep_months_from);
+        for (_, buckets) in std::mem::replace(&mut self.months, kept_months) {
+            merge(&mut self.earlier, &buckets);
+            changed = true;
+        }
+
+        changed
+    }
+
+    /// Every bucket through `line`, oldest first and sorted by key within a bucket
+    fn lines(&self, line: impl Fn(Option<Period>, &K, &V) -> String) -> Vec<String> {
+        let mut lines = sorted_lines(&self.earlier, |key, value| line(None, key, value));
+        for (month, buckets) in &self.months {
+            let period = Some(Period::Month(*month));
+            lines.extend(sorted_lines(buckets, |key, value| line(period, key, value)));
+        }
+        for (day, buckets) in &self.days {
+            let period = Some(Period::Day(*day));
+            lines.extend(sorted_lines(buckets, |key, value| line(period, key, value)));
+        }
+        lines
+    }
+}
+
+/// The span a bucket covers
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Period {
+    Day(NaiveDate),
+    /// Keyed by the first day of the month
+    Month(NaiveDate),
+}
+
+impl Period {
+    fn parse(value: &str) -> Option<Self> {
+        if let Ok(day) = NaiveDate::parse_from_str(value, "%Y-%m-%d") {
+            return Some(Period::Day(day));
+        }
+        NaiveDate::parse_from_str(&format!("{value}-01"), "%Y-%m-%d")
+            .ok()
+            .map(Period::Month)
+    }
+
+    fn format(period: Option<Period>) -> String {
+        match period {
+            None => NO_PERIOD.to_string(),
+            Some(Period::Day(day)) => day.to_string(),
+            Some(Period::Month(month)) => month.format("%Y-%m").to_string(),
+        }
+    }
+}
+
+/// View counts bucketed by day and slug, with the sources of the counted views
+///
+/// Recent days are kept one bucket per day; past the retention they are downsampled
+/// into one bucket per month, and past that into a single bucket, which also holds the
+/// lifetime totals recorded before history was kept.
+#[derive(Debug, Clone, Default)]
+pub struct ViewHistory {
+    views: Tiers<String, ViewCount>,
+    sources: Tiers<SourceKey, u64>,
+    retention: Retention,
+}
+
+impl ViewHistory {
+    pub fn with_retention(mut self, retention: Retention) -> Self {
+        self.retention = retention;
+        self
+    }
+
+    /// Count one request for `slug` on `day`
+    pub fn add(&mut self, day: NaiveDate, slug: &str, counted: bool) {
+        let count = ViewCount {
+            views: counted as u64,
+            raw: 1,
+        };
+        self.views
+            .add(Some(Period::Day(day)), slug.to_string(), count);
+    }
+
+    /// Attribute one counted view of `slug` on `day` to each part of `source`
+    pub fn add_source(&mut self, day: NaiveDate, slug: &str, source: &ViewSource) {
+        for (kind, value) in source.parts() {
+            let key = SourceKey {
+                slug: slug.to_string(),
+                kind,
+                value: value.to_string(),
+            };
+            self.sources.add(Some(Period::Day(day)), key, 1);
+        }
+    }
+
+    /// Lifetime counts per slug across every bucket
+    pub fn totals(&self) -> HashMap<String, ViewCount> {
+        self.views.totals()
+    }
+
+    /// Counts per slug from `from` to `to`, both included
+    ///
+    /// Days that were already downsampled count only when their whole month lies in the
+    /// range, so ranges reaching past the daily retention are approximate.
+    pub fn range(&self, from: NaiveDate, to: NaiveDate) -> HashMap<String, ViewCount> {
+        self.views.range(from, to)
+    }
+
+    /// Counts for `slug` on every day from `from` to `to`, zero for days without views
+    pub fn daily(&self, slug: &str, from: NaiveDate, to: NaiveDate) -> Vec<(NaiveDate, ViewCount)> {
+        let slug = slug.to_string();
+        from.iter_days()
+            .take_while(|day| *day <= to)
+            .map(|day| (day, self.views.get(day, &slug)))
+            .collect()
+    }
+
+    /// Counts of all posts together on every day from `from` to `to`
+    pub fn daily_totals(&self, from: NaiveDate, to: NaiveDate) -> Vec<(NaiveDate, ViewCount)> {
+        from.iter_days()
+            .take_while(|day| *day <= to)
+            .map(|day| (day, self.views.day_total(day)))
+            .collect()
+    }
+
+    /// Where the views from `from` to `to` came from, for every post together
+    pub fn sources(&self, from: NaiveDate, to: NaiveDate) -> SourceBreakdown {
+        let range = self.sources.range(from, to);
+        let mut counts: HashMap<(SourceKind, &str), u64> = HashMap::new();
+        for (key, views) in &range {
+            *counts.entry((key.kind, key.value.as_str())).or_default() += views;
+        }
+        SourceBreakdown::from_counts(counts)
+    }
+
+    /// Where the views from `from` to `to` came from, per post
+    pub fn sources_by_post(
+        &self,
+        from: NaiveDate,
+        to: NaiveDate,
+    ) -> BTreeMap<String, SourceBreakdown> {
+        let range = self.sources.range(from, to);
+        let mut counts: BTreeMap<&str, HashMap<(SourceKind, &str), u64>> = BTreeMap::new();
+        for (key, views) in &range {
+            *counts
+                .entry(key.slug.as_str())
+                .or_default()
+                .entry((key.kind, key.value.as_str()))
+                .or_default() += views;
+        }
+        counts
+            .into_iter()
+            .map(|(slug, counts)| (slug.to_string(), SourceBreakdown::from_counts(counts)))
+            .collect()
+    }
+
+    /// Posts whose views grew the most in the `days` up to `today` against the `days` before
+    pub fn movers(&self, today: NaiveDate, days: u32, limit: usize) -> Vec<Mover> {
+        let span = Duration::days(days.max(1) as i64);
+        let current_start = today - span + Duration::days(1);
+        let current = self.range(current_start, today);
+        let previous = self.range(current_start - span, current_start - Duration::days(1));
+
+        let mut slugs: Vec<&String> = current.keys().chain(previous.keys()).collect();
+        slugs.sort();
+        slugs.dedup();
+        let mut movers: Vec<Mover> = slugs
+            .into_iter()
+            .map(|slug| {
+                let current = current.get(slug).map_or(0, |count| count.views);
+                let previous = previous.get(slug).map_or(0, |count| count.views);
+                Mover {
+                    slug: slug.clone(),
+                    current,
+                    previous,
+                    change: current as i64 - previous as i64,
+                }
+            })
+            .filter(|mover| mover.change != 0)
+            .collect();
+        movers.sort_by(|a, b| {
+            b.change
+                .cmp(&a.change)
+                .then_with(|| b.current.cmp(&a.current))
+                .then_with(|| a.slug.cmp(&b.slug))
+        });
+        movers.truncate(limit);
+        movers
+    }
+
+    /// Downsample buckets that fell out of their retention as of `today`
+    ///
+    /// # Returns
+    /// Whether any bucket was merged
+    pub fn compact(&mut self, today: NaiveDate) -> bool {
+        let views = self.views.compact(today, self.retention);
+        let sources = self.sources.compact(today, self.retention);
+        views || sources
+    }
+
+    /// Read a line written by [`ViewHistory::lines`]; returns `false` for other lines
+    ///
+    /// Lines of `slug`, views and requests are totals from before history was kept, and
+    /// files older still have no requests column.
+    pub fn parse_line(&mut self, line: &str) -> bool {
+        if let Some(line) = line.strip_prefix(SOURCE_LINE) {
+            return self.parse_source_line(line);
+        }
+
+        let fields: Vec<&str> = line.split('\t').collect();
+        let count = |views: &str, raw: &str| {
+            Some(ViewCount {
+                views: views.parse().ok()?,
+                raw: raw.parse().ok()?,
+            })
+        };
+        let (period, slug, count) = match fields[..] {
+            [slug, views] => (None, slug, count(views, views)),
+            [slug, views, raw] => (None, slug, count(views, raw)),
+            [period, slug, views, raw] => match Period::parse(period) {
+                Some(period) => (Some(period), slug, count(views, raw)),
+                None => return false,
+            },
+            _ => return false,
+        };
+        let Some(count) = count else {
+            return false;
+        };
+        self.views.add(period, slug.to_string(), count);
+        true
+    }
+
+    fn parse_source_line(&mut self, line: &str) -> bool {
+        let [kind, period, slug, value, views] = line.split('\t').collect::<Vec<_>>()[..] else {
+            return false;
+        };
+        let (Some(kind), Ok(views)) = (SourceKind::parse(kind), views.parse::<u64>()) else {
+            return false;
+        };
+        let period = match period {
+            NO_PERIOD => None,
+            period => match Period::parse(period) {
+                Some(period) => Some(period),
+                None => return false,
+            },
+        };
+        let key = SourceKey {
+            slug: slug.to_string(),
+            kind,
+            value: value.to_string(),
+        };
+        self.sources.add(period, key, views);
+        true
+    }
+
+    /// Every bucket as a tab separated line, oldest first and sorted by slug within
+    ///
+    /// View counts come first as `[period]`, slug, views and requests; source counts
+    /// follow as `@kind`, period or `-`, slug, value and views.
+    pub fn lines(&self) -> Vec<String> {
+        let mut lines = self.views.lines(|period, slug, count| match period {
+            None => format!("{slug}\t{}\t{}", count.views, count.raw),
+            Some(_) => format!(
+                "{}\t{slug}\t{}\t{}",
+                Period::format(period),
+                count.views,
+                count.raw
+            ),
+        });
+        lines.extend(self.sources.lines(|period, key, views| {
+            format!(
+                "{SOURCE_LINE}{}\t{}\t{}\t{}\t{views}",
+                key.kind.as_str(),
+                Period::format(period),
+                key.slug,
+                key.value
+            )
+        }));
+        lines
+    }
+}
+
+fn merge<K: Clone + Eq + Hash, V: Tally>(into: &mut HashMap<K, V>, buckets: &HashMap<K, V>) {
+    for (key, value) in buckets {
+        into.entry(key.clone()).or_default().add(*value);
+    }
+}
+
+fn sorted_lines<K: Ord, V>(
+    buckets: &HashMap<K, V>,
+    line: impl Fn(&K, &V) -> String,
+) -> Vec<String> {
+    let mut entries: Vec<(&K, &V)> = buckets.iter().collect();
+    entries.sort_by(|a, b| a.0.cmp(b.0));
+    entries
+        .into_iter()
+        .map(|(key, value)| line(key, value))
+        .collect()
+}
+
+fn first_day_of_month(day: NaiveDate) -> NaiveDate {
+    day.with_day(1).expect("every month has a first day")
+}
+
+fn last_day_of_month(month: NaiveDate) -> NaiveDate {
+    month
+        .checked_add_months(chrono::Months::new(1))
+        .map_or(NaiveDate::MAX, |next| next - Duration::days(1))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn day(month: u32, day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(2026, month, day).unwrap()
+    }
+
+    #[test]
+    fn test_old_days_are_downsampled_into_months() {
+        let mut history = ViewHistory::default().with_retention(Retention { days: 7, months: 1 });
+        history.add(day(1, 15), "post", true);
+        history.add(day(2, 20), "post", true);
+        history.add(day(3, 28), "post", false);
+        history.add(day(3, 30), "post", true);
+        let source = ViewSource {
+            referrer: Some("example.org".to_string()),
+            ..ViewSource::default()
+        };
+        history.add_source(day(2, 20), "post", &source);
+        history.add_source(day(3, 30), "post", &source);
+
+        assert!(history.compact(day(3, 31)));
+        assert!(!history.compact(day(3, 31)));
+        assert_eq!(
+            history.lines(),
+            vec![
+                "post\t1\t1",
+                "2026-02\tpost\t1\t1",
+                "2026-03-28\tpost\t0\t1",
+                "2026-03-30\tpost\t1\t1",
+                "@referrer\t2026-02\tpost\texample.org\t1",
+                "@referrer\t2026-03-30\tpost\texample.org\t1",
+            ]
+        );
+        assert_eq!(history.totals()["post"], ViewCount { views: 3, raw: 4 });
+        assert_eq!(
+            history.sources(day(2, 1), day(3, 31)).referrers,
+            vec![SourceCount {
+                value: "example.org".to_string(),
+                views: 2
+            }]
+        );
+
+        let mut reloaded = ViewHistory::default();
+        assert!(history.lines().iter().all(|line| reloaded.parse_line(line)));
+        assert_eq!(reloaded.lines(), history.lines());
+    }
+}
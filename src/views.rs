@@ -1,18 +1,66 @@
+/// Per-post view counters kept in a snapshot file plus an append-only log
 use std::{
     collections::HashMap,
-    fs,
-    io::{self, BufRead, BufReader},
-    path::PathBuf,
+    fs::{self, File, OpenOptions},
+    io::{self, BufRead, BufReader, Read, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::Duration,
 };
 
+use chrono::NaiveDate;
+
+use crate::{
+    db::{local_today, Clock},
+    view_history::{Retention, SourceKind, ViewHistory, ViewSource},
+};
+
+/// First line of the snapshot: logs older than this generation are already counted in it
+const GENERATION_HEADER: &str = "#generation";
+/// Marks log entries that count towards the raw total only
+const RAW_ONLY: &str = "raw";
+
+/// View counts persisted as a snapshot (`views.tsv`) and a write-ahead log
+///
+/// Two numbers are kept per post: `counts` holds views that passed the
+/// [`crate::visitors::ViewFilter`], `raw` every request for the page, bots and repeats
+/// included. Both are also bucketed per day in a [`ViewHistory`], which the snapshot
+/// stores alongside the totals.
+///
+/// Every view appends one line to the current log, `views.tsv.<generation>.log`, so a
+/// page view costs a single small write. [`ViewCounterStore::flush`] compacts the log
+/// into the snapshot: it moves appends to a new generation, records that generation in
+/// the snapshot, and only then deletes the old logs. Loading replays the logs from the
+/// snapshot's generation on, so a crash at any point neither loses nor repeats a view.
 pub struct ViewCounterStore {
     file_path: PathBuf,
     counts: HashMap<String, u64>,
+    raw: HashMap<String, u64>,
+    history: ViewHistory,
+    /// Decides which day a view is bucketed under
+    clock: Clock,
+    generation: u64,
+    /// Opened on the first view, so read-only users such as the mail report never write
+    log: Option<File>,
+    /// Bytes of the current log that hold complete entries
+    log_len: u64,
+    /// Views appended since the last flush
+    unflushed: usize,
 }
 
 impl ViewCounterStore {
+    /// Load the counts, bucketing views by the local date
     pub fn load(file_path: PathBuf) -> io::Result<Self> {
-        let mut counts = HashMap::new();
+        Self::load_with_clock(file_path, local_today)
+    }
+
+    /// Load the counts, bucketing views by the days `clock` reports
+    ///
+    /// Log entries written before views were bucketed by day are replayed under `clock`'s
+    /// today, so the clock has to be known before loading.
+    pub fn load_with_clock(file_path: PathBuf, clock: Clock) -> io::Result<Self> {
+        let mut history = ViewHistory::default();
+        let mut generation = 0;
 
         if file_path.exists() {
             let file = fs::File::open(&file_path)?;
@@ -24,33 +72,134 @@ impl ViewCounterStore {
                     Err(_) => continue,
                 };
 
-                let Some((slug, count_raw)) = line.split_once('\t') else {
-                    continue;
-                };
+                if let Some(header) = line.strip_prefix(GENERATION_HEADER) {
+                    generation = header.trim().parse().unwrap_or(0);
+                } else {
+                    history.parse_line(&line);
+                }
+            }
+        }
 
-                let Ok(count) = count_raw.parse::<u64>() else {
-                    continue;
-                };
+        let mut store = Self {
+            file_path,
+            counts: HashMap::new(),
+            raw: HashMap::new(),
+            history,
+            clock,
+            generation,
+            log: None,
+            log_len: 0,
+            unflushed: 0,
+        };
+        for (slug, count) in store.history.totals() {
+            // Posts only bots asked for stay out of the filtered counts
+            if count.views > 0 {
+                store.counts.insert(slug.clone(), count.views);
+            }
+            store.raw.insert(slug, count.raw);
+        }
 
-                counts.insert(slug.to_string(), count);
+        let today = clock();
+        for (log_generation, path) in store.logs()? {
+            if log_generation < store.generation {
+                continue;
             }
+            let (views, len) = read_log(&path)?;
+            for entry in views {
+                let day = entry.day.unwrap_or(today);
+                store.count(day, entry.slug, entry.counted, &entry.source);
+                store.unflushed += 1;
+            }
+            store.generation = log_generation;
+            store.log_len = len;
         }
+        Ok(store)
+    }
 
-        Ok(Self { file_path, counts })
+    /// Keep daily and monthly buckets for as long as `retention` says
+    pub fn with_retention(mut self, retention: Retention) -> Self {
+        self.history = self.history.with_retention(retention);
+        self
     }
 
+    /// Count a view of `slug` and append it to the log
     pub fn increment(&mut self, slug: &str) -> io::Result<u64> {
-        let count = self.counts.entry(slug.to_string()).or_insert(0);
-        *count += 1;
-        let updated_count = *count;
-        self.persist()?;
-        Ok(updated_count)
+        self.record(slug, true)
+    }
+
+    /// Log a request for `slug`, counting it as a view only when `counted`
+    ///
+    /// # Returns
+    /// The filtered view count of `slug`, including this request if it was counted
+    pub fn record(&mut self, slug: &str, counted: bool) -> io::Result<u64> {
+        self.record_from(slug, counted, &ViewSource::default())
+    }
+
+    /// Like [`ViewCounterStore::record`], attributing a counted view to `source`
+    pub fn record_from(
+        &mut self,
+        slug: &str,
+        counted: bool,
+        source: &ViewSource,
+    ) -> io::Result<u64> {
+        let today = (self.clock)();
+        // Parts that would break the line format are dropped rather than escaped
+        let mut source = source.clone();
+        for (kind, value) in source.clone().parts() {
+            if value.contains(['\t', '\n', '\r']) {
+                source.clear(kind);
+            }
+        }
+        let entry = if counted {
+            let mut entry = format!("{today}\t{slug}");
+            for (kind, value) in source.parts() {
+                entry.push_str(&format!("\t{}={value}", kind.as_str()));
+            }
+            entry + "\n"
+        } else {
+            format!("{today}\t{slug}\t{RAW_ONLY}\n")
+        };
+        let log = self.open_log()?;
+        if let Err(err) = log.write_all(entry.as_bytes()) {
+            // Drop the handle so the next view truncates whatever part was written
+            self.log = None;
+            return Err(err);
+        }
+        self.log_len += entry.len() as u64;
+        self.unflushed += 1;
+
+        self.count(today, slug.to_string(), counted, &source);
+        Ok(self.get(slug))
+    }
+
+    fn count(&mut self, day: NaiveDate, slug: String, counted: bool, source: &ViewSource) {
+        self.history.add(day, &slug, counted);
+        if counted {
+            self.history.add_source(day, &slug, source);
+            *self.counts.entry(slug.clone()).or_insert(0) += 1;
+        }
+        *self.raw.entry(slug).or_insert(0) += 1;
     }
 
     pub fn get(&self, slug: &str) -> u64 {
         self.counts.get(slug).copied().unwrap_or(0)
     }
 
+    /// Requests for `slug` before filtering
+    pub fn get_raw(&self, slug: &str) -> u64 {
+        self.raw.get(slug).copied().unwrap_or(0)
+    }
+
+    /// Every view count by slug
+    pub fn counts(&self) -> &HashMap<String, u64> {
+        &self.counts
+    }
+
+    /// Every unfiltered request count by slug
+    pub fn raw_counts(&self) -> &HashMap<String, u64> {
+        &self.raw
+    }
+
     pub fn snapshot_sorted(&self) -> Vec<(String, u64)> {
         let mut entries: Vec<(String, u64)> = self
             .counts
@@ -61,23 +210,197 @@ impl ViewCounterStore {
         entries
     }
 
-    fn persist(&self) -> io::Result<()> {
+    /// Views per day and slug
+    pub fn history(&self) -> &ViewHistory {
+        &self.history
+    }
+
+    /// The day views are currently counted under
+    pub fn today(&self) -> NaiveDate {
+        (self.clock)()
+    }
+
+    /// Views logged since the last [`ViewCounterStore::flush`]
+    pub fn unflushed(&self) -> usize {
+        self.unflushed
+    }
+
+    /// Compact the log into the snapshot file; does nothing when there is nothing new
+    ///
+    /// # Effects
+    /// Downsamples history past its retention, rewrites the snapshot through a temporary
+    /// file and rename, then deletes the logs it now covers. Later views go to a log of
+    /// the next generation.
+    pub fn flush(&mut self) -> io::Result<()> {
+        let downsampled = self.history.compact((self.clock)());
+        if self.unflushed == 0 && !downsampled {
+            return Ok(());
+        }
+        if let Some(log) = self.log.take() {
+            log.sync_data()?;
+        }
+        let covered = self.generation + 1;
+        self.persist(covered)?;
+
+        self.generation = covered;
+        self.log_len = 0;
+        self.unflushed = 0;
+        for (log_generation, path) in self.logs()? {
+            if log_generation < covered {
+                fs::remove_file(path)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn persist(&self, generation: u64) -> io::Result<()> {
         if let Some(parent) = self.file_path.parent() {
             fs::create_dir_all(parent)?;
         }
 
         let tmp_path = self.file_path.with_extension("tmp");
-        let mut lines: Vec<(&String, &u64)> = self.counts.iter().collect();
-        lines.sort_by(|a, b| a.0.cmp(b.0));
-
-        let data = lines
-            .into_iter()
-            .map(|(slug, count)| format!("{slug}\t{count}"))
+        let data = std::iter::once(format!("{GENERATION_HEADER}\t{generation}"))
+            .chain(self.history.lines())
             .collect::<Vec<String>>()
             .join("\n");
 
-        fs::write(&tmp_path, data)?;
+        let mut file = File::create(&tmp_path)?;
+        file.write_all(data.as_bytes())?;
+        file.sync_all()?;
         fs::rename(tmp_path, &self.file_path)?;
         Ok(())
     }
+
+    fn log_path(&self, generation: u64) -> PathBuf {
+        let mut name = self.file_path.file_name().unwrap_or_default().to_owned();
+        name.push(format!(".{generation}.log"));
+        self.file_path.with_file_name(name)
+    }
+
+    /// Existing log files with their generation, oldest first
+    fn logs(&self) -> io::Result<Vec<(u64, PathBuf)>> {
+        let dir = match self.file_path.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => parent,
+            _ => Path::new("."),
+        };
+        let Some(prefix) = self.file_path.file_name().and_then(|name| name.to_str()) else {
+            return Ok(Vec::new());
+        };
+        let entries = match fs::read_dir(dir) {
+            Ok(entries) => entries,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(err) => return Err(err),
+        };
+
+        let mut logs = Vec::new();
+        for entry in entries {
+            let entry = entry?;
+            let name = entry.file_name();
+            let Some(generation) = name
+                .to_str()
+                .and_then(|name| name.strip_prefix(prefix))
+                .and_then(|rest| rest.strip_prefix('.'))
+                .and_then(|rest| rest.strip_suffix(".log"))
+                .and_then(|generation| generation.parse::<u64>().ok())
+            else {
+                continue;
+            };
+            logs.push((generation, entry.path()));
+        }
+        logs.sort();
+        Ok(logs)
+    }
+
+    fn open_log(&mut self) -> io::Result<&mut File> {
+        if self.log.is_none() {
+            if let Some(parent) = self.file_path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            let mut log = OpenOptions::new()
+                .create(true)
+                .truncate(false)
+                .write(true)
+                .open(self.log_path(self.generation))?;
+            // A crash can leave half an entry at the end; it was never counted
+            log.set_len(self.log_len)?;
+            log.seek(SeekFrom::Start(self.log_len))?;
+            self.log = Some(log);
+        }
+        Ok(self.log.as_mut().expect("the log was just opened"))
+    }
+}
+
+/// One request recorded in a log
+struct LogEntry {
+    /// Missing from entries written before views were bucketed by day
+    day: Option<NaiveDate>,
+    slug: String,
+    counted: bool,
+    source: ViewSource,
+}
+
+/// Complete entries of a log, and the length in bytes they take up
+fn read_log(path: &Path) -> io::Result<(Vec<LogEntry>, u64)> {
+    let mut data = Vec::new();
+    File::open(path)?.read_to_end(&mut data)?;
+    let complete = data
+        .iter()
+        .rposition(|byte| *byte == b'\n')
+        .map_or(0, |last| last + 1);
+
+    let views = String::from_utf8_lossy(&data[..complete])
+        .lines()
+        .filter(|line| !line.is_empty())
+        .map(|line| {
+            let mut fields: Vec<&str> = line.split('\t').collect();
+            let counted = !(fields.len() > 1 && fields.last() == Some(&RAW_ONLY));
+            if !counted {
+                fields.pop();
+            }
+            let day = match fields[..] {
+                [day, _, ..] => day.parse::<NaiveDate>().ok(),
+                _ => None,
+            };
+            if day.is_some() {
+                fields.remove(0);
+            }
+
+            let mut source = ViewSource::default();
+            for field in &fields[1..] {
+                if let Some((kind, value)) = field.split_once('=') {
+                    if let Some(kind) = SourceKind::parse(kind) {
+                        source.set(kind, value.to_string());
+                    }
+                }
+            }
+            LogEntry {
+                day,
+                slug: fields[0].to_string(),
+                counted,
+                source,
+            }
+        })
+        .collect();
+    Ok((views, complete as u64))
+}
+
+/// Flush `views` every `interval` in the background, for as long as the runtime lives
+pub fn spawn_flusher(
+    views: Arc<Mutex<ViewCounterStore>>,
+    interval: Duration,
+) -> tokio::task::JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(interval);
+        ticker.tick().await;
+        loop {
+            ticker.tick().await;
+            let mut views = match views.lock() {
+                Ok(guard) => guard,
+                Err(poisoned) => poisoned.into_inner(),
+            };
+            if let Err(err) = views.flush() {
+                eprintln!("Failed to flush view counts: {err}");
+            }
+        }
+    })
 }
@@ -0,0 +1,315 @@
+/// Filesystem watching for local-path mode: re-parse edited posts and notify open browser tabs
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+use notify::{RecommendedWatcher, RecursiveMode, Watcher};
+use tokio::sync::{broadcast, mpsc, Mutex};
+
+use crate::{
+    data::{parse_with_links, Post},
+    db::{Database, InMemDatabase},
+    diagnostics::{duplicate_slugs, log_diagnostics, Diagnostic},
+    error::ApplicationError,
+    http::state::AppState,
+    repo_utils::{is_post_path, resource_path, Repository, ResourceLinks},
+    resources::ResourceManifest,
+    snapshot::Snapshot,
+};
+
+/// How long to wait for an editor to finish writing before reprocessing a burst of events
+const WATCH_DEBOUNCE: Duration = Duration::from_millis(200);
+
+/// Parsed view of a local content directory that can be refreshed file by file
+pub struct LocalWatch {
+    root: PathBuf,
+    resources_dir: PathBuf,
+    /// Resources currently published, which posts link to by fingerprint
+    resources: ResourceManifest,
+    posts: BTreeMap<PathBuf, Post>,
+    /// Posts whose current contents fail to parse, including the ones in `posts` that keep
+    /// their previous version
+    failures: BTreeMap<PathBuf, Diagnostic>,
+    /// Resources linked by the version of each post being served
+    links: ResourceLinks,
+}
+
+/// Files reprocessed by a single [`LocalWatch::apply`] call, relative to the content root
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct WatchOutcome {
+    pub changed_posts: Vec<PathBuf>,
+    pub changed_resources: Vec<PathBuf>,
+    /// Posts that failed to parse and kept their previous version
+    pub failed_posts: Vec<PathBuf>,
+}
+
+impl WatchOutcome {
+    pub fn is_empty(&self) -> bool {
+        self.changed_posts.is_empty() && self.changed_resources.is_empty()
+    }
+}
+
+impl LocalWatch {
+    /// Parse every post under `root` and publish its resources into `resources_dir`
+    pub async fn load(
+        root: impl AsRef<Path>,
+        resources_dir: impl Into<PathBuf>,
+    ) -> Result<Self, ApplicationError> {
+        let root = fs::canonicalize(root.as_ref())?;
+        let resources_dir = resources_dir.into();
+        let repo = Repository::try_from(root.as_path())?;
+
+        let resources = ResourceManifest::build(&repo.resources)?;
+        resources.publish(&repo.resources, &resources_dir)?;
+
+        let mut posts = BTreeMap::new();
+        let mut failures = BTreeMap::new();
+        let mut links = ResourceLinks::default();
+        for entry in fs::read_dir(&repo.posts)? {
+            let path = entry?.path();
+            if !path.is_file() || is_editor_artifact(&path) {
+                continue;
+            }
+            let relative = path.strip_prefix(&root).unwrap_or(&path).to_path_buf();
+            match parse_with_links(&path, &resources).await {
+                Ok((post, linked)) => {
+                    links.insert(relative.clone(), linked);
+                    posts.insert(relative, post);
+                }
+                Err(err) => {
+                    failures.insert(relative.clone(), Diagnostic::from_error(relative, &err));
+                }
+            }
+        }
+
+        let watch = Self {
+            root,
+            resources_dir,
+            resources,
+            posts,
+            failures,
+            links,
+        };
+        log_diagnostics(&watch.diagnostics());
+        Ok(watch)
+    }
+
+    /// Parse every post and publish every resource again, as [`LocalWatch::load`] does
+    pub async fn rescan(&mut self) -> Result<(), ApplicationError> {
+        *self = Self::load(&self.root, self.resources_dir.clone()).await?;
+        Ok(())
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    pub fn posts(&self) -> Vec<Post> {
+        self.posts.values().cloned().collect()
+    }
+
+    /// Posts currently skipped, and why
+    pub fn diagnostics(&self) -> Vec<Diagnostic> {
+        let mut diagnostics: Vec<Diagnostic> = self
+            .failures
+            .iter()
+            .filter(|(path, _)| !self.posts.contains_key(*path))
+            .map(|(_, diagnostic)| diagnostic.clone())
+            .collect();
+        diagnostics.extend(duplicate_slugs(&self.posts));
+        diagnostics.sort_by(|a, b| a.path.cmp(&b.path));
+        diagnostics
+    }
+
+    /// Posts that fail to parse but keep serving their previous version, and why
+    pub fn stale(&self) -> Vec<Diagnostic> {
+        self.failures
+            .iter()
+            .filter(|(path, _)| self.posts.contains_key(*path))
+            .map(|(_, diagnostic)| diagnostic.clone())
+            .collect()
+    }
+
+    /// Build a database from the currently parsed posts
+    pub fn build_database(&self) -> Result<InMemDatabase, ApplicationError> {
+        let mut db = InMemDatabase::new();
+        for post in self.posts.values() {
+            db.insert_parsed_to_database(post.clone())?;
+        }
+        Ok(db)
+    }
+
+    /// Reprocess the given absolute paths, ignoring anything outside `posts/` and `resources/`
+    ///
+    /// A post that fails to parse keeps its previous version, since an editor may have
+    /// saved it half-way through a change.
+    pub async fn apply(
+        &mut self,
+        changed: &BTreeSet<PathBuf>,
+    ) -> Result<WatchOutcome, ApplicationError> {
+        let mut outcome = WatchOutcome::default();
+
+        let mut posts = BTreeSet::new();
+        for absolute in changed {
+            let Ok(relative) = absolute.strip_prefix(&self.root) else {
+                continue;
+            };
+            if is_editor_artifact(relative) {
+                continue;
+            }
+
+            if is_post_path(relative) {
+                posts.insert(relative.to_path_buf());
+            } else if let Some(resource) = resource_path(relative) {
+                let name = resource.to_string_lossy();
+                if absolute.is_file() {
+                    self.resources
+                        .publish_one(absolute, &self.resources_dir, &name)?;
+                } else if self.resources.fingerprinted(&name).is_some() {
+                    let target = self.resources_dir.join(resource);
+                    if target.is_file() {
+                        fs::remove_file(&target)?;
+                    }
+                    self.resources.remove(&name);
+                } else {
+                    continue;
+                }
+                outcome.changed_resources.push(relative.to_path_buf());
+            }
+        }
+
+        posts.extend(self.links.posts_linking_to(&outcome.changed_resources));
+
+        for relative in posts {
+            let absolute = self.root.join(&relative);
+            if absolute.is_file() {
+                match parse_with_links(&absolute, &self.resources).await {
+                    Ok((post, links)) => {
+                        self.failures.remove(&relative);
+                        self.links.insert(relative.clone(), links);
+                        self.posts.insert(relative.clone(), post);
+                    }
+                    Err(err) => {
+                        let diagnostic = Diagnostic::from_error(&relative, &err);
+                        eprintln!("Keeping previous version of post {diagnostic}");
+                        self.failures.insert(relative.clone(), diagnostic);
+                        outcome.failed_posts.push(relative);
+                        continue;
+                    }
+                }
+            } else {
+                let had_failure = self.failures.remove(&relative).is_some();
+                self.links.remove(&relative);
+                if self.posts.remove(&relative).is_none() && !had_failure {
+                    continue;
+                }
+            }
+            outcome.changed_posts.push(relative);
+        }
+
+        Ok(outcome)
+    }
+}
+
+/// Swap files, backups and hidden files editors leave next to the real content
+fn is_editor_artifact(path: &Path) -> bool {
+    path.file_name()
+        .and_then(|name| name.to_str())
+        .is_some_and(|name| name.starts_with('.') || name.ends_with('~'))
+}
+
+/// Watch the directory of `local_watch` and keep the served snapshot in sync with it
+///
+/// # Arguments
+/// * `local_watch` - The loaded content, shared with the [`crate::reload::Reloader`] so a
+///   full reload and the edits applied here never diverge
+/// * `state` - Application state whose snapshot gets replaced after every change
+/// * `live_reload` - Optional channel notified after each applied change, for browser refresh
+///
+/// # Returns
+/// The filesystem watcher; dropping it stops watching
+///
+/// # Effects
+/// - Keeps the published resources updated
+/// - Re-parses only the posts whose files or linked resources changed
+pub async fn watch_local_path(
+    local_watch: Arc<Mutex<LocalWatch>>,
+    state: AppState,
+    live_reload: Option<broadcast::Sender<()>>,
+) -> Result<RecommendedWatcher, ApplicationError> {
+    let root = local_watch.lock().await.root().to_path_buf();
+
+    let (events_tx, events_rx) = mpsc::unbounded_channel();
+    let mut watcher =
+        notify::recommended_watcher(move |event: notify::Result<notify::Event>| match event {
+            Ok(event) => {
+                let _ = events_tx.send(event.paths);
+            }
+            Err(err) => eprintln!("Filesystem watch error: {err}"),
+        })?;
+    watcher.watch(&root, RecursiveMode::Recursive)?;
+
+    println!("Watching {:?} for changes", root);
+    tokio::spawn(apply_changes(local_watch, state, events_rx, live_reload));
+
+    Ok(watcher)
+}
+
+async fn apply_changes(
+    local_watch: Arc<Mutex<LocalWatch>>,
+    state: AppState,
+    mut events: mpsc::UnboundedReceiver<Vec<PathBuf>>,
+    live_reload: Option<broadcast::Sender<()>>,
+) {
+    while let Some(paths) = events.recv().await {
+        let mut changed: BTreeSet<PathBuf> = paths.into_iter().collect();
+        while let Ok(Some(paths)) = tokio::time::timeout(WATCH_DEBOUNCE, events.recv()).await {
+            changed.extend(paths);
+        }
+
+        let mut watch = local_watch.lock().await;
+        let outcome = match watch.apply(&changed).await {
+            Ok(outcome) => outcome,
+            Err(err) => {
+                eprintln!("Failed to apply filesystem changes: {err}");
+                continue;
+            }
+        };
+        let diagnostics = watch.diagnostics();
+        let stale = watch.stale();
+        let current = state.snapshot();
+        if outcome.is_empty() && diagnostics == current.diagnostics && stale == current.stale {
+            continue;
+        }
+
+        match watch.build_database() {
+            Ok(new_db) => {
+                state.content.publish(
+                    Snapshot::new(new_db)
+                        .with_diagnostics(diagnostics)
+                        .with_stale(stale),
+                );
+            }
+            Err(err) => {
+                eprintln!("Failed to rebuild database: {err}");
+                continue;
+            }
+        }
+        // A post that broke keeps its previous version, so only the reports changed
+        if outcome.is_empty() {
+            continue;
+        }
+
+        println!(
+            "Reloaded {} posts and {} resources",
+            outcome.changed_posts.len(),
+            outcome.changed_resources.len()
+        );
+        if let Some(live_reload) = &live_reload {
+            // No receivers just means no browser tab is open
+            let _ = live_reload.send(());
+        }
+    }
+}
@@ -0,0 +1,166 @@
+mod common;
+
+mod admin_dashboard_tests {
+    use axum::{
+        body::{to_bytes, Body},
+        http::{header, Request, StatusCode},
+        response::Response,
+        Router,
+    };
+    use chrono::NaiveDate;
+    use personal::{
+        db::InMemDatabase,
+        http::{
+            admin::{self, AdminCredentials},
+            state::AppState,
+        },
+        views::ViewCounterStore,
+    };
+    use tower::ServiceExt;
+
+    use crate::common::{database, post, PostBuilder};
+
+    /// base64 of "editor:hunter2"
+    const LOGIN: &str = "Basic ZWRpdG9yOmh1bnRlcjI=";
+
+    fn march_fifth() -> NaiveDate {
+        NaiveDate::from_ymd_opt(2026, 3, 5).unwrap()
+    }
+
+    fn dashboard(views: ViewCounterStore) -> Router {
+        let db = database(&[
+            post("popular").with_title("Popular post"),
+            post("quiet")
+                .with_title("Quiet post")
+                .with_tags(&["rust", "sql"])
+                .with_date(2026, 1, 2),
+            post("forgotten")
+                .with_title("Forgotten post")
+                .with_tags(&["sql"])
+                .with_date(2026, 1, 3),
+        ]);
+        let state = AppState::new(db, views).with_admin_credentials(AdminCredentials {
+            username: Some("editor".to_string()),
+            password: Some("hunter2".to_string()),
+            ..AdminCredentials::default()
+        });
+        admin::router().with_state(state)
+    }
+
+    async fn get(app: &Router, uri: &str, authorization: Option<&str>) -> Response {
+        let mut request = Request::get(uri);
+        if let Some(authorization) = authorization {
+            request = request.header(header::AUTHORIZATION, authorization);
+        }
+        app.clone()
+            .oneshot(request.body(Body::empty()).unwrap())
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_dashboard_asks_browsers_to_log_in() {
+        let dir = tempfile::tempdir().unwrap();
+        let app = dashboard(ViewCounterStore::load(dir.path().join("views.tsv")).unwrap());
+
+        let response = get(&app, "/admin", None).await;
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        assert!(response.headers()[header::WWW_AUTHENTICATE]
+            .to_str()
+            .unwrap()
+            .starts_with("Basic"));
+        let response = get(&app, "/admin", Some("Basic ZWRpdG9yOndyb25n")).await;
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        let response = get(&app, "/admin/diagnostics", Some(LOGIN)).await;
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let closed = admin::router().with_state(AppState::new(
+            InMemDatabase::new(),
+            ViewCounterStore::load(dir.path().join("other.tsv")).unwrap(),
+        ));
+        let response = get(&closed, "/admin", Some(LOGIN)).await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_dashboard_shows_posts_tags_and_unviewed_posts() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut views =
+            ViewCounterStore::load_with_clock(dir.path().join("views.tsv"), march_fifth).unwrap();
+        for _ in 0..3 {
+            views.record("popular", true).unwrap();
+        }
+        views.record("quiet", true).unwrap();
+        views.record("quiet", false).unwrap();
+        views.record("deleted", true).unwrap();
+        let app = dashboard(views);
+
+        let response = get(&app, "/admin?days=30", Some(LOGIN)).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()[header::CACHE_CONTROL], "no-store");
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let html = String::from_utf8(body.to_vec()).unwrap();
+
+        assert!(html.contains("2026-02-04 to 2026-03-05"));
+        assert!(html.contains("5 views <span class=\"muted\">(6 requests)</span>"));
+        assert!(html.contains("<svg class=\"chart\""));
+        assert!(html.contains("2026-03-05: 5 views, 6 requests"));
+        assert!(html.contains("<svg class=\"sparkline\""));
+        assert!(html.contains("deleted <span class=\"muted\">(not published)</span>"));
+
+        let popular = html.find("Popular post").unwrap();
+        let quiet = html.find("Quiet post").unwrap();
+        assert!(popular < quiet);
+        let rust = html.find("<td>rust</td>").unwrap();
+        let sql = html.find("<td>sql</td>").unwrap();
+        assert!(rust < sql);
+
+        let unviewed = html.find("Posts without views").unwrap();
+        assert!(html[unviewed..].contains("Forgotten post"));
+        assert!(!html[unviewed..].contains("Quiet post"));
+    }
+
+    async fn json(app: &Router, uri: &str) -> serde_json::Value {
+        let response = get(app, uri, Some(LOGIN)).await;
+        assert_eq!(response.status(), StatusCode::OK, "{uri}");
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        serde_json::from_slice(&body).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_view_statistics_need_a_login() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut views = ViewCounterStore::load(dir.path().join("views.tsv")).unwrap();
+        views.record("popular", true).unwrap();
+        views.record("popular", true).unwrap();
+        views.record("popular", false).unwrap();
+        let app = dashboard(views);
+
+        for uri in [
+            "/admin/views",
+            "/admin/views/movers",
+            "/admin/views/popular",
+        ] {
+            let response = get(&app, uri, None).await;
+            assert_eq!(response.status(), StatusCode::UNAUTHORIZED, "{uri}");
+        }
+
+        assert_eq!(
+            json(&app, "/admin/views?days=30").await,
+            serde_json::json!([{"slug": "popular", "views": 2, "raw": 3}])
+        );
+
+        let daily = json(&app, "/admin/views/popular?days=3").await;
+        let days = daily.as_array().unwrap();
+        assert_eq!(days.len(), 3);
+        assert_eq!(days[2]["views"], 2);
+        assert_eq!(days[0]["views"], 0);
+
+        let movers = json(&app, "/admin/views/movers").await;
+        assert_eq!(movers[0]["slug"], "popular");
+        assert_eq!(movers[0]["change"], 2);
+
+        let response = get(&app, "/admin/views/missing", Some(LOGIN)).await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+}
@@ -0,0 +1,249 @@
+mod common;
+
+mod admin_reload_tests {
+    use std::collections::BTreeMap;
+    use std::time::Duration;
+
+    use axum::{
+        body::{to_bytes, Body},
+        http::{Request, StatusCode},
+        Router,
+    };
+    use hmac::{Hmac, Mac};
+    use personal::{
+        db::Database,
+        diagnostics::Diagnostic,
+        http::{
+            admin::{self, AdminCredentials},
+            state::AppState,
+        },
+        reload::{build_database, ContentSource, Reloader},
+        repo_utils::RepositorySync,
+        snapshot::Snapshot,
+        view_history::ViewSource,
+        views::ViewCounterStore,
+    };
+    use sha2::Sha256;
+    use tower::ServiceExt;
+
+    use crate::common::{bare_remote, commit_snapshot, post_source};
+
+    const TOKEN: &str = "admin-token";
+    const SECRET: &str = "webhook-secret";
+
+    fn sign(body: &[u8]) -> String {
+        let mut mac = Hmac::<Sha256>::new_from_slice(SECRET.as_bytes()).unwrap();
+        mac.update(body);
+        format!("sha256={}", hex::encode(mac.finalize().into_bytes()))
+    }
+
+    async fn wait_for_commit(state: &AppState, commit: &str) {
+        for _ in 0..100 {
+            if state.snapshot().commit.as_deref() == Some(commit) {
+                return;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        panic!("reload to {commit} never happened");
+    }
+
+    async fn send(app: &Router, request: Request<Body>) -> StatusCode {
+        app.clone().oneshot(request).await.unwrap().status()
+    }
+
+    #[tokio::test]
+    async fn test_admin_and_webhook_reloads() {
+        let root = tempfile::tempdir().unwrap();
+        let (remote, url) = bare_remote(root.path());
+        let mut files: BTreeMap<&str, Vec<u8>> = BTreeMap::new();
+        files.insert("posts/first.md", post_source("first", "First").into_bytes());
+        commit_snapshot(&remote, &files);
+
+        let mut source = ContentSource::Git(Box::new(
+            RepositorySync::new(url, root.path().join("mirror"))
+                .with_resources_dir(root.path().join("misc")),
+        ));
+        let snapshot = build_database(&mut source).await.unwrap();
+        let views = ViewCounterStore::load(root.path().join("views.tsv")).unwrap();
+        let state = AppState::from_snapshot(
+            Snapshot::new(snapshot.db).with_commit(snapshot.commit),
+            views,
+        )
+        .with_admin_credentials(AdminCredentials {
+            token: Some(TOKEN.to_string()),
+            webhook_secret: Some(SECRET.to_string()),
+            ..AdminCredentials::default()
+        });
+        let reloader = Reloader::spawn(source, &state, Duration::from_millis(10));
+        let state = state.with_reloader(reloader);
+        let app = admin::router().with_state(state.clone());
+
+        let unauthorized = Request::post("/admin/reload").body(Body::empty()).unwrap();
+        assert_eq!(send(&app, unauthorized).await, StatusCode::UNAUTHORIZED);
+
+        files.insert(
+            "posts/second.md",
+            post_source("second", "Second").into_bytes(),
+        );
+        let second = commit_snapshot(&remote, &files).to_string();
+        let authorized = Request::post("/admin/reload")
+            .header("Authorization", format!("Bearer {TOKEN}"))
+            .body(Body::empty())
+            .unwrap();
+        assert_eq!(send(&app, authorized).await, StatusCode::ACCEPTED);
+        wait_for_commit(&state, &second).await;
+        assert!(state
+            .snapshot()
+            .db
+            .get_by_slug("second".to_string())
+            .is_some());
+
+        files.insert("posts/third.md", post_source("third", "Third").into_bytes());
+        let third = commit_snapshot(&remote, &files).to_string();
+        let payload = br#"{"ref":"refs/heads/main"}"#;
+
+        let forged = Request::post("/webhooks/push")
+            .header("X-GitHub-Event", "push")
+            .header("X-Hub-Signature-256", "sha256=00")
+            .body(Body::from(&payload[..]))
+            .unwrap();
+        assert_eq!(send(&app, forged).await, StatusCode::UNAUTHORIZED);
+
+        let other_branch = br#"{"ref":"refs/heads/drafts"}"#;
+        let ignored = Request::post("/webhooks/push")
+            .header("X-GitHub-Event", "push")
+            .header("X-Hub-Signature-256", sign(other_branch))
+            .body(Body::from(&other_branch[..]))
+            .unwrap();
+        assert_eq!(send(&app, ignored).await, StatusCode::OK);
+
+        let signed = Request::post("/webhooks/push")
+            .header("X-GitHub-Event", "push")
+            .header("X-Hub-Signature-256", sign(payload))
+            .body(Body::from(&payload[..]))
+            .unwrap();
+        assert_eq!(send(&app, signed).await, StatusCode::ACCEPTED);
+        wait_for_commit(&state, &third).await;
+
+        let status = state.reloader.as_ref().unwrap().status();
+        assert_eq!(status.commit.as_deref(), Some(third.as_str()));
+        assert_eq!(status.posts, 3);
+        assert!(status.last_error.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_admin_diagnostics_lists_skipped_posts() {
+        let root = tempfile::tempdir().unwrap();
+        let views = ViewCounterStore::load(root.path().join("views.tsv")).unwrap();
+        let skipped = vec![Diagnostic::new("posts/broken.md", "bad yaml")];
+        let stale = vec![Diagnostic::new("posts/edited.md", "half written")];
+        let snapshot = Snapshot::new(personal::db::InMemDatabase::new())
+            .with_diagnostics(skipped)
+            .with_stale(stale);
+        let state =
+            AppState::from_snapshot(snapshot, views).with_admin_credentials(AdminCredentials {
+                token: Some(TOKEN.to_string()),
+                ..AdminCredentials::default()
+            });
+        let app = admin::router().with_state(state.clone());
+
+        let unauthorized = Request::get("/admin/diagnostics")
+            .body(Body::empty())
+            .unwrap();
+        assert_eq!(send(&app, unauthorized).await, StatusCode::UNAUTHORIZED);
+
+        let report = || async {
+            let request = Request::get("/admin/diagnostics")
+                .header("Authorization", format!("Bearer {TOKEN}"))
+                .body(Body::empty())
+                .unwrap();
+            let response = app.clone().oneshot(request).await.unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+            let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+            serde_json::from_slice::<serde_json::Value>(&body).unwrap()
+        };
+        let skipped = report().await;
+        assert_eq!(skipped["diagnostics"][0]["path"], "posts/broken.md");
+        assert_eq!(skipped["diagnostics"][0]["reason"], "bad yaml");
+        assert_eq!(skipped["diagnostics"].as_array().unwrap().len(), 1);
+        assert_eq!(skipped["stale"][0]["path"], "posts/edited.md");
+
+        // The diagnostics are swapped together with the content they describe
+        state.content.publish(
+            Snapshot::new(personal::db::InMemDatabase::new()).with_commit(Some("fixed".into())),
+        );
+        let fixed = report().await;
+        assert_eq!(fixed["commit"], "fixed");
+        assert_eq!(fixed["diagnostics"], serde_json::json!([]));
+        assert_eq!(fixed["stale"], serde_json::json!([]));
+    }
+
+    #[tokio::test]
+    async fn test_admin_stats_break_views_down_by_source() {
+        let root = tempfile::tempdir().unwrap();
+        let mut views = ViewCounterStore::load(root.path().join("views.tsv")).unwrap();
+        let from_search = ViewSource {
+            referrer: Some("duckduckgo.com".to_string()),
+            ..ViewSource::default()
+        };
+        views.record_from("first", true, &from_search).unwrap();
+        views.record_from("first", true, &from_search).unwrap();
+        views
+            .record_from(
+                "second",
+                true,
+                &ViewSource {
+                    utm_campaign: Some("spring".to_string()),
+                    ..ViewSource::default()
+                },
+            )
+            .unwrap();
+        views.record_from("second", false, &from_search).unwrap();
+        let state = AppState::new(personal::db::InMemDatabase::new(), views)
+            .with_admin_credentials(AdminCredentials {
+                token: Some(TOKEN.to_string()),
+                ..AdminCredentials::default()
+            });
+        let app = admin::router().with_state(state);
+
+        let unauthorized = Request::get("/admin/stats").body(Body::empty()).unwrap();
+        assert_eq!(send(&app, unauthorized).await, StatusCode::UNAUTHORIZED);
+
+        let request = Request::get("/admin/stats?days=30")
+            .header("Authorization", format!("Bearer {TOKEN}"))
+            .body(Body::empty())
+            .unwrap();
+        let response = app.clone().oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let stats: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(stats["totals"]["referrers"][0]["value"], "duckduckgo.com");
+        assert_eq!(stats["totals"]["referrers"][0]["views"], 2);
+        assert_eq!(stats["totals"]["utm_campaigns"][0]["value"], "spring");
+        assert_eq!(stats["posts"][0]["slug"], "first");
+        assert_eq!(stats["posts"][0]["sources"]["referrers"][0]["views"], 2);
+        assert_eq!(stats["posts"][1]["slug"], "second");
+        assert_eq!(stats["posts"][1]["raw"], 2);
+        assert_eq!(
+            stats["posts"][1]["sources"]["referrers"],
+            serde_json::json!([])
+        );
+    }
+
+    #[tokio::test]
+    async fn test_admin_routes_disabled_without_credentials() {
+        let root = tempfile::tempdir().unwrap();
+        let views = ViewCounterStore::load(root.path().join("views.tsv")).unwrap();
+        let state = AppState::new(personal::db::InMemDatabase::new(), views);
+        let app = admin::router().with_state(state);
+
+        let status_request = Request::get("/admin/reload/status")
+            .header("Authorization", "Bearer anything")
+            .body(Body::empty())
+            .unwrap();
+        assert_eq!(send(&app, status_request).await, StatusCode::NOT_FOUND);
+
+        let webhook = Request::post("/webhooks/push").body(Body::empty()).unwrap();
+        assert_eq!(send(&app, webhook).await, StatusCode::NOT_FOUND);
+    }
+}
@@ -0,0 +1,99 @@
+mod api_tests {
+    use std::path::Path;
+
+    use axum::{
+        body::{to_bytes, Body},
+        http::{Request, StatusCode},
+        Router,
+    };
+    use personal::{
+        data::Post,
+        db::{Database, InMemDatabase},
+        http::{api, state::AppState},
+        repo_utils::{get_posts_from_repository, Repository},
+        views::ViewCounterStore,
+    };
+    use tower::ServiceExt;
+
+    async fn test_app() -> (Router, tempfile::TempDir) {
+        let repo = Repository::try_from(Path::new("./tests/data")).unwrap();
+        let mut db = InMemDatabase::new();
+        for post in get_posts_from_repository(repo).await.unwrap() {
+            db.insert_parsed_to_database(post).unwrap();
+        }
+        let views_dir = tempfile::tempdir().unwrap();
+        let views = ViewCounterStore::load(views_dir.path().join("views.tsv")).unwrap();
+        let app = api::router().with_state(AppState::new(db, views));
+        (app, views_dir)
+    }
+
+    async fn get(app: Router, uri: &str) -> (StatusCode, serde_json::Value) {
+        let response = app
+            .oneshot(Request::get(uri).body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        let status = response.status();
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let json = serde_json::from_slice(&body).unwrap_or(serde_json::Value::Null);
+        (status, json)
+    }
+
+    #[tokio::test]
+    async fn test_get_post_by_slug() {
+        let (app, _views_dir) = test_app().await;
+        let (status, json) = get(app.clone(), "/api/v1/posts/example-title").await;
+        assert_eq!(status, StatusCode::OK);
+        let post: Post = serde_json::from_value(json).unwrap();
+        assert_eq!(post.markdown.slug, "example-title");
+
+        let (status, _) = get(app, "/api/v1/posts/missing").await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_listings_and_counts() {
+        let (app, _views_dir) = test_app().await;
+
+        let (status, json) = get(app.clone(), "/api/v1/posts/by-tag?tag=rust").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(json.as_array().unwrap().len(), 1);
+
+        let (_, json) = get(app.clone(), "/api/v1/posts/by-date?year=2026&month=2").await;
+        assert_eq!(json.as_array().unwrap().len(), 1);
+
+        let (_, json) = get(app.clone(), "/api/v1/tags").await;
+        assert!(json
+            .as_array()
+            .unwrap()
+            .iter()
+            .any(|tag| tag["tag"] == "rust" && tag["count"] == 1));
+
+        let (_, json) = get(app, "/api/v1/dates").await;
+        assert_eq!(json[0]["year"], 2026);
+        assert_eq!(json[0]["month"], 2);
+    }
+
+    #[tokio::test]
+    async fn test_openapi_document_lists_routes() {
+        let (app, _views_dir) = test_app().await;
+        let (status, json) = get(app, "/api/openapi.json").await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(json["paths"]["/api/v1/posts/{slug}"].is_object());
+        assert!(json["components"]["schemas"]["Post"].is_object());
+    }
+
+    #[tokio::test]
+    async fn test_view_statistics_are_not_public() {
+        let (app, _views_dir) = test_app().await;
+        for uri in [
+            "/api/v1/views",
+            "/api/v1/views/movers",
+            "/api/v1/views/example-title",
+        ] {
+            let (status, _) = get(app.clone(), uri).await;
+            assert_eq!(status, StatusCode::NOT_FOUND, "{uri}");
+        }
+        let (_, json) = get(app, "/api/openapi.json").await;
+        assert!(json["paths"]["/api/v1/views"].is_null());
+    }
+}
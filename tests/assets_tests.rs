@@ -0,0 +1,167 @@
+mod assets_tests {
+    use std::fs;
+
+    use axum::{
+        body::{to_bytes, Body},
+        http::{header, Request, StatusCode},
+        Router,
+    };
+    use personal::{
+        db::InMemDatabase,
+        http::{
+            assets::{self, StaticAssets},
+            caching::CachePolicy,
+            state::AppState,
+        },
+        resources::{fingerprinted_path, ResourceManifest},
+        views::ViewCounterStore,
+    };
+    use tower::ServiceExt;
+
+    async fn get(app: &Router, url: &str) -> (StatusCode, Option<String>, Vec<u8>) {
+        let response = app
+            .clone()
+            .oneshot(Request::get(url).body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        let status = response.status();
+        let content_type = response
+            .headers()
+            .get(header::CONTENT_TYPE)
+            .map(|value| value.to_str().unwrap().to_string());
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (status, content_type, body.to_vec())
+    }
+
+    async fn cache_control(app: &Router, url: &str) -> Option<String> {
+        let response = app
+            .clone()
+            .oneshot(Request::get(url).body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK, "{url}");
+        response
+            .headers()
+            .get(header::CACHE_CONTROL)
+            .map(|value| value.to_str().unwrap().to_string())
+    }
+
+    #[tokio::test]
+    async fn test_static_assets_are_served_without_a_static_directory() {
+        let dir = tempfile::tempdir().unwrap();
+        let overrides = dir.path().join("static");
+        fs::create_dir_all(&overrides).unwrap();
+        fs::write(overrides.join("style.css"), "body { color: red }").unwrap();
+        let resources = dir.path().join("resources");
+        fs::create_dir_all(&resources).unwrap();
+        fs::write(resources.join("diagram.png"), [1, 2, 3]).unwrap();
+
+        let state = AppState::new(
+            InMemDatabase::new(),
+            ViewCounterStore::load(dir.path().join("views.tsv")).unwrap(),
+        )
+        .with_static_assets(StaticAssets::new(Some(overrides)));
+        let app = assets::router(&resources).with_state(state);
+
+        let (status, content_type, body) = get(&app, "/static/style.css").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(content_type.as_deref(), Some("text/css"));
+        assert_eq!(body, b"body { color: red }");
+
+        let (status, content_type, body) = get(&app, "/static/highlight.css").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(content_type.as_deref(), Some("text/css"));
+        assert_eq!(body, include_bytes!("../static/highlight.css"));
+
+        let (status, _, body) = get(&app, "/static/misc/diagram.png").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body, vec![1, 2, 3]);
+
+        for url in [
+            "/static/missing.css",
+            "/static/../Cargo.toml",
+            "/static/%2E%2E/Cargo.toml",
+            "/static/misc/missing.png",
+        ] {
+            assert_eq!(get(&app, url).await.0, StatusCode::NOT_FOUND, "{url}");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_precompressed_assets_are_served_when_accepted() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("app.js"), "let answer = 42;").unwrap();
+        fs::write(dir.path().join("app.js.gz"), [31, 139]).unwrap();
+        let state = AppState::new(
+            InMemDatabase::new(),
+            ViewCounterStore::load(dir.path().join("views.tsv")).unwrap(),
+        )
+        .with_static_assets(StaticAssets::new(Some(dir.path().to_path_buf())));
+        let url = state.assets.url("app.js");
+        let app = assets::router(dir.path()).with_state(state);
+
+        for url in ["/static/app.js", url.as_str()] {
+            let response = app
+                .clone()
+                .oneshot(
+                    Request::get(url)
+                        .header(header::ACCEPT_ENCODING, "br, gzip")
+                        .body(Body::empty())
+                        .unwrap(),
+                )
+                .await
+                .unwrap();
+            assert_eq!(response.headers()[header::CONTENT_ENCODING], "gzip");
+            assert_eq!(response.headers()[header::VARY], "accept-encoding");
+            assert!(response.headers().contains_key(header::ETAG));
+            let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+            assert_eq!(body.as_ref(), [31, 139]);
+        }
+
+        let (_, content_type, body) = get(&app, "/static/app.js").await;
+        assert_eq!(content_type.as_deref(), Some("text/javascript"));
+        assert_eq!(body, b"let answer = 42;");
+    }
+
+    #[tokio::test]
+    async fn test_fingerprinted_urls_are_cached_for_good() {
+        let dir = tempfile::tempdir().unwrap();
+        let content = dir.path().join("content");
+        fs::create_dir_all(content.join("img")).unwrap();
+        fs::write(content.join("img/diagram.png"), [1, 2, 3]).unwrap();
+        let resources = dir.path().join("resources");
+        let manifest = ResourceManifest::build(&content).unwrap();
+        manifest.publish(&content, &resources).unwrap();
+
+        let static_assets = StaticAssets::default();
+        let stylesheet = static_assets.url("style.css");
+        let state = AppState::new(
+            InMemDatabase::new(),
+            ViewCounterStore::load(dir.path().join("views.tsv")).unwrap(),
+        );
+        let app = assets::router(&resources).with_state(state);
+
+        let header =
+            |policy: CachePolicy| Some(policy.header_value().to_str().unwrap().to_string());
+        let immutable = header(CachePolicy::Immutable);
+        assert_ne!(stylesheet, "/static/style.css");
+        assert_eq!(cache_control(&app, &stylesheet).await, immutable);
+        let resource = manifest.url("img/diagram.png");
+        assert_eq!(cache_control(&app, &resource).await, immutable);
+
+        // Plain names keep working, but may change
+        let asset = header(CachePolicy::Asset);
+        assert_eq!(cache_control(&app, "/static/style.css").await, asset);
+        assert_eq!(
+            cache_control(&app, "/static/misc/img/diagram.png").await,
+            asset
+        );
+
+        // A fingerprint of an earlier version gets the current file without the promise
+        let stale = fingerprinted_path("/static/style.css", b"body {}");
+        assert_eq!(cache_control(&app, &stale).await, asset);
+        let (_, content_type, body) = get(&app, &stale).await;
+        assert_eq!(content_type.as_deref(), Some("text/css"));
+        assert_eq!(body, include_bytes!("../static/style.css"));
+    }
+}
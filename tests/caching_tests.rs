@@ -0,0 +1,221 @@
+mod common;
+
+mod caching_tests {
+    use std::io::Read;
+    use std::net::SocketAddr;
+
+    use axum::{
+        body::{to_bytes, Body},
+        extract::ConnectInfo,
+        http::{header, HeaderMap, Method, Request, StatusCode},
+        response::Response,
+        Router,
+    };
+    use personal::{
+        http::{
+            caching::{self, http_date, CachePolicy},
+            handlers,
+            state::AppState,
+        },
+        snapshot::Snapshot,
+        views::ViewCounterStore,
+    };
+    use tower::ServiceExt;
+
+    use crate::common::{database, post, PostBuilder};
+
+    const FIREFOX: &str = "Mozilla/5.0 (X11; Linux x86_64; rv:128.0) Gecko/20100101 Firefox/128.0";
+
+    fn app(state: AppState) -> Router {
+        caching::http_caching(handlers::router()).with_state(state)
+    }
+
+    async fn request(
+        app: &Router,
+        uri: &str,
+        headers: &[(header::HeaderName, &str)],
+    ) -> (StatusCode, HeaderMap, Vec<u8>) {
+        let mut request = Request::get(uri);
+        for (name, value) in headers {
+            request = request.header(name, *value);
+        }
+        let response = app
+            .clone()
+            .oneshot(request.body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        let status = response.status();
+        let headers = response.headers().clone();
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (status, headers, body.to_vec())
+    }
+
+    #[tokio::test]
+    async fn test_unchanged_pages_answer_not_modified() {
+        let dir = tempfile::tempdir().unwrap();
+        let views = ViewCounterStore::load(dir.path().join("views.tsv")).unwrap();
+        let state = AppState::new(database(&[post("first")]), views);
+        let app = app(state.clone());
+
+        for uri in ["/", "/tags/rust", "/feed.atom", "/sitemap.xml"] {
+            let (status, headers, _) = request(&app, uri, &[]).await;
+            assert_eq!(status, StatusCode::OK, "{uri}");
+            let etag = headers[header::ETAG].to_str().unwrap().to_string();
+            let last_modified = headers[header::LAST_MODIFIED].to_str().unwrap().to_string();
+
+            let (status, headers, body) =
+                request(&app, uri, &[(header::IF_NONE_MATCH, &etag)]).await;
+            assert_eq!(status, StatusCode::NOT_MODIFIED, "{uri}");
+            assert_eq!(headers[header::ETAG], etag.as_str());
+            assert!(headers.contains_key(header::CACHE_CONTROL));
+            assert!(body.is_empty());
+
+            let (status, _, _) =
+                request(&app, uri, &[(header::IF_MODIFIED_SINCE, &last_modified)]).await;
+            assert_eq!(status, StatusCode::NOT_MODIFIED, "{uri}");
+
+            // A tag that doesn't match wins over a date that does
+            let (status, _, _) = request(
+                &app,
+                uri,
+                &[
+                    (header::IF_NONE_MATCH, "\"stale\""),
+                    (header::IF_MODIFIED_SINCE, &last_modified),
+                ],
+            )
+            .await;
+            assert_eq!(status, StatusCode::OK, "{uri}");
+        }
+
+        // Publishing different content changes the tag of every page
+        let (_, before, _) = request(&app, "/", &[]).await;
+        state.content.publish(Snapshot::new(database(&[
+            post("first").with_body("<p>Edited</p>")
+        ])));
+        let (status, after, _) = request(
+            &app,
+            "/",
+            &[(
+                header::IF_NONE_MATCH,
+                before[header::ETAG].to_str().unwrap(),
+            )],
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_ne!(after[header::ETAG], before[header::ETAG]);
+
+        // Old clients asking about a date before the content was loaded get the page
+        let (status, _, _) = request(
+            &app,
+            "/",
+            &[(header::IF_MODIFIED_SINCE, "Thu, 01 Jan 2015 00:00:00 GMT")],
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(
+            http_date(state.snapshot().last_modified()),
+            after[header::LAST_MODIFIED].to_str().unwrap()
+        );
+    }
+
+    async fn visit(app: &Router, method: Method, reader: &str, etag: Option<&str>) -> Response {
+        let mut request = Request::builder()
+            .method(method)
+            .uri("/posts/first")
+            .header(header::USER_AGENT, FIREFOX)
+            .extension(ConnectInfo(SocketAddr::new(reader.parse().unwrap(), 5000)));
+        if let Some(etag) = etag {
+            request = request.header(header::IF_NONE_MATCH, etag);
+        }
+        app.clone()
+            .oneshot(request.body(Body::empty()).unwrap())
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_post_pages_count_only_fetched_views() {
+        let dir = tempfile::tempdir().unwrap();
+        let views = ViewCounterStore::load(dir.path().join("views.tsv")).unwrap();
+        let state = AppState::new(database(&[post("first")]), views);
+        let app = app(state.clone());
+        let counts = || {
+            let views = state.views.lock().unwrap();
+            (views.get("first"), views.get_raw("first"))
+        };
+
+        let first = visit(&app, Method::GET, "198.51.100.1", None).await;
+        assert_eq!(first.status(), StatusCode::OK);
+        assert!(!first.headers().contains_key(header::LAST_MODIFIED));
+        assert_eq!(
+            first.headers()[header::CACHE_CONTROL],
+            CachePolicy::Page.header_value()
+        );
+        assert_eq!(counts(), (1, 1));
+
+        // Revalidations and HEAD requests don't fetch the page, so they aren't views
+        let etag = first.headers()[header::ETAG].to_str().unwrap();
+        let revalidated = visit(&app, Method::GET, "198.51.100.2", Some(etag)).await;
+        assert_eq!(revalidated.status(), StatusCode::NOT_MODIFIED);
+        let head = visit(&app, Method::HEAD, "198.51.100.3", None).await;
+        assert_eq!(head.status(), StatusCode::OK);
+        assert_eq!(counts(), (1, 1));
+
+        // New views change the count shown but not the tag, so caches stay valid
+        let second = visit(&app, Method::GET, "198.51.100.4", None).await;
+        assert_eq!(second.status(), StatusCode::OK);
+        assert_eq!(second.headers()[header::ETAG], etag);
+        assert_eq!(counts(), (2, 2));
+
+        let (_, feed, _) = request(&app, "/feed.rss", &[]).await;
+        assert_eq!(
+            feed[header::CACHE_CONTROL],
+            CachePolicy::Feed.header_value()
+        );
+        let (_, missing, _) = request(&app, "/preview/first?token=wrong", &[]).await;
+        assert_eq!(
+            missing[header::CACHE_CONTROL],
+            CachePolicy::NoStore.header_value()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_responses_are_compressed_when_accepted() {
+        let dir = tempfile::tempdir().unwrap();
+        let views = ViewCounterStore::load(dir.path().join("views.tsv")).unwrap();
+        let state = AppState::new(database(&[post("first")]), views);
+        let app = app(state);
+
+        let (_, plain_headers, plain) = request(&app, "/", &[]).await;
+        assert!(!plain_headers.contains_key(header::CONTENT_ENCODING));
+        let plain_etag = plain_headers[header::ETAG].to_str().unwrap();
+
+        let (status, headers, compressed) =
+            request(&app, "/", &[(header::ACCEPT_ENCODING, "gzip")]).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(headers[header::CONTENT_ENCODING], "gzip");
+        assert_eq!(headers[header::VARY], "accept-encoding");
+        let mut decoded = Vec::new();
+        flate2::read::GzDecoder::new(compressed.as_slice())
+            .read_to_end(&mut decoded)
+            .unwrap();
+        assert_eq!(decoded, plain);
+
+        // Each encoding is its own representation, validated separately
+        let etag = headers[header::ETAG].to_str().unwrap();
+        assert_ne!(etag, plain_etag);
+        let (status, _, _) = request(
+            &app,
+            "/",
+            &[
+                (header::ACCEPT_ENCODING, "gzip"),
+                (header::IF_NONE_MATCH, etag),
+            ],
+        )
+        .await;
+        assert_eq!(status, StatusCode::NOT_MODIFIED);
+
+        let (_, headers, _) = request(&app, "/feed.atom", &[(header::ACCEPT_ENCODING, "br")]).await;
+        assert_eq!(headers[header::CONTENT_ENCODING], "br");
+    }
+}
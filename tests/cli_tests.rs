@@ -0,0 +1,205 @@
+mod common;
+
+mod cli_tests {
+    use std::collections::BTreeMap;
+    use std::fs;
+    use std::process::ExitCode;
+
+    use chrono::NaiveDate;
+    use clap::Parser;
+    use personal::{
+        cli::{self, Cli, Command, EXIT_ERROR, EXIT_INVALID_CONTENT},
+        config::Config,
+        data::parse_to_data,
+    };
+
+    use crate::common::{bare_remote, commit_snapshot, post_source};
+
+    fn parse(args: &[&str]) -> Cli {
+        Cli::try_parse_from(std::iter::once("personal").chain(args.iter().copied())).unwrap()
+    }
+
+    #[test]
+    fn test_serve_flags_override_the_configuration() {
+        assert!(parse(&[]).command.is_none());
+
+        let cli = parse(&[
+            "serve",
+            "--bind",
+            "0.0.0.0:8080",
+            "--source",
+            "../blog",
+            "--branch",
+            "drafts",
+            "--config",
+            "site.toml",
+        ]);
+        assert_eq!(cli.config.unwrap().to_str(), Some("site.toml"));
+        let Some(Command::Serve(args)) = cli.command else {
+            panic!("expected serve");
+        };
+        let mut config = Config::default();
+        args.apply(&mut config);
+        assert_eq!(config.server.bind.to_string(), "0.0.0.0:8080");
+        assert_eq!(config.content.source, "../blog");
+        assert_eq!(config.content.branch.as_deref(), Some("drafts"));
+    }
+
+    #[test]
+    fn test_usage_errors_exit_with_the_error_status() {
+        for args in [
+            vec!["personal", "publish"],
+            vec!["personal", "serve", "--bind", "not-an-address"],
+            vec!["personal", "new-post", "Title", "--episode", "2"],
+        ] {
+            let err = Cli::try_parse_from(&args).unwrap_err();
+            assert_eq!(err.exit_code(), EXIT_ERROR as i32, "{args:?}");
+        }
+        let help = Cli::try_parse_from(["personal", "--help"]).unwrap_err();
+        assert_eq!(help.exit_code(), 0);
+        assert!(help.to_string().contains("new-post"));
+    }
+
+    #[tokio::test]
+    async fn test_check_exits_non_zero_for_invalid_posts() {
+        let repo = tempfile::tempdir().unwrap();
+        let posts = repo.path().join("posts");
+        fs::create_dir_all(&posts).unwrap();
+        fs::write(posts.join("valid.md"), post_source("valid", "Valid")).unwrap();
+        let source = repo.path().to_str().unwrap();
+        let config = Config::default();
+
+        let Some(Command::Check(args)) = parse(&["check", source]).command else {
+            panic!("expected check");
+        };
+        assert_eq!(cli::check(&args, &config).await, ExitCode::SUCCESS);
+
+        fs::write(posts.join("broken.md"), "no frontmatter\n").unwrap();
+        assert_eq!(
+            cli::check(&args, &config).await,
+            ExitCode::from(EXIT_INVALID_CONTENT)
+        );
+
+        let missing = repo.path().join("missing");
+        let Some(Command::Check(args)) =
+            parse(&["check", "--json", missing.to_str().unwrap()]).command
+        else {
+            panic!("expected check");
+        };
+        assert_eq!(cli::check(&args, &config).await, ExitCode::from(EXIT_ERROR));
+    }
+
+    #[tokio::test]
+    async fn test_check_clones_the_configured_branch() {
+        let root = tempfile::tempdir().unwrap();
+        let (remote, url) = bare_remote(root.path());
+        let mut files =
+            BTreeMap::from([("posts/valid.md", post_source("valid", "Valid").into_bytes())]);
+        let valid = commit_snapshot(&remote, &files);
+        files.insert("posts/broken.md", b"no frontmatter\n".to_vec());
+        let broken = commit_snapshot(&remote, &files);
+        remote
+            .reference("refs/heads/drafts", broken, false, "drafts")
+            .unwrap();
+        remote
+            .reference("refs/heads/main", valid, true, "main")
+            .unwrap();
+        let url = format!("file://{url}");
+
+        let Some(Command::Check(args)) = parse(&["check", &url]).command else {
+            panic!("expected check");
+        };
+        assert_eq!(
+            cli::check(&args, &Config::default()).await,
+            ExitCode::SUCCESS
+        );
+
+        let mut config = Config::default();
+        config.content.branch = Some("drafts".to_string());
+        assert_eq!(
+            cli::check(&args, &config).await,
+            ExitCode::from(EXIT_INVALID_CONTENT)
+        );
+
+        // The flag wins over the configuration
+        let Some(Command::Check(args)) = parse(&["check", "--branch", "main", &url]).command else {
+            panic!("expected check");
+        };
+        assert_eq!(cli::check(&args, &config).await, ExitCode::SUCCESS);
+
+        let Some(Command::Export(args)) = parse(&["export", "--branch", "drafts", &url]).command
+        else {
+            panic!("expected export");
+        };
+        assert_eq!(args.branch.as_deref(), Some("drafts"));
+    }
+
+    #[tokio::test]
+    async fn test_new_post_writes_a_post_that_parses() {
+        let repo = tempfile::tempdir().unwrap();
+        fs::create_dir_all(repo.path().join("posts")).unwrap();
+        let Some(Command::NewPost(args)) = parse(&[
+            "new-post",
+            "Rust: the \"good\" parts",
+            "--tag",
+            "rust,language",
+            "--tag",
+            "opinion",
+            "--series",
+            "Rust notes",
+            "--episode",
+            "3",
+            "--date",
+            "2026-04-01",
+            "--draft",
+            "--repo",
+            repo.path().to_str().unwrap(),
+        ])
+        .command
+        else {
+            panic!("expected new-post");
+        };
+
+        let path = cli::new_post(&args).unwrap();
+        assert_eq!(path, repo.path().join("posts/rust-the-good-parts.md"));
+        let post = parse_to_data(&path).await.unwrap();
+        assert_eq!(post.markdown.title, "Rust: the \"good\" parts");
+        assert_eq!(post.markdown.tags, vec!["rust", "language", "opinion"]);
+        assert_eq!(post.markdown.series.unwrap().ep, 3);
+        assert_eq!(
+            post.markdown.date,
+            NaiveDate::from_ymd_opt(2026, 4, 1).unwrap()
+        );
+        assert!(post.markdown.draft);
+
+        assert!(cli::new_post(&args).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_export_renders_the_index_and_posts() {
+        let repo = tempfile::tempdir().unwrap();
+        let posts = repo.path().join("posts");
+        fs::create_dir_all(&posts).unwrap();
+        fs::write(posts.join("first.md"), post_source("first", "First post")).unwrap();
+        let out = tempfile::tempdir().unwrap();
+        let mut config = Config::default();
+        config.views.file = Some(out.path().join("views.tsv"));
+
+        let Some(Command::Export(args)) = parse(&[
+            "export",
+            "--out",
+            out.path().join("site").to_str().unwrap(),
+            repo.path().to_str().unwrap(),
+        ])
+        .command
+        else {
+            panic!("expected export");
+        };
+        assert_eq!(cli::export(&args, &config).await, ExitCode::SUCCESS);
+
+        let index = fs::read_to_string(out.path().join("site/index.html")).unwrap();
+        assert!(index.contains("First post"));
+        let post = fs::read_to_string(out.path().join("site/posts/first/index.html")).unwrap();
+        assert!(post.contains("Body of first"));
+    }
+}
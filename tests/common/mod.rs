@@ -0,0 +1,162 @@
+//! Helpers shared by the integration tests: posts, databases, requests and git remotes
+#![allow(dead_code)]
+
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use axum::{
+    body::{to_bytes, Body},
+    http::{HeaderMap, Request, StatusCode},
+    Router,
+};
+use chrono::NaiveDate;
+use git2::{Oid, Repository, Signature};
+use personal::{
+    data::{Markdown, Post, Series},
+    db::{Database, InMemDatabase},
+};
+use tower::ServiceExt;
+
+/// A live post tagged `rust` from 2026-01-01, with a title, description and body naming `slug`
+///
+/// Change the rest with the [`PostBuilder`] methods, e.g.
+/// `post("first").with_tags(&[]).with_date(2026, 3, 1)`.
+pub fn post(slug: &str) -> Post {
+    Post {
+        data: format!("<p>Body of {slug}</p>"),
+        markdown: Markdown {
+            title: format!("Title of {slug}"),
+            description: format!("Description of {slug}"),
+            slug: slug.to_string(),
+            tags: vec!["rust".to_string()],
+            date: NaiveDate::from_ymd_opt(2026, 1, 1).unwrap(),
+            series: None,
+            draft: false,
+        },
+    }
+}
+
+pub trait PostBuilder {
+    fn with_title(self, title: &str) -> Self;
+    fn with_description(self, description: &str) -> Self;
+    /// Rendered HTML of the post
+    fn with_body(self, body: &str) -> Self;
+    fn with_tags(self, tags: &[&str]) -> Self;
+    fn with_series(self, title: &str, ep: u8) -> Self;
+    fn with_date(self, year: i32, month: u32, day: u32) -> Self;
+    fn with_draft(self, draft: bool) -> Self;
+}
+
+impl PostBuilder for Post {
+    fn with_title(mut self, title: &str) -> Self {
+        self.markdown.title = title.to_string();
+        self
+    }
+
+    fn with_description(mut self, description: &str) -> Self {
+        self.markdown.description = description.to_string();
+        self
+    }
+
+    fn with_body(mut self, body: &str) -> Self {
+        self.data = body.to_string();
+        self
+    }
+
+    fn with_tags(mut self, tags: &[&str]) -> Self {
+        self.markdown.tags = tags.iter().map(|tag| tag.to_string()).collect();
+        self
+    }
+
+    fn with_series(mut self, title: &str, ep: u8) -> Self {
+        self.markdown.series = Some(Series {
+            title: title.to_string(),
+            ep,
+        });
+        self
+    }
+
+    fn with_date(mut self, year: i32, month: u32, day: u32) -> Self {
+        self.markdown.date = NaiveDate::from_ymd_opt(year, month, day).unwrap();
+        self
+    }
+
+    fn with_draft(mut self, draft: bool) -> Self {
+        self.markdown.draft = draft;
+        self
+    }
+}
+
+/// An in-memory database holding `posts`, on the real clock until given another
+pub fn database(posts: &[Post]) -> InMemDatabase {
+    let mut db = InMemDatabase::new();
+    for post in posts {
+        db.insert_parsed_to_database(post.clone()).unwrap();
+    }
+    db
+}
+
+/// Status, headers and body of `app`'s response to `request`
+pub async fn send(app: &Router, request: Request<Body>) -> (StatusCode, HeaderMap, String) {
+    let response = app.clone().oneshot(request).await.unwrap();
+    let status = response.status();
+    let headers = response.headers().clone();
+    let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    (status, headers, String::from_utf8_lossy(&body).into_owned())
+}
+
+/// Status, headers and body of a plain GET of `uri`
+pub async fn fetch(app: &Router, uri: &str) -> (StatusCode, HeaderMap, String) {
+    send(app, Request::get(uri).body(Body::empty()).unwrap()).await
+}
+
+pub fn post_source(slug: &str, title: &str) -> String {
+    format!(
+        "---\ntitle: {title}\ndescription: Description of {slug}\nslug: {slug}\ntags: [\"rust\"]\ndate: 2026-02-07\n---\nBody of {slug}\n"
+    )
+}
+
+/// Commit the full `files` snapshot (`dir/name` -> contents) onto `main` of a bare repository
+pub fn commit_snapshot(repo: &Repository, files: &BTreeMap<&str, Vec<u8>>) -> Oid {
+    let mut dirs: BTreeMap<&str, Vec<(&str, Oid)>> = BTreeMap::new();
+    for (path, contents) in files {
+        let (dir, name) = path.split_once('/').expect("files live in a directory");
+        let blob = repo.blob(contents).unwrap();
+        dirs.entry(dir).or_default().push((name, blob));
+    }
+
+    let mut root = repo.treebuilder(None).unwrap();
+    for (dir, entries) in dirs {
+        let mut builder = repo.treebuilder(None).unwrap();
+        for (name, blob) in entries {
+            builder.insert(name, blob, 0o100644).unwrap();
+        }
+        root.insert(dir, builder.write().unwrap(), 0o040000)
+            .unwrap();
+    }
+    let tree = repo.find_tree(root.write().unwrap()).unwrap();
+
+    let signature = Signature::now("Test", "test@example.com").unwrap();
+    let parent = repo
+        .refname_to_id("refs/heads/main")
+        .ok()
+        .map(|oid| repo.find_commit(oid).unwrap());
+    let parents: Vec<&git2::Commit> = parent.iter().collect();
+    repo.commit(
+        Some("refs/heads/main"),
+        &signature,
+        &signature,
+        "update content",
+        &tree,
+        &parents,
+    )
+    .unwrap()
+}
+
+pub fn bare_remote(root: &Path) -> (Repository, String) {
+    let path = root.join("remote.git");
+    let repo = Repository::init_bare(&path).unwrap();
+    repo.set_head("refs/heads/main").unwrap();
+    let url = path.to_str().unwrap().to_string();
+    (repo, url)
+}
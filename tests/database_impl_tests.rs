@@ -1,26 +1,243 @@
+mod common;
+
 mod database_tests {
     use std::path::Path;
 
+    use chrono::NaiveDate;
     use personal::{
+        data::Post,
         db::*,
         error::ApplicationError,
         repo_utils::{get_posts_from_repository, Repository},
+        sqlite::SqliteDatabase,
     };
 
+    use crate::common::{post, PostBuilder};
+
+    fn fixed_clock() -> NaiveDate {
+        NaiveDate::from_ymd_opt(2026, 12, 31).unwrap()
+    }
+
+    fn slugs(posts: &[Post]) -> Vec<&str> {
+        posts.iter().map(|p| p.markdown.slug.as_str()).collect()
+    }
+
+    /// Every `Database` implementation, empty and on the same clock
+    fn backends() -> Vec<Box<dyn Database>> {
+        vec![
+            Box::new(InMemDatabase::new().with_clock(fixed_clock)),
+            Box::new(
+                SqliteDatabase::open_in_memory()
+                    .unwrap()
+                    .with_clock(fixed_clock),
+            ),
+        ]
+    }
+
+    /// 300 posts in March, spread over its days and all tagged `rust`, in every backend
+    fn crowded_databases() -> Vec<Box<dyn Database>> {
+        backends().into_iter().map(crowd).collect()
+    }
+
+    fn crowd(mut db: Box<dyn Database>) -> Box<dyn Database> {
+        for i in 0..300u32 {
+            let tags: &[&str] = if i % 2 == 0 {
+                &["rust", "even"]
+            } else {
+                &["rust"]
+            };
+            db.insert_parsed_to_database(post(&format!("march-{i:03}")).with_tags(tags).with_date(
+                2026,
+                3,
+                i % 31 + 1,
+            ))
+            .unwrap();
+        }
+        db.insert_parsed_to_database(post("april").with_date(2026, 4, 2))
+            .unwrap();
+        db
+    }
+
     #[tokio::test]
     async fn test_insert_to_in_mem_db() -> Result<(), ApplicationError> {
-        let mut in_mem_db = InMemDatabase::new();
         let repo: Repository = Repository::try_from(Path::new("./tests/data"))?;
         let posts = get_posts_from_repository(repo).await?;
 
-        for post in posts.clone() {
-            in_mem_db.insert_parsed_to_database(post)?
+        for mut db in backends() {
+            for post in posts.clone() {
+                db.insert_parsed_to_database(post)?
+            }
+
+            assert_eq!(
+                db.get_by_slug("example-title".to_owned()),
+                Some(posts.first().unwrap().clone())
+            );
         }
+        Ok(())
+    }
+
+    #[test]
+    fn test_every_post_of_a_month_is_returned() {
+        for db in crowded_databases() {
+            let march = db.get_by_year_month(2026, Some(3));
+            assert_eq!(march.len(), 300);
+            assert!(march
+                .windows(2)
+                .all(|pair| pair[0].markdown.date >= pair[1].markdown.date));
+            assert_eq!(db.get_by_year_month(2026, None).len(), 301);
+            assert_eq!(slugs(&db.get_by_year_month(2026, Some(4))), vec!["april"]);
+            assert!(db.get_by_year_month(2026, Some(12)).is_empty());
+            assert!(db.get_by_year_month(2026, Some(13)).is_empty());
+        }
+    }
+
+    #[test]
+    fn test_counts_do_not_overflow() {
+        for db in crowded_databases() {
+            let tags = db.get_all_tags_with_count();
+            assert_eq!(
+                tags,
+                vec![("even".to_string(), 150), ("rust".to_string(), 301)]
+            );
+            assert_eq!(
+                db.get_all_dates_with_count(),
+                vec![((2026, 4), 1), ((2026, 3), 300)]
+            );
+        }
+    }
 
+    #[test]
+    fn test_tag_listing_is_newest_first() {
+        for db in crowded_databases() {
+            let posts = db.get_by_tag("rust".to_string());
+            assert_eq!(posts.len(), 301);
+            assert_eq!(posts[0].markdown.slug, "april");
+            assert!(posts.windows(2).all(|pair| (
+                std::cmp::Reverse(pair[0].markdown.date),
+                &pair[0].markdown.slug
+            ) < (
+                std::cmp::Reverse(pair[1].markdown.date),
+                &pair[1].markdown.slug
+            )));
+            assert_eq!(slugs(&db.get_last_n_posts(2)), vec!["april", "march-030"]);
+        }
+    }
+
+    #[test]
+    fn test_series_follow_episode_order() {
+        for mut db in backends() {
+            for (slug, day, ep) in [("part-3", 1, 3), ("part-1", 9, 1), ("part-2", 5, 2)] {
+                db.insert_parsed_to_database(
+                    post(slug)
+                        .with_tags(&[])
+                        .with_date(2026, 6, day)
+                        .with_series("Saga", ep),
+                )
+                .unwrap();
+            }
+            db.insert_parsed_to_database(
+                post("other")
+                    .with_tags(&[])
+                    .with_date(2026, 6, 2)
+                    .with_series("Other", 1),
+            )
+            .unwrap();
+            assert_eq!(
+                slugs(&db.get_by_series("Saga".to_string())),
+                vec!["part-1", "part-2", "part-3"]
+            );
+            assert_eq!(slugs(&db.get_by_series("Other".to_string())), vec!["other"]);
+            assert!(db.get_by_series("Missing".to_string()).is_empty());
+        }
+    }
+
+    #[test]
+    fn test_duplicate_tags_and_slugs_are_indexed_once() {
+        for mut db in backends() {
+            db.insert_parsed_to_database(post("twice").with_tags(&["a", "a"]))
+                .unwrap();
+            db.insert_parsed_to_database(post("twice").with_tags(&["a"]).with_date(2026, 2, 1))
+                .unwrap();
+            assert_eq!(slugs(&db.get_by_tag("a".to_string())), vec!["twice"]);
+            assert_eq!(db.get_all_dates_with_count(), vec![((2026, 1), 1)]);
+        }
+    }
+
+    #[test]
+    fn test_drafts_and_scheduled_posts_stay_unpublished() {
+        for mut db in backends() {
+            db.insert_parsed_to_database(post("draft").with_draft(true))
+                .unwrap();
+            db.insert_parsed_to_database(post("scheduled").with_date(2027, 1, 1))
+                .unwrap();
+            db.insert_parsed_to_database(post("live").with_date(2026, 1, 2))
+                .unwrap();
+
+            assert_eq!(slugs(&db.get_all_posts()), vec!["live"]);
+            assert!(db.get_by_slug("draft".to_string()).is_none());
+            assert_eq!(
+                slugs(&db.get_unpublished_posts()),
+                vec!["draft", "scheduled"]
+            );
+            assert!(db
+                .get_unpublished_by_slug("scheduled".to_string())
+                .is_some());
+            assert_eq!(db.get_all_tags_with_count(), vec![("rust".to_string(), 1)]);
+        }
+    }
+
+    #[test]
+    fn test_keyword_search_ranks_titles_first() {
+        for mut db in backends() {
+            let in_title = post("in-title")
+                .with_title("Borrow checker basics")
+                .with_date(2026, 5, 1);
+            let in_body = post("in-body")
+                .with_body("<p>Fighting the borrow checker again.</p>")
+                .with_date(2026, 5, 2);
+            db.insert_parsed_to_database(in_title).unwrap();
+            db.insert_parsed_to_database(in_body).unwrap();
+            db.insert_parsed_to_database(post("unrelated").with_date(2026, 5, 3))
+                .unwrap();
+
+            assert_eq!(
+                slugs(&db.get_by_keyword("borrow checker".to_string())),
+                vec!["in-title", "in-body"]
+            );
+            assert_eq!(
+                slugs(&db.get_by_keyword("\"checker again\"".to_string())),
+                vec!["in-body"]
+            );
+            assert_eq!(
+                slugs(&db.get_by_keyword("fight*".to_string())),
+                vec!["in-body"]
+            );
+            assert!(db.get_by_keyword("   ".to_string()).is_empty());
+        }
+    }
+
+    #[test]
+    fn test_sqlite_content_survives_reopening() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("content.sqlite");
+        let mut memory = InMemDatabase::new().with_clock(fixed_clock);
+        for post in crowd(backends().remove(0)).get_all_posts() {
+            memory.insert_parsed_to_database(post).unwrap();
+        }
+
+        let mut store = SqliteDatabase::open(&path).unwrap();
+        store.replace_content(&memory, Some("abc123")).unwrap();
+        store.replace_content(&memory, Some("def456")).unwrap();
+        drop(store);
+
+        let store = SqliteDatabase::open(&path).unwrap().with_clock(fixed_clock);
+        assert_eq!(store.post_count().unwrap(), 301);
+        assert_eq!(store.commit().unwrap().as_deref(), Some("def456"));
+        let loaded = store.load_in_memory().unwrap();
+        assert_eq!(loaded.get_all_posts(), memory.get_all_posts());
         assert_eq!(
-            in_mem_db.get_by_slug("example-title".to_owned()),
-            Some(posts.iter().next().unwrap().clone())
+            loaded.get_all_tags_with_count(),
+            memory.get_all_tags_with_count()
         );
-        Ok(())
     }
 }
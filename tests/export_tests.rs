@@ -0,0 +1,232 @@
+mod common;
+
+mod export_tests {
+    use std::collections::HashMap;
+    use std::fs;
+    use std::path::Path;
+    use std::sync::Arc;
+
+    use axum::{
+        body::Body,
+        http::{Request, StatusCode},
+    };
+    use personal::{
+        config::SiteConfig,
+        data::Post,
+        db::InMemDatabase,
+        export::Export,
+        http::{assets::StaticAssets, handlers, state::AppState},
+        resources::fingerprinted_path,
+        snapshot::Snapshot,
+        views::ViewCounterStore,
+    };
+    use tower::ServiceExt;
+
+    use crate::common::{self, post, PostBuilder};
+
+    /// A post linking the `diagram.png` resource
+    fn illustrated(slug: &str) -> Post {
+        post(slug).with_body(&format!(
+            "<p>Body of {slug}</p><img src=\"/static/misc/diagram.png\">"
+        ))
+    }
+
+    fn database() -> InMemDatabase {
+        common::database(&[
+            illustrated("first").with_series("Rust 101", 1),
+            illustrated("second")
+                .with_tags(&["rust", "c++"])
+                .with_date(2026, 2, 1),
+        ])
+    }
+
+    /// Relative paths of every file under `dir`
+    fn files(dir: &Path) -> Vec<String> {
+        let mut found = Vec::new();
+        for entry in fs::read_dir(dir).unwrap() {
+            let path = entry.unwrap().path();
+            if path.is_dir() {
+                let prefix = path.file_name().unwrap().to_str().unwrap().to_string();
+                found.extend(files(&path).into_iter().map(|f| format!("{prefix}/{f}")));
+            } else {
+                found.push(path.file_name().unwrap().to_str().unwrap().to_string());
+            }
+        }
+        found.sort();
+        found
+    }
+
+    #[tokio::test]
+    async fn test_export_writes_every_page_at_its_server_url() {
+        let out = tempfile::tempdir().unwrap();
+        let site = Export::new(out.path().join("site"));
+        let summary = site
+            .run(
+                &Snapshot::new(database()),
+                Arc::new(SiteConfig::default()),
+                &HashMap::from([("first".to_string(), 7)]),
+            )
+            .unwrap();
+
+        let all = files(&out.path().join("site"));
+        let (assets, written): (Vec<String>, Vec<String>) = all
+            .into_iter()
+            .partition(|path| path.starts_with("static/"));
+        assert!(assets.contains(&"static/style.css".to_string()));
+        assert_eq!(
+            written,
+            vec![
+                "archive/2026/1/index.html",
+                "archive/2026/2/index.html",
+                "archive/2026/index.html",
+                "feed.atom",
+                "feed.json",
+                "feed.rss",
+                "index.html",
+                "posts/first/index.html",
+                "posts/second/index.html",
+                "robots.txt",
+                "series/Rust 101/index.html",
+                "sitemap.xml",
+                "tags/c++/index.html",
+                "tags/rust/index.html",
+            ]
+        );
+        assert_eq!(summary.pages, written.len());
+
+        let read = |path: &str| fs::read_to_string(out.path().join("site").join(path)).unwrap();
+        // Pages link the bundled stylesheet by fingerprint, and that file is written
+        let index = read("index.html");
+        let stylesheet = assets
+            .iter()
+            .find(|path| index.contains(&format!("href=\"/{path}\"")) && path.ends_with(".css"))
+            .unwrap();
+        assert_ne!(stylesheet, "static/style.css");
+        assert!(read("posts/first/index.html").contains("href=\"/tags/rust\""));
+        assert!(read("posts/first/index.html").contains(">7</span>"));
+        assert!(read("index.html").contains("href=\"/tags/c%2B%2B\""));
+        assert!(read("index.html").contains("href=\"/archive/2026/2\""));
+        let rust = read("tags/rust/index.html");
+        assert!(rust.contains("Title of first") && rust.contains("Title of second"));
+        assert!(!rust.contains("Sort by"));
+
+        // Every exported page is served at the same address by the live server
+        let dir = tempfile::tempdir().unwrap();
+        let state = AppState::new(
+            database(),
+            ViewCounterStore::load(dir.path().join("views.tsv")).unwrap(),
+        );
+        let app = handlers::router().with_state(state);
+        for path in written {
+            let url = format!("/{}", path.trim_end_matches("index.html"))
+                .trim_end_matches('/')
+                .replace(' ', "%20")
+                .replace('+', "%2B");
+            let url = if url.is_empty() { "/".to_string() } else { url };
+            let response = app
+                .clone()
+                .oneshot(Request::get(&url).body(Body::empty()).unwrap())
+                .await
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::OK, "{url}");
+        }
+    }
+
+    #[test]
+    fn test_pages_link_the_fingerprints_of_the_exported_assets() {
+        let assets = tempfile::tempdir().unwrap();
+        fs::create_dir_all(assets.path().join("static")).unwrap();
+        fs::write(assets.path().join("static/style.css"), "body {}").unwrap();
+        fs::create_dir_all(assets.path().join("resources")).unwrap();
+        fs::write(assets.path().join("resources/diagram.png"), [1, 2, 3]).unwrap();
+
+        let out = tempfile::tempdir().unwrap();
+        let summary = Export::new(out.path())
+            .with_assets(assets.path().join("static"), "/static")
+            .with_assets(assets.path().join("resources"), "/static/misc")
+            .with_assets(assets.path().join("missing"), "/static")
+            .run(
+                &Snapshot::new(database()),
+                Arc::new(SiteConfig::default()),
+                &HashMap::new(),
+            )
+            .unwrap();
+        // The bundled files, with style.css replaced, and the resource
+        assert_eq!(summary.assets, StaticAssets::default().paths().len() + 1);
+
+        let written = files(out.path());
+        let hashed_css = written
+            .iter()
+            .find(|path| path.starts_with("static/style.") && path.as_str() != "static/style.css")
+            .unwrap();
+        let hashed_png = written
+            .iter()
+            .find(|path| {
+                path.starts_with("static/misc/diagram.")
+                    && path.as_str() != "static/misc/diagram.png"
+            })
+            .unwrap();
+        assert!(written.contains(&"static/style.css".to_string()));
+        assert!(written.contains(&"static/favicon.png".to_string()));
+        assert_eq!(
+            fs::read(out.path().join("static/style.css")).unwrap(),
+            b"body {}"
+        );
+        assert_eq!(
+            fs::read(out.path().join(hashed_png)).unwrap(),
+            vec![1, 2, 3]
+        );
+
+        let page = fs::read_to_string(out.path().join("posts/first/index.html")).unwrap();
+        assert!(page.contains(&format!("href=\"/{hashed_css}\"")));
+        assert_eq!(
+            hashed_css,
+            &fingerprinted_path("static/style.css", b"body {}")
+        );
+        assert!(page.contains(&format!("src=\"/{hashed_png}\"")));
+        assert!(!page.contains("/static/style.css\""));
+        let feed = fs::read_to_string(out.path().join("feed.atom")).unwrap();
+        assert!(feed.contains(hashed_png.as_str()));
+    }
+
+    #[test]
+    fn test_pages_link_plain_names_without_fingerprinting() {
+        let assets = tempfile::tempdir().unwrap();
+        fs::create_dir_all(assets.path().join("resources")).unwrap();
+        fs::write(assets.path().join("resources/diagram.png"), [1, 2, 3]).unwrap();
+        let hashed_png = fingerprinted_path("/static/misc/diagram.png", &[1, 2, 3]);
+        let db =
+            common::database(&[post("first").with_body(&format!("<img src=\"{hashed_png}\">"))]);
+
+        let out = tempfile::tempdir().unwrap();
+        let summary = Export::new(out.path())
+            .with_assets(assets.path().join("resources"), "/static/misc")
+            .with_fingerprinting(false)
+            .run(
+                &Snapshot::new(db),
+                Arc::new(SiteConfig::default()),
+                &HashMap::new(),
+            )
+            .unwrap();
+
+        // One copy of each asset, under the name pages link
+        let written: Vec<String> = files(out.path())
+            .into_iter()
+            .filter(|path| path.starts_with("static/"))
+            .collect();
+        assert_eq!(written.len(), summary.assets);
+        let expected: Vec<String> = StaticAssets::default()
+            .paths()
+            .into_iter()
+            .map(|path| format!("static/{path}"))
+            .chain(["static/misc/diagram.png".to_string()])
+            .collect();
+        assert_eq!(written.len(), expected.len());
+        assert!(expected.iter().all(|path| written.contains(path)));
+
+        let page = fs::read_to_string(out.path().join("posts/first/index.html")).unwrap();
+        assert!(page.contains("href=\"/static/style.css\""));
+        assert!(page.contains("src=\"/static/misc/diagram.png\""));
+        assert!(!page.contains(&hashed_png));
+    }
+}
@@ -0,0 +1,97 @@
+mod common;
+
+mod ingestion_tests {
+    use std::collections::BTreeMap;
+    use std::fs;
+    use std::path::PathBuf;
+
+    use personal::{
+        diagnostics::Diagnostic,
+        error::ApplicationError,
+        repo_utils::{ingest_repository, Repository, RepositorySync},
+    };
+
+    use crate::common::{bare_remote, commit_snapshot, post_source};
+
+    #[tokio::test]
+    async fn test_ingestion_skips_broken_posts() -> Result<(), ApplicationError> {
+        let root = tempfile::tempdir()?;
+        let posts = root.path().join("posts");
+        fs::create_dir_all(&posts)?;
+        fs::write(posts.join("a_valid.md"), post_source("valid", "Valid"))?;
+        fs::write(
+            posts.join("b_bad_date.md"),
+            "---\ntitle: Bad date\ndescription: d\nslug: bad-date\ntags: []\ndate: yesterday\n---\nBody\n",
+        )?;
+        fs::write(posts.join("c_no_frontmatter.md"), "Just some text\n")?;
+        fs::write(posts.join("d_duplicate.md"), post_source("valid", "Again"))?;
+        let long_description = "x".repeat(201);
+        fs::write(
+            posts.join("e_long.md"),
+            format!("---\ntitle: Long\nslug: long\ndescription: {long_description}\ntags: []\ndate: 2026-02-07\n---\nBody\n"),
+        )?;
+
+        let repo = Repository::try_from(root.path())?;
+        let ingestion = ingest_repository(&repo).await?;
+
+        assert_eq!(ingestion.posts.len(), 1);
+        assert_eq!(ingestion.posts[0].markdown.title, "Valid");
+
+        let by_path: BTreeMap<PathBuf, Diagnostic> = ingestion
+            .diagnostics
+            .into_iter()
+            .map(|diagnostic| (diagnostic.path.clone(), diagnostic))
+            .collect();
+        assert_eq!(by_path.len(), 4);
+
+        let bad_date = &by_path[&PathBuf::from("posts/b_bad_date.md")];
+        assert_eq!((bad_date.line, bad_date.column), (Some(6), Some(7)));
+        assert!(bad_date.reason.contains("date"));
+        assert!(!bad_date.reason.contains("at line"));
+
+        let no_frontmatter = &by_path[&PathBuf::from("posts/c_no_frontmatter.md")];
+        assert_eq!(no_frontmatter.line, None);
+
+        let duplicate = &by_path[&PathBuf::from("posts/d_duplicate.md")];
+        assert!(duplicate.reason.contains("posts/a_valid.md"));
+
+        let long = &by_path[&PathBuf::from("posts/e_long.md")];
+        assert_eq!(long.line, Some(4));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_sync_keeps_valid_posts_when_one_breaks() -> Result<(), ApplicationError> {
+        let root = tempfile::tempdir()?;
+        let (remote, url) = bare_remote(root.path());
+        let mut files: BTreeMap<&str, Vec<u8>> = BTreeMap::new();
+        files.insert("posts/first.md", post_source("first", "First").into_bytes());
+        files.insert("posts/broken.md", b"---\ntitle: [\n---\n".to_vec());
+        commit_snapshot(&remote, &files);
+
+        let mut sync = RepositorySync::new(url, root.path().join("mirror"))
+            .with_resources_dir(root.path().join("misc"));
+        sync.sync().await?;
+        assert_eq!(sync.posts().len(), 1);
+        let diagnostics = sync.diagnostics();
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].path, PathBuf::from("posts/broken.md"));
+
+        files.insert(
+            "posts/broken.md",
+            post_source("fixed", "Fixed").into_bytes(),
+        );
+        files.insert("posts/first.md", b"no frontmatter".to_vec());
+        commit_snapshot(&remote, &files);
+
+        let outcome = sync.sync().await?;
+        assert!(!outcome.full_reload);
+        let posts = sync.posts();
+        assert_eq!(posts.len(), 1);
+        assert_eq!(posts[0].markdown.slug, "fixed");
+        let diagnostics = sync.diagnostics();
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].path, PathBuf::from("posts/first.md"));
+        Ok(())
+    }
+}
@@ -0,0 +1,64 @@
+mod common;
+
+mod page_cache_tests {
+    use axum::{http::StatusCode, Router};
+    use personal::{
+        db::InMemDatabase,
+        http::{handlers, page_cache::VIEW_COUNT_SLOT, state::AppState},
+        snapshot::Snapshot,
+        views::ViewCounterStore,
+    };
+
+    use crate::common::{self, post, PostBuilder};
+
+    fn database(body: &str) -> InMemDatabase {
+        common::database(&[post("first")
+            .with_tags(&["notes"])
+            .with_body(&format!("<p>{body}</p>"))])
+    }
+
+    async fn fetch(app: &Router, uri: &str) -> String {
+        let (status, _, body) = common::fetch(app, uri).await;
+        assert_eq!(status, StatusCode::OK, "{uri}");
+        body
+    }
+
+    fn shown_views(html: &str) -> u64 {
+        let (_, rest) = html.split_once("<span>Views</span>").unwrap();
+        let (_, rest) = rest.split_once("\">").unwrap();
+        rest.split_once('<').unwrap().0.parse().unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_cached_pages_follow_views_and_reloads() {
+        let dir = tempfile::tempdir().unwrap();
+        let views = ViewCounterStore::load(dir.path().join("views.tsv")).unwrap();
+        let state = AppState::new(database("Hello"), views);
+        let app = handlers::router().with_state(state.clone());
+
+        let first = fetch(&app, "/posts/first").await;
+        assert!(!first.contains(VIEW_COUNT_SLOT));
+        fetch(&app, "/").await;
+        fetch(&app, "/tags/notes").await;
+        assert_eq!(state.pages.len(), 3);
+
+        // The cached post page still shows the live count
+        state.views.lock().unwrap().record("first", true).unwrap();
+        let second = fetch(&app, "/posts/first").await;
+        assert_eq!(shown_views(&second), shown_views(&first) + 1);
+        assert_eq!(state.pages.len(), 3);
+
+        // Unknown parameters share the entry of the page they don't change
+        fetch(&app, "/tags/notes?utm_source=feed").await;
+        assert_eq!(state.pages.len(), 3);
+
+        // Popularity order changes with every view, so those pages aren't kept
+        fetch(&app, "/?sort=views").await;
+        assert_eq!(state.pages.len(), 3);
+
+        state.content.publish(Snapshot::new(database("Edited")));
+        let edited = fetch(&app, "/posts/first").await;
+        assert!(edited.contains("<p>Edited</p>"));
+        assert_eq!(state.pages.len(), 1);
+    }
+}
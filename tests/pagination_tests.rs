@@ -0,0 +1,164 @@
+mod common;
+
+mod pagination_tests {
+    use std::collections::HashMap;
+
+    use axum::{
+        http::{header, StatusCode},
+        routing::get,
+        Router,
+    };
+    use chrono::NaiveDate;
+    use personal::{
+        data::Post,
+        db::{Database, InMemDatabase, Listing},
+        http::{api, handlers, state::AppState},
+        pagination::{PageQuery, PageRequest, SortOrder},
+        views::ViewCounterStore,
+    };
+
+    use crate::common::{self, fetch, post, PostBuilder};
+
+    fn fixed_clock() -> NaiveDate {
+        NaiveDate::from_ymd_opt(2026, 6, 1).unwrap()
+    }
+
+    /// Posts `post-01` to `post-25`, one per day of May, the first five in a series
+    /// published in reverse episode order
+    fn database() -> InMemDatabase {
+        let posts: Vec<Post> = (1..=25u32)
+            .map(|day| {
+                let post = post(&format!("post-{day:02}"))
+                    .with_title(&format!("Title {}", (b'a' + (day as u8 * 7) % 26) as char))
+                    .with_body(&format!("<p>Post number {day}</p>"))
+                    .with_date(2026, 5, day);
+                if day <= 5 {
+                    post.with_series("Deep Dive", (6 - day) as u8)
+                } else {
+                    post
+                }
+            })
+            .collect();
+        common::database(&posts).with_clock(fixed_clock)
+    }
+
+    fn slugs(posts: &[Post]) -> Vec<&str> {
+        posts.iter().map(|p| p.markdown.slug.as_str()).collect()
+    }
+
+    fn request(page: usize, per_page: usize, sort: SortOrder) -> PageRequest {
+        PageRequest {
+            page,
+            per_page,
+            sort,
+        }
+    }
+
+    #[test]
+    fn test_pages_cover_the_archive_once() {
+        let db = database();
+        let no_views = HashMap::new();
+        let mut seen = Vec::new();
+        for number in 1..=3 {
+            let page = db.get_page(
+                &Listing::All,
+                &request(number, 10, SortOrder::Newest),
+                &no_views,
+            );
+            assert_eq!(page.total, 25);
+            assert_eq!(page.total_pages(), 3);
+            assert_eq!(page.has_next(), number < 3);
+            seen.extend(page.items.into_iter().map(|p| p.markdown.slug));
+        }
+        let mut expected: Vec<String> = (1..=25).rev().map(|d| format!("post-{d:02}")).collect();
+        assert_eq!(seen, expected);
+
+        let past_the_end =
+            db.get_page(&Listing::All, &request(4, 10, SortOrder::Oldest), &no_views);
+        assert!(past_the_end.items.is_empty());
+        expected.reverse();
+        let oldest = db.get_page(&Listing::All, &request(1, 3, SortOrder::Oldest), &no_views);
+        assert_eq!(slugs(&oldest.items), expected[..3].to_vec());
+    }
+
+    #[test]
+    fn test_sort_orders() {
+        let db = database();
+        let series = Listing::Series("Deep Dive".to_string());
+        let default = PageQuery::default().to_request(series.sorts());
+        assert_eq!(default.sort, SortOrder::Episode);
+        let page = db.get_page(&series, &default, &HashMap::new());
+        assert_eq!(
+            slugs(&page.items),
+            vec!["post-05", "post-04", "post-03", "post-02", "post-01"]
+        );
+
+        let title = db.get_page(
+            &Listing::Tag("rust".to_string()),
+            &request(1, 25, SortOrder::Title),
+            &HashMap::new(),
+        );
+        let titles: Vec<&str> = title
+            .items
+            .iter()
+            .map(|p| p.markdown.title.as_str())
+            .collect();
+        let mut sorted = titles.clone();
+        sorted.sort();
+        assert_eq!(titles, sorted);
+
+        let views = HashMap::from([("post-07".to_string(), 40), ("post-02".to_string(), 9)]);
+        let popular = db.get_page(&Listing::All, &request(1, 3, SortOrder::Views), &views);
+        assert_eq!(slugs(&popular.items), vec!["post-07", "post-02", "post-25"]);
+    }
+
+    fn app() -> (Router, tempfile::TempDir) {
+        let views_dir = tempfile::tempdir().unwrap();
+        let views = ViewCounterStore::load(views_dir.path().join("views.tsv")).unwrap();
+        let app = Router::new()
+            .route("/", get(handlers::html_index))
+            .route("/posts/by-tag", get(handlers::html_get_posts_by_tag))
+            .merge(api::router())
+            .with_state(AppState::new(database(), views));
+        (app, views_dir)
+    }
+
+    #[tokio::test]
+    async fn test_html_listings_link_to_neighbouring_pages() {
+        let (app, _views_dir) = app();
+        let (status, _, html) = fetch(&app, "/").await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(html.contains(r#"<link rel="next" href="/?page=2">"#));
+        assert!(!html.contains(r#"rel="prev""#));
+        assert!(html.contains("Page 1 of 3"));
+
+        let (_, _, html) = fetch(&app, "/?page=3").await;
+        assert!(html.contains(r#"<link rel="prev" href="/?page=2">"#));
+        assert!(html.contains("post-01"));
+        assert!(!html.contains(r#"rel="next""#));
+
+        let (_, _, html) = fetch(&app, "/posts/by-tag?tag=rust&page=2&sort=oldest").await;
+        assert!(html.contains("href=\"/posts/by-tag?tag=rust&amp;page=3&amp;sort=oldest\""));
+        assert!(html.contains("/posts/post-11"));
+    }
+
+    #[tokio::test]
+    async fn test_api_pagination_headers() {
+        let (app, _views_dir) = app();
+        let (status, headers, body) = fetch(&app, "/api/v1/posts?per_page=5&page=2").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(headers["x-total-count"], "25");
+        assert_eq!(
+            headers[header::LINK],
+            r#"</api/v1/posts?per_page=5>; rel="prev", </api/v1/posts?page=3&per_page=5>; rel="next""#
+        );
+        let posts: Vec<Post> = serde_json::from_str(&body).unwrap();
+        assert_eq!(
+            slugs(&posts),
+            vec!["post-20", "post-19", "post-18", "post-17", "post-16"]
+        );
+
+        let (status, _, _) = fetch(&app, "/api/v1/posts?sort=sideways").await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+}
@@ -22,6 +22,10 @@ mod tests {
             let post = parse_to_data(&path).await.expect("should be valid post");
             dbg!(&post);
             assert_eq!(post.markdown.slug, "example-title".to_owned());
+            assert!(post
+                .data
+                .contains("<pre><code class=\"hl-code language-rust\">"));
+            assert!(post.data.contains("<span class=\"hl-"));
         }
     }
 }
@@ -0,0 +1,130 @@
+mod common;
+
+mod publishing_tests {
+    use std::sync::atomic::{AtomicI32, Ordering};
+
+    use axum::{
+        body::Body,
+        http::{header, Request, StatusCode},
+        routing::get,
+        Router,
+    };
+    use chrono::NaiveDate;
+    use personal::{
+        data::Post,
+        db::{Database, InMemDatabase},
+        http::{
+            admin::{self, preview_token, AdminCredentials},
+            handlers,
+            state::AppState,
+        },
+        views::ViewCounterStore,
+    };
+
+    use crate::common::{self, post, send, PostBuilder};
+
+    const SECRET: &str = "preview-secret";
+    const TOKEN: &str = "admin-token";
+
+    /// Day of March 2026 the test clock reports
+    static TODAY: AtomicI32 = AtomicI32::new(1);
+
+    fn test_clock() -> NaiveDate {
+        NaiveDate::from_ymd_opt(2026, 3, TODAY.load(Ordering::SeqCst) as u32).unwrap()
+    }
+
+    fn database() -> InMemDatabase {
+        common::database(&[
+            post("published").with_date(2026, 3, 1),
+            post("scheduled").with_date(2026, 3, 10),
+            post("draft").with_date(2026, 3, 1).with_draft(true),
+        ])
+        .with_clock(test_clock)
+    }
+
+    fn slugs(posts: Vec<Post>) -> Vec<String> {
+        let mut slugs: Vec<String> = posts.into_iter().map(|p| p.markdown.slug).collect();
+        slugs.sort();
+        slugs
+    }
+
+    async fn fetch(app: &Router, uri: &str) -> (StatusCode, Option<String>, String) {
+        let request = Request::get(uri)
+            .header("Authorization", format!("Bearer {TOKEN}"))
+            .body(Body::empty())
+            .unwrap();
+        let (status, headers, body) = send(app, request).await;
+        let location = headers
+            .get(header::LOCATION)
+            .map(|value| value.to_str().unwrap().to_string());
+        (status, location, body)
+    }
+
+    // Both scenarios share the process-wide test clock, so they run in one test
+    #[tokio::test]
+    async fn test_drafts_and_scheduled_posts() {
+        TODAY.store(1, Ordering::SeqCst);
+        let db = database();
+        assert_eq!(slugs(db.get_all_posts()), vec!["published"]);
+        assert_eq!(slugs(db.get_by_tag("rust".to_string())), vec!["published"]);
+        assert_eq!(slugs(db.get_last_n_posts(10)), vec!["published"]);
+        assert!(db.get_by_slug("scheduled".to_string()).is_none());
+        assert!(db.get_by_slug("draft".to_string()).is_none());
+        assert_eq!(db.get_all_tags_with_count(), vec![("rust".to_string(), 1)]);
+        assert_eq!(
+            slugs(db.get_unpublished_posts()),
+            vec!["draft", "scheduled"]
+        );
+
+        let views_dir = tempfile::tempdir().unwrap();
+        let views = ViewCounterStore::load(views_dir.path().join("views.tsv")).unwrap();
+        let state = AppState::new(db, views).with_admin_credentials(AdminCredentials {
+            token: Some(TOKEN.to_string()),
+            preview_secret: Some(SECRET.to_string()),
+            ..AdminCredentials::default()
+        });
+        let app = Router::new()
+            .route("/sitemap.xml", get(handlers::sitemap_xml))
+            .route("/preview/{slug}", get(handlers::html_preview_post))
+            .merge(admin::router())
+            .with_state(state.clone());
+
+        let (_, _, sitemap) = fetch(&app, "/sitemap.xml").await;
+        assert!(sitemap.contains("/posts/published"));
+        assert!(!sitemap.contains("/posts/scheduled"));
+        assert!(!sitemap.contains("/posts/draft"));
+
+        let draft_token = preview_token(SECRET.as_bytes(), "draft");
+        let (status, _, html) = fetch(&app, &format!("/preview/draft?token={draft_token}")).await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(html.contains("noindex"));
+        assert_eq!(state.views.lock().unwrap().get("draft"), 0);
+
+        let (status, _, _) = fetch(&app, "/preview/draft?token=00").await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        let (status, _, _) = fetch(&app, &format!("/preview/scheduled?token={draft_token}")).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+
+        let (status, _, links) = fetch(&app, "/admin/previews").await;
+        assert_eq!(status, StatusCode::OK);
+        let links: serde_json::Value = serde_json::from_str(&links).unwrap();
+        assert_eq!(links.as_array().unwrap().len(), 2);
+        let scheduled_url = links[1]["url"].as_str().unwrap().to_string();
+        assert_eq!(links[1]["slug"], "scheduled");
+
+        // Once its date arrives the scheduled post is public without a reload
+        TODAY.store(10, Ordering::SeqCst);
+        {
+            let snapshot = state.snapshot();
+            let db = &snapshot.db;
+            assert!(db.get_by_slug("scheduled".to_string()).is_some());
+            assert!(db.get_by_slug("draft".to_string()).is_none());
+            assert_eq!(slugs(db.get_unpublished_posts()), vec!["draft"]);
+        }
+        let (status, location, _) = fetch(&app, &scheduled_url).await;
+        assert_eq!(status, StatusCode::SEE_OTHER);
+        assert_eq!(location.as_deref(), Some("/posts/scheduled"));
+        let (_, _, sitemap) = fetch(&app, "/sitemap.xml").await;
+        assert!(sitemap.contains("/posts/scheduled"));
+    }
+}
@@ -0,0 +1,166 @@
+mod common;
+
+mod repository_sync_tests {
+    use std::collections::BTreeMap;
+    use std::path::PathBuf;
+
+    use personal::{
+        error::ApplicationError, repo_utils::RepositorySync, resources::fingerprinted_path,
+    };
+
+    use crate::common::{bare_remote, commit_snapshot, post_source};
+
+    #[tokio::test]
+    async fn test_sync_clones_then_applies_incremental_changes() -> Result<(), ApplicationError> {
+        let root = tempfile::tempdir()?;
+        let (remote, url) = bare_remote(root.path());
+
+        let mut files: BTreeMap<&str, Vec<u8>> = BTreeMap::new();
+        files.insert("posts/first.md", post_source("first", "First").into_bytes());
+        files.insert(
+            "posts/second.md",
+            post_source("second", "Second").into_bytes(),
+        );
+        files.insert("resources/image.png", vec![1, 2, 3]);
+        let first_commit = commit_snapshot(&remote, &files);
+
+        let resources_dir = root.path().join("misc");
+        let mut sync =
+            RepositorySync::new(url, root.path().join("mirror")).with_resources_dir(&resources_dir);
+
+        let outcome = sync.sync().await?;
+        assert!(outcome.full_reload);
+        assert_eq!(outcome.commit, first_commit.to_string());
+        assert_eq!(sync.commit(), Some(first_commit.to_string()));
+        assert_eq!(sync.posts().len(), 2);
+        assert_eq!(
+            std::fs::read(resources_dir.join("image.png"))?,
+            vec![1, 2, 3]
+        );
+
+        let outcome = sync.sync().await?;
+        assert!(outcome.is_unchanged());
+
+        files.insert(
+            "posts/first.md",
+            post_source("first", "First, edited").into_bytes(),
+        );
+        files.remove("posts/second.md");
+        files.remove("resources/image.png");
+        files.insert("resources/other.png", vec![4, 5]);
+        let second_commit = commit_snapshot(&remote, &files);
+
+        let outcome = sync.sync().await?;
+        assert!(!outcome.full_reload);
+        assert_eq!(outcome.commit, second_commit.to_string());
+        assert_eq!(
+            outcome
+                .changed_posts
+                .iter()
+                .collect::<std::collections::BTreeSet<_>>(),
+            [
+                PathBuf::from("posts/first.md"),
+                PathBuf::from("posts/second.md")
+            ]
+            .iter()
+            .collect()
+        );
+        assert_eq!(outcome.changed_resources.len(), 2);
+
+        let posts = sync.posts();
+        assert_eq!(posts.len(), 1);
+        assert_eq!(posts[0].markdown.title, "First, edited");
+        assert!(!resources_dir.join("image.png").exists());
+        assert!(resources_dir.join("other.png").exists());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_sync_reuses_existing_mirror() -> Result<(), ApplicationError> {
+        let root = tempfile::tempdir()?;
+        let (remote, url) = bare_remote(root.path());
+        let mut files: BTreeMap<&str, Vec<u8>> = BTreeMap::new();
+        files.insert("posts/first.md", post_source("first", "First").into_bytes());
+        commit_snapshot(&remote, &files);
+
+        let mirror = root.path().join("mirror");
+        let resources_dir = root.path().join("misc");
+        RepositorySync::new(url.clone(), &mirror)
+            .with_resources_dir(&resources_dir)
+            .sync()
+            .await?;
+
+        files.insert(
+            "posts/second.md",
+            post_source("second", "Second").into_bytes(),
+        );
+        let latest = commit_snapshot(&remote, &files);
+
+        let mut restarted = RepositorySync::new(url, &mirror).with_resources_dir(&resources_dir);
+        let outcome = restarted.sync().await?;
+        assert!(outcome.full_reload);
+        assert_eq!(restarted.commit(), Some(latest.to_string()));
+        assert_eq!(restarted.posts().len(), 2);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_posts_link_the_current_fingerprint_of_resources() -> Result<(), ApplicationError>
+    {
+        let root = tempfile::tempdir()?;
+        let (remote, url) = bare_remote(root.path());
+        let source = post_source("first", "First") + "\n[Diagram](image.png)\n";
+        let mut files: BTreeMap<&str, Vec<u8>> = BTreeMap::new();
+        files.insert("posts/first.md", source.into_bytes());
+        files.insert(
+            "posts/second.md",
+            (post_source("second", "Second") + "\n[Photo](photo.jpg)\n").into_bytes(),
+        );
+        files.insert("resources/image.png", vec![1, 2, 3]);
+        commit_snapshot(&remote, &files);
+
+        let resources_dir = root.path().join("misc");
+        let mut sync =
+            RepositorySync::new(url, root.path().join("mirror")).with_resources_dir(&resources_dir);
+        sync.sync().await?;
+        let first_url = fingerprinted_path("/static/misc/image.png", &[1, 2, 3]);
+        assert!(sync.posts()[0]
+            .data
+            .contains(&format!("href=\"{first_url}\"")));
+        let first_file = first_url.trim_start_matches("/static/misc/");
+        assert_eq!(
+            std::fs::read(resources_dir.join(first_file))?,
+            vec![1, 2, 3]
+        );
+
+        // Editing only the resource re-renders the post that links to it, and no other
+        files.insert("resources/image.png", vec![4, 5]);
+        commit_snapshot(&remote, &files);
+        let outcome = sync.sync().await?;
+        assert_eq!(outcome.changed_posts, vec![PathBuf::from("posts/first.md")]);
+        let second_url = fingerprinted_path("/static/misc/image.png", &[4, 5]);
+        assert!(sync.posts()[0]
+            .data
+            .contains(&format!("href=\"{second_url}\"")));
+        assert_eq!(std::fs::read(resources_dir.join("image.png"))?, vec![4, 5]);
+        // Pages cached before the edit keep their copy
+        assert_eq!(
+            std::fs::read(resources_dir.join(first_file))?,
+            vec![1, 2, 3]
+        );
+
+        // A resource added later is picked up by the posts already linking to it
+        files.insert("resources/photo.jpg", vec![6]);
+        commit_snapshot(&remote, &files);
+        let outcome = sync.sync().await?;
+        assert_eq!(
+            outcome.changed_posts,
+            vec![PathBuf::from("posts/second.md")]
+        );
+        let photo_url = fingerprinted_path("/static/misc/photo.jpg", &[6]);
+        assert!(sync.posts()[1]
+            .data
+            .contains(&format!("href=\"{photo_url}\"")));
+        Ok(())
+    }
+}
@@ -0,0 +1,142 @@
+mod common;
+
+mod search_tests {
+    use axum::{http::StatusCode, routing::get, Router};
+    use chrono::NaiveDate;
+    use personal::{
+        data::Post,
+        db::{Database, InMemDatabase},
+        http::{api, handlers, state::AppState},
+        views::ViewCounterStore,
+    };
+
+    use crate::common::{self, fetch, post, PostBuilder};
+
+    fn fixed_clock() -> NaiveDate {
+        NaiveDate::from_ymd_opt(2026, 3, 1).unwrap()
+    }
+
+    fn note(slug: &str, title: &str, body: &str, day: u32) -> Post {
+        post(slug)
+            .with_title(title)
+            .with_description(&format!("Notes on {title}"))
+            .with_body(body)
+            .with_tags(&["notes"])
+            .with_date(2026, 2, day)
+    }
+
+    fn database() -> InMemDatabase {
+        common::database(&[
+            note(
+                "ownership",
+                "Ownership in Rust",
+                "<p>Borrowing rules keep Rust memory safe at zero cost.</p>",
+                1,
+            ),
+            note(
+                "iterators",
+                "Iterators",
+                "<p>Rust iterators are a zero cost abstraction over loops.</p>",
+                2,
+            ),
+            note(
+                "gardening",
+                "Gardening",
+                "<h2>Cost of <code>seeds</code></h2><p>Tomatoes need sun &amp; water.</p>",
+                3,
+            ),
+            note(
+                "scheduled",
+                "Rust futures",
+                "<p>Rust async is coming.</p>",
+                1,
+            )
+            .with_date(2026, 4, 1),
+        ])
+        .with_clock(fixed_clock)
+    }
+
+    fn slugs(db: &InMemDatabase, query: &str) -> Vec<String> {
+        db.search(query)
+            .into_iter()
+            .map(|hit| hit.post.markdown.slug)
+            .collect()
+    }
+
+    #[test]
+    fn test_title_matches_rank_first() {
+        let db = database();
+        assert_eq!(slugs(&db, "rust"), vec!["ownership", "iterators"]);
+        assert_eq!(slugs(&db, "RUST iterators"), vec!["iterators"]);
+        assert!(slugs(&db, "rust tomatoes").is_empty());
+    }
+
+    #[test]
+    fn test_phrase_and_prefix_queries() {
+        let db = database();
+        assert_eq!(slugs(&db, r#""zero cost abstraction""#), vec!["iterators"]);
+        assert!(slugs(&db, r#""cost zero""#).is_empty());
+        assert_eq!(slugs(&db, "tomat*"), vec!["gardening"]);
+        assert_eq!(slugs(&db, "borrow*"), vec!["ownership"]);
+    }
+
+    #[test]
+    fn test_headings_are_searchable_and_snippets_marked() {
+        let db = database();
+        let hits = db.search("seeds");
+        assert_eq!(hits.len(), 1);
+        assert_eq!(
+            hits[0].snippet,
+            "Cost of <mark>seeds</mark> Tomatoes need sun &amp; water."
+        );
+    }
+
+    #[test]
+    fn test_keyword_lookup_is_ranked_search() {
+        let db = database();
+        let posts = db.get_by_keyword("zero cost".to_string());
+        let slugs: Vec<&str> = posts.iter().map(|p| p.markdown.slug.as_str()).collect();
+        assert_eq!(slugs.len(), 2);
+        assert!(!slugs.contains(&"scheduled"));
+    }
+
+    fn app() -> (Router, tempfile::TempDir) {
+        let views_dir = tempfile::tempdir().unwrap();
+        let views = ViewCounterStore::load(views_dir.path().join("views.tsv")).unwrap();
+        let app = Router::new()
+            .route(
+                "/posts/by-keyword",
+                get(handlers::html_get_posts_by_keyword),
+            )
+            .merge(api::router())
+            .with_state(AppState::new(database(), views));
+        (app, views_dir)
+    }
+
+    #[tokio::test]
+    async fn test_search_endpoint() {
+        let (app, _views_dir) = app();
+        let (status, _, body) = fetch(&app, "/api/v1/search?q=rust&limit=1").await;
+        assert_eq!(status, StatusCode::OK);
+        let hits: serde_json::Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(hits.as_array().unwrap().len(), 1);
+        assert_eq!(hits[0]["post"]["markdown"]["slug"], "ownership");
+        assert!(hits[0]["snippet"]
+            .as_str()
+            .unwrap()
+            .contains("<mark>Rust</mark>"));
+
+        let (_, _, body) = fetch(&app, "/api/v1/search?q=futures").await;
+        assert_eq!(body, "[]");
+    }
+
+    #[tokio::test]
+    async fn test_search_results_page_shows_snippets() {
+        let (app, _views_dir) = app();
+        let (status, _, html) = fetch(&app, "/posts/by-keyword?keyword=tomatoes").await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(html.contains("Gardening"));
+        assert!(html.contains("<mark>Tomatoes</mark>"));
+        assert!(!html.contains("Ownership in Rust"));
+    }
+}
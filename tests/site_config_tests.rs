@@ -0,0 +1,105 @@
+mod site_config_tests {
+    use axum::{
+        body::{to_bytes, Body},
+        http::{Request, StatusCode},
+        routing::get,
+        Router,
+    };
+    use chrono::NaiveDate;
+    use personal::{
+        config::SiteConfig,
+        data::{Markdown, Post},
+        db::{Database, InMemDatabase},
+        http::{api, handlers, state::AppState},
+        views::ViewCounterStore,
+    };
+    use tower::ServiceExt;
+
+    fn site() -> SiteConfig {
+        SiteConfig {
+            base_url: "https://notes.example.org".to_string(),
+            title: "Example Notes".to_string(),
+            description: "Notes from the example team".to_string(),
+            author: "Example Team".to_string(),
+            twitter: None,
+            profiles: vec!["https://github.com/example".to_string()],
+        }
+    }
+
+    async fn fetch(app: &Router, uri: &str) -> String {
+        let response = app
+            .clone()
+            .oneshot(Request::get(uri).body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK, "{uri}");
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        String::from_utf8(body.to_vec()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_pages_and_feeds_use_the_configured_site() {
+        let mut db = InMemDatabase::new();
+        db.insert_parsed_to_database(Post {
+            data: "<p>Hello</p>".to_string(),
+            markdown: Markdown {
+                title: "First note".to_string(),
+                description: "The first one".to_string(),
+                slug: "first-note".to_string(),
+                tags: vec!["notes".to_string()],
+                date: NaiveDate::from_ymd_opt(2026, 1, 1).unwrap(),
+                series: None,
+                draft: false,
+            },
+        })
+        .unwrap();
+        let dir = tempfile::tempdir().unwrap();
+        let views = ViewCounterStore::load(dir.path().join("views.tsv")).unwrap();
+        let state = AppState::new(db, views).with_site(site());
+        let app = Router::new()
+            .route("/", get(handlers::html_index))
+            .route("/posts/{slug}", get(handlers::html_get_post_by_slug))
+            .route("/posts/by-tag", get(handlers::html_get_posts_by_tag))
+            .route("/robots.txt", get(handlers::robots_txt))
+            .route("/sitemap.xml", get(handlers::sitemap_xml))
+            .route("/feed.atom", get(handlers::feed_atom))
+            .route("/feed.json", get(handlers::feed_json))
+            .merge(api::router())
+            .with_state(state);
+
+        for uri in [
+            "/",
+            "/posts/first-note",
+            "/posts/by-tag?tag=notes",
+            "/robots.txt",
+            "/sitemap.xml",
+            "/feed.atom",
+            "/feed.json",
+            "/api/openapi.json",
+        ] {
+            let body = fetch(&app, uri).await;
+            assert!(
+                !body.contains("rwd.works"),
+                "{uri} mentions the default site"
+            );
+            assert!(!body.contains("Rafał"), "{uri} mentions the default author");
+        }
+
+        let index = fetch(&app, "/").await;
+        assert!(index.contains("<title>Example Notes</title>"));
+        assert!(index.contains("href=\"https://notes.example.org/\""));
+        assert!(index.contains("&copy; 2026 Example Team"));
+        assert!(!index.contains("twitter:creator"));
+
+        let post = fetch(&app, "/posts/first-note").await;
+        assert!(post.contains("First note | Example Notes"));
+        assert!(post.contains("https://notes.example.org/posts/first-note"));
+
+        let robots = fetch(&app, "/robots.txt").await;
+        assert!(robots.contains("Sitemap: https://notes.example.org/sitemap.xml"));
+        let feed = fetch(&app, "/feed.atom").await;
+        assert!(feed.contains("<name>Example Team</name>"));
+        let openapi = fetch(&app, "/api/openapi.json").await;
+        assert!(openapi.contains("Example Notes API"));
+    }
+}
@@ -0,0 +1,64 @@
+mod common;
+
+mod snapshot_tests {
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    use chrono::NaiveDate;
+    use personal::{
+        data::Post,
+        db::{Database, InMemDatabase},
+        http::state::AppState,
+        snapshot::Snapshot,
+        views::ViewCounterStore,
+    };
+
+    use crate::common::{self, post, PostBuilder};
+
+    /// Day of May 2026 the test clock reports
+    static TODAY: AtomicU32 = AtomicU32::new(1);
+
+    fn test_clock() -> NaiveDate {
+        NaiveDate::from_ymd_opt(2026, 5, TODAY.load(Ordering::SeqCst)).unwrap()
+    }
+
+    fn database(posts: &[Post]) -> InMemDatabase {
+        common::database(posts).with_clock(test_clock)
+    }
+
+    #[test]
+    fn test_nav_refreshes_when_a_scheduled_post_goes_live() {
+        TODAY.store(1, Ordering::SeqCst);
+        let snapshot = Snapshot::new(database(&[
+            post("now").with_date(2026, 5, 1),
+            post("later").with_tags(&["go"]).with_date(2026, 5, 20),
+        ]));
+        let nav = snapshot.nav();
+        assert_eq!(nav.tags_with_count, vec![("rust".to_string(), 1)]);
+        assert!(std::sync::Arc::ptr_eq(&nav, &snapshot.nav()));
+
+        TODAY.store(20, Ordering::SeqCst);
+        let nav = snapshot.nav();
+        assert_eq!(nav.tags_with_count.len(), 2);
+        assert!(std::sync::Arc::ptr_eq(&nav, &snapshot.nav()));
+    }
+
+    #[test]
+    fn test_publishing_leaves_held_snapshots_intact() {
+        let views_dir = tempfile::tempdir().unwrap();
+        let views = ViewCounterStore::load(views_dir.path().join("views.tsv")).unwrap();
+        let state = AppState::new(database(&[post("first").with_date(2026, 5, 1)]), views);
+
+        let held = state.snapshot();
+        state.content.publish(
+            Snapshot::new(database(&[post("second").with_date(2026, 5, 1)]))
+                .with_commit(Some("abc".into())),
+        );
+
+        assert!(held.db.get_by_slug("first".to_string()).is_some());
+        assert_eq!(held.commit, None);
+        let current = state.snapshot();
+        assert!(current.db.get_by_slug("first".to_string()).is_none());
+        assert!(current.db.get_by_slug("second".to_string()).is_some());
+        assert_eq!(current.commit.as_deref(), Some("abc"));
+    }
+}
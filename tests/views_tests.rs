@@ -0,0 +1,398 @@
+mod common;
+
+mod views_tests {
+    use std::fs;
+    use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    use axum::{
+        body::Body,
+        extract::ConnectInfo,
+        http::{header, Request, StatusCode},
+        routing::get,
+        Router,
+    };
+    use chrono::{Duration, Local, NaiveDate, TimeZone};
+    use personal::{
+        http::{handlers, state::AppState},
+        view_history::{SourceCount, ViewCount, ViewSource},
+        views::ViewCounterStore,
+        visitors::{ViewFilter, ViewRequest, ViewVerdict},
+    };
+    use tower::ServiceExt;
+
+    use crate::common::{database, post};
+
+    fn march_fifth() -> NaiveDate {
+        NaiveDate::from_ymd_opt(2026, 3, 5).unwrap()
+    }
+
+    const PROXY: SocketAddr = SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 4000);
+
+    const FIREFOX: &str = "Mozilla/5.0 (X11; Linux x86_64; rv:128.0) Gecko/20100101 Firefox/128.0";
+
+    #[test]
+    fn test_views_survive_a_crash_before_flushing() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("views.tsv");
+
+        let mut views = ViewCounterStore::load(path.clone()).unwrap();
+        views.increment("first").unwrap();
+        views.increment("first").unwrap();
+        views.increment("second").unwrap();
+        assert!(!path.exists());
+        drop(views);
+
+        let views = ViewCounterStore::load(path.clone()).unwrap();
+        assert_eq!(views.get("first"), 2);
+        assert_eq!(views.get("second"), 1);
+        assert_eq!(views.unflushed(), 3);
+    }
+
+    #[test]
+    fn test_flush_compacts_the_log_without_double_counting() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("views.tsv");
+
+        let mut views = ViewCounterStore::load(path.clone()).unwrap();
+        views.increment("post").unwrap();
+        views.flush().unwrap();
+        views.increment("post").unwrap();
+        assert_eq!(views.unflushed(), 1);
+        drop(views);
+
+        let logs: Vec<String> = fs::read_dir(dir.path())
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+            .filter(|name| name.ends_with(".log"))
+            .collect();
+        assert_eq!(logs, vec!["views.tsv.1.log"]);
+
+        let mut views = ViewCounterStore::load(path.clone()).unwrap();
+        assert_eq!(views.get("post"), 2);
+        views.flush().unwrap();
+        assert_eq!(ViewCounterStore::load(path).unwrap().get("post"), 2);
+    }
+
+    #[test]
+    fn test_logs_already_in_the_snapshot_are_ignored() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("views.tsv");
+
+        // A crash after the snapshot was renamed into place but before the old log went
+        let mut views = ViewCounterStore::load(path.clone()).unwrap();
+        views.increment("post").unwrap();
+        let log = fs::read(dir.path().join("views.tsv.0.log")).unwrap();
+        views.flush().unwrap();
+        fs::write(dir.path().join("views.tsv.0.log"), log).unwrap();
+
+        assert_eq!(ViewCounterStore::load(path).unwrap().get("post"), 1);
+    }
+
+    #[test]
+    fn test_a_torn_entry_is_dropped_and_overwritten() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("views.tsv");
+        fs::write(dir.path().join("views.tsv.0.log"), "2026-03-04\tpost\npo").unwrap();
+
+        let mut views = ViewCounterStore::load_with_clock(path.clone(), march_fifth).unwrap();
+        assert_eq!(views.get("post"), 1);
+        assert_eq!(views.get("po"), 0);
+        assert_eq!(views.increment("other").unwrap(), 1);
+        assert_eq!(
+            fs::read_to_string(dir.path().join("views.tsv.0.log")).unwrap(),
+            "2026-03-04\tpost\n2026-03-05\tother\n"
+        );
+    }
+
+    #[test]
+    fn test_undated_log_entries_count_on_the_clocks_day() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("views.tsv");
+        fs::write(dir.path().join("views.tsv.0.log"), "post\npost\traw\n").unwrap();
+
+        let views = ViewCounterStore::load_with_clock(path, march_fifth).unwrap();
+        let today = views.history().range(march_fifth(), march_fifth());
+        assert_eq!(today["post"], ViewCount { views: 1, raw: 2 });
+    }
+
+    #[test]
+    fn test_lifetime_totals_migrate_into_the_history() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("views.tsv");
+        fs::write(&path, "old\t7\nnew\t2").unwrap();
+
+        let mut views = ViewCounterStore::load_with_clock(path.clone(), march_fifth).unwrap();
+        assert_eq!(views.increment("old").unwrap(), 8);
+        views.flush().unwrap();
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            "#generation\t1\nnew\t2\t2\nold\t7\t7\n2026-03-05\told\t1\t1"
+        );
+
+        let views = ViewCounterStore::load(path).unwrap();
+        assert_eq!((views.get("old"), views.get_raw("old")), (8, 8));
+        let week = views
+            .history()
+            .range(march_fifth() - Duration::days(6), march_fifth());
+        assert_eq!(week["old"], ViewCount { views: 1, raw: 1 });
+        assert!(!week.contains_key("new"));
+    }
+
+    /// Day of March 2026 reported by `march_clock`
+    static MARCH_DAY: AtomicU32 = AtomicU32::new(1);
+
+    fn march_clock() -> NaiveDate {
+        NaiveDate::from_ymd_opt(2026, 3, MARCH_DAY.load(Ordering::SeqCst)).unwrap()
+    }
+
+    #[test]
+    fn test_history_answers_ranges_and_movers() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut views =
+            ViewCounterStore::load_with_clock(dir.path().join("views.tsv"), march_clock).unwrap();
+        for (day, slug, times) in [
+            (1, "fading", 3),
+            (2, "rising", 1),
+            (4, "rising", 4),
+            (4, "steady", 1),
+            (5, "fading", 1),
+            (5, "steady", 1),
+        ] {
+            MARCH_DAY.store(day, Ordering::SeqCst);
+            for _ in 0..times {
+                views.record(slug, true).unwrap();
+            }
+        }
+        views.record("rising", false).unwrap();
+
+        let history = views.history();
+        let today = march_clock();
+        let last_two_days = history.range(today - Duration::days(1), today);
+        assert_eq!(last_two_days["rising"], ViewCount { views: 4, raw: 5 });
+        assert_eq!(last_two_days["fading"].views, 1);
+
+        let series = history.daily("rising", today - Duration::days(3), today);
+        let daily: Vec<u64> = series.iter().map(|(_, count)| count.views).collect();
+        assert_eq!(daily, vec![1, 0, 4, 0]);
+
+        let movers = history.movers(today, 2, 10);
+        let changes: Vec<(&str, i64)> = movers
+            .iter()
+            .map(|mover| (mover.slug.as_str(), mover.change))
+            .collect();
+        assert_eq!(changes, vec![("rising", 3), ("steady", 2), ("fading", 1)]);
+    }
+
+    #[test]
+    fn test_raw_counts_include_filtered_requests() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("views.tsv");
+
+        let mut views = ViewCounterStore::load(path.clone()).unwrap();
+        assert_eq!(views.record("post", true).unwrap(), 1);
+        assert_eq!(views.record("post", false).unwrap(), 1);
+        assert_eq!(views.record("bots-only", false).unwrap(), 0);
+        drop(views);
+
+        let mut views = ViewCounterStore::load(path.clone()).unwrap();
+        assert_eq!((views.get("post"), views.get_raw("post")), (1, 2));
+        assert_eq!(views.get_raw("bots-only"), 1);
+        views.flush().unwrap();
+
+        let views = ViewCounterStore::load(path).unwrap();
+        assert_eq!((views.get("post"), views.get_raw("post")), (1, 2));
+        assert_eq!(views.snapshot_sorted(), vec![("post".to_string(), 1)]);
+    }
+
+    #[test]
+    fn test_sources_of_counted_views_are_kept() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("views.tsv");
+        let from_news = ViewSource {
+            referrer: Some("news.ycombinator.com".to_string()),
+            utm_campaign: Some("launch".to_string()),
+            ..ViewSource::default()
+        };
+        let broken = ViewSource {
+            referrer: Some("news.ycombinator.com".to_string()),
+            utm_source: Some("tab\tseparated".to_string()),
+            ..ViewSource::default()
+        };
+
+        let mut views = ViewCounterStore::load_with_clock(path.clone(), march_fifth).unwrap();
+        views.record_from("post", true, &from_news).unwrap();
+        views.record_from("post", true, &broken).unwrap();
+        views.record_from("post", false, &from_news).unwrap();
+        views.record_from("other", true, &from_news).unwrap();
+        drop(views);
+
+        let news = |views: u64| {
+            vec![SourceCount {
+                value: "news.ycombinator.com".to_string(),
+                views,
+            }]
+        };
+        let mut views = ViewCounterStore::load_with_clock(path.clone(), march_fifth).unwrap();
+        let replayed = views.history().sources(march_fifth(), march_fifth());
+        assert_eq!(replayed.referrers, news(3));
+        assert_eq!(replayed.utm_campaigns[0].views, 2);
+        assert!(replayed.utm_sources.is_empty());
+        views.flush().unwrap();
+
+        let views = ViewCounterStore::load_with_clock(path, march_fifth).unwrap();
+        let by_post = views
+            .history()
+            .sources_by_post(march_fifth(), march_fifth());
+        assert_eq!(by_post["post"].referrers, news(2));
+        assert_eq!(by_post["other"].referrers, news(1));
+        let yesterday = march_fifth() - Duration::days(1);
+        assert!(views.history().sources(yesterday, yesterday).is_empty());
+    }
+
+    fn visitor(ip: &str, user_agent: &str) -> ViewRequest {
+        ViewRequest {
+            ip: ip.parse().ok(),
+            user_agent: user_agent.to_string(),
+            ..ViewRequest::default()
+        }
+    }
+
+    #[test]
+    fn test_visitors_are_counted_once_per_window() {
+        let mut filter = ViewFilter::new(Duration::minutes(30));
+        let noon = Local.with_ymd_and_hms(2026, 3, 4, 12, 0, 0).unwrap();
+        let reader = visitor("198.51.100.7", FIREFOX);
+
+        assert_eq!(filter.check("post", &reader, noon), ViewVerdict::Counted);
+        let soon = noon + Duration::minutes(10);
+        assert_eq!(filter.check("post", &reader, soon), ViewVerdict::Repeat);
+        assert_eq!(filter.check("other", &reader, soon), ViewVerdict::Counted);
+        let neighbour = visitor("198.51.100.8", FIREFOX);
+        assert_eq!(filter.check("post", &neighbour, soon), ViewVerdict::Counted);
+        let later = noon + Duration::minutes(45);
+        assert_eq!(filter.check("post", &reader, later), ViewVerdict::Counted);
+
+        let bot = visitor("198.51.100.7", "Mozilla/5.0 (compatible; bingbot/2.0)");
+        assert_eq!(filter.check("post", &bot, later), ViewVerdict::Bot);
+        let private = ViewRequest {
+            do_not_track: true,
+            ..visitor("198.51.100.9", FIREFOX)
+        };
+        assert_eq!(
+            filter.check("post", &private, later),
+            ViewVerdict::DoNotTrack
+        );
+        let prefetch = ViewRequest {
+            prefetch: true,
+            ..visitor("198.51.100.9", FIREFOX)
+        };
+        assert_eq!(
+            filter.check("post", &prefetch, later),
+            ViewVerdict::Prefetch
+        );
+    }
+
+    #[test]
+    fn test_a_new_day_forgets_yesterdays_visitors() {
+        let mut filter = ViewFilter::new(Duration::hours(2));
+        let reader = visitor("198.51.100.7", FIREFOX);
+        let late = Local.with_ymd_and_hms(2026, 3, 4, 23, 30, 0).unwrap();
+        assert_eq!(filter.check("post", &reader, late), ViewVerdict::Counted);
+        let after_midnight = late + Duration::minutes(40);
+        assert_eq!(
+            filter.check("post", &reader, after_midnight),
+            ViewVerdict::Counted
+        );
+    }
+
+    #[tokio::test]
+    async fn test_post_page_counts_filtered_views() {
+        let dir = tempfile::tempdir().unwrap();
+        let views = ViewCounterStore::load(dir.path().join("views.tsv")).unwrap();
+        let db = database(&[post("post")]);
+        let state = AppState::new(db, views).with_trusted_proxies(vec![PROXY.ip()]);
+        let app = Router::new()
+            .route("/posts/{slug}", get(handlers::html_get_post_by_slug))
+            .with_state(state.clone());
+
+        for (user_agent, ip) in [
+            (FIREFOX, "203.0.113.1"),
+            (FIREFOX, "203.0.113.1"),
+            (FIREFOX, "203.0.113.2"),
+            ("Slackbot-LinkExpanding 1.0", "203.0.113.3"),
+        ] {
+            let response = app
+                .clone()
+                .oneshot(
+                    Request::get("/posts/post")
+                        .header(header::USER_AGENT, user_agent)
+                        .header("x-forwarded-for", ip)
+                        .extension(ConnectInfo(PROXY))
+                        .body(Body::empty())
+                        .unwrap(),
+                )
+                .await
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+        }
+
+        let response = app
+            .clone()
+            .oneshot(
+                Request::get("/posts/post?utm_source=newsletter&utm_medium=email")
+                    .header(header::USER_AGENT, FIREFOX)
+                    .header(header::HOST, "blog.example")
+                    .header(header::REFERER, "https://mail.example.com/inbox/42")
+                    .header("x-forwarded-for", "203.0.113.4")
+                    .extension(ConnectInfo(PROXY))
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let views = state.views.lock().unwrap();
+        assert_eq!(views.get("post"), 3);
+        assert_eq!(views.get_raw("post"), 5);
+        let today = views.today();
+        let sources = views.history().sources(today, today);
+        assert_eq!(sources.referrers[0].value, "mail.example.com");
+        assert_eq!(sources.utm_sources[0].value, "newsletter");
+        assert_eq!(sources.utm_mediums[0].value, "email");
+    }
+
+    #[tokio::test]
+    async fn test_direct_clients_cannot_spoof_their_address() {
+        let dir = tempfile::tempdir().unwrap();
+        let views = ViewCounterStore::load(dir.path().join("views.tsv")).unwrap();
+        let db = database(&[post("post")]);
+        let state = AppState::new(db, views).with_trusted_proxies(vec![PROXY.ip()]);
+        let app = Router::new()
+            .route("/posts/{slug}", get(handlers::html_get_post_by_slug))
+            .with_state(state.clone());
+
+        let client: SocketAddr = "198.51.100.7:5000".parse().unwrap();
+        for forged in ["203.0.113.1", "203.0.113.2", "203.0.113.3"] {
+            let response = app
+                .clone()
+                .oneshot(
+                    Request::get("/posts/post")
+                        .header(header::USER_AGENT, FIREFOX)
+                        .header("x-forwarded-for", forged)
+                        .header("x-real-ip", forged)
+                        .extension(ConnectInfo(client))
+                        .body(Body::empty())
+                        .unwrap(),
+                )
+                .await
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+        }
+
+        let views = state.views.lock().unwrap();
+        assert_eq!(views.get("post"), 1);
+        assert_eq!(views.get_raw("post"), 3);
+    }
+}
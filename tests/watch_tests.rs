@@ -0,0 +1,207 @@
+mod common;
+
+mod watch_tests {
+    use std::collections::BTreeSet;
+    use std::fs;
+    use std::path::{Path, PathBuf};
+    use std::sync::Arc;
+
+    use axum::{
+        body::{to_bytes, Body},
+        http::{Request, StatusCode},
+        middleware,
+        response::Html,
+        routing::get,
+        Router,
+    };
+    use personal::{
+        db::{Database, InMemDatabase},
+        http::{live_reload, state::AppState},
+        reload::{build_database, ContentSource},
+        views::ViewCounterStore,
+        watch::LocalWatch,
+    };
+    use tokio::sync::{broadcast, Mutex};
+    use tower::ServiceExt;
+
+    use crate::common::post_source;
+
+    fn write(root: &Path, relative: &str, contents: &str) -> PathBuf {
+        let path = root.join(relative);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(&path, contents).unwrap();
+        path
+    }
+
+    fn changed(paths: &[&PathBuf]) -> BTreeSet<PathBuf> {
+        paths.iter().map(|path| (*path).clone()).collect()
+    }
+
+    #[tokio::test]
+    async fn test_apply_reparses_only_changed_files() {
+        let root = tempfile::tempdir().unwrap();
+        let content = root.path().join("content");
+        let misc = root.path().join("misc");
+        write(&content, "posts/first.md", &post_source("first", "First"));
+        write(&content, "resources/image.png", "png");
+
+        let mut watch = LocalWatch::load(&content, &misc).await.unwrap();
+        assert_eq!(watch.posts().len(), 1);
+        assert!(misc.join("image.png").is_file());
+
+        let content = watch.root().to_path_buf();
+        let edited = write(&content, "posts/first.md", &post_source("first", "Edited"));
+        let added = write(
+            &content,
+            "posts/second.md",
+            &post_source("second", "Second"),
+        );
+        let swap = write(&content, "posts/.second.md.swp", "not a post");
+        let resource = write(&content, "resources/extra/photo.jpg", "jpg");
+        let outcome = watch
+            .apply(&changed(&[&edited, &added, &swap, &resource]))
+            .await
+            .unwrap();
+        assert_eq!(outcome.changed_posts.len(), 2);
+        assert_eq!(
+            outcome.changed_resources,
+            vec![PathBuf::from("resources/extra/photo.jpg")]
+        );
+        assert!(misc.join("extra/photo.jpg").is_file());
+
+        let db = watch.build_database().unwrap();
+        assert_eq!(
+            db.get_by_slug("first".to_string()).unwrap().markdown.title,
+            "Edited"
+        );
+        assert!(db.get_by_slug("second".to_string()).is_some());
+
+        // A half-written post keeps its last good version
+        fs::write(&edited, "---\ntitle: [broken").unwrap();
+        fs::remove_file(&added).unwrap();
+        let outcome = watch.apply(&changed(&[&edited, &added])).await.unwrap();
+        assert_eq!(outcome.failed_posts, vec![PathBuf::from("posts/first.md")]);
+        assert_eq!(
+            outcome.changed_posts,
+            vec![PathBuf::from("posts/second.md")]
+        );
+        // and is reported as stale rather than skipped
+        assert!(watch.diagnostics().is_empty());
+        assert_eq!(watch.stale()[0].path, PathBuf::from("posts/first.md"));
+
+        let db = watch.build_database().unwrap();
+        assert_eq!(
+            db.get_by_slug("first".to_string()).unwrap().markdown.title,
+            "Edited"
+        );
+        assert!(db.get_by_slug("second".to_string()).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_resource_changes_reparse_only_the_posts_linking_to_them() {
+        let root = tempfile::tempdir().unwrap();
+        let content = root.path().join("content");
+        let misc = root.path().join("misc");
+        let linking = post_source("first", "First") + "\n![Diagram](./image.png)\n";
+        write(&content, "posts/first.md", &linking);
+        write(
+            &content,
+            "posts/second.md",
+            &post_source("second", "Second"),
+        );
+        write(&content, "resources/image.png", "png");
+
+        let mut watch = LocalWatch::load(&content, &misc).await.unwrap();
+        let content = watch.root().to_path_buf();
+        let image = write(&content, "resources/image.png", "edited png");
+        let outcome = watch.apply(&changed(&[&image])).await.unwrap();
+        assert_eq!(outcome.changed_posts, vec![PathBuf::from("posts/first.md")]);
+
+        let unlinked = write(&content, "resources/unlinked.png", "png");
+        let outcome = watch.apply(&changed(&[&unlinked])).await.unwrap();
+        assert!(outcome.changed_posts.is_empty());
+
+        fs::remove_file(&image).unwrap();
+        let outcome = watch.apply(&changed(&[&image])).await.unwrap();
+        assert_eq!(outcome.changed_posts, vec![PathBuf::from("posts/first.md")]);
+        let db = watch.build_database().unwrap();
+        let first = db.get_by_slug("first".to_string()).unwrap();
+        assert!(first.data.contains("src=\"/static/misc/./image.png\""));
+    }
+
+    #[tokio::test]
+    async fn test_reloads_rescan_the_content_the_watcher_applies_edits_to() {
+        let root = tempfile::tempdir().unwrap();
+        let content = root.path().join("content");
+        write(&content, "posts/first.md", &post_source("first", "First"));
+        let local_watch = LocalWatch::load(&content, root.path().join("misc"))
+            .await
+            .unwrap();
+        let content = local_watch.root().to_path_buf();
+        let local_watch = Arc::new(Mutex::new(local_watch));
+        let mut source = ContentSource::Local(local_watch.clone());
+
+        // A file the watcher never heard of is picked up by a full reload
+        write(
+            &content,
+            "posts/second.md",
+            &post_source("second", "Second"),
+        );
+        let snapshot = build_database(&mut source).await.unwrap();
+        assert!(snapshot.db.get_by_slug("second".to_string()).is_some());
+
+        // and later edits apply on top of that reload instead of replacing it
+        let edited = write(&content, "posts/first.md", &post_source("first", "Edited"));
+        let mut watch = local_watch.lock().await;
+        let outcome = watch.apply(&changed(&[&edited])).await.unwrap();
+        assert_eq!(outcome.changed_posts, vec![PathBuf::from("posts/first.md")]);
+        let db = watch.build_database().unwrap();
+        assert!(db.get_by_slug("second".to_string()).is_some());
+        assert_eq!(
+            db.get_by_slug("first".to_string()).unwrap().markdown.title,
+            "Edited"
+        );
+    }
+
+    async fn page_body(state: AppState, uri: &str) -> (StatusCode, String) {
+        let app = Router::new()
+            .route(
+                "/",
+                get(|| async { Html("<html><body>page</body></html>") }),
+            )
+            .route("/plain", get(|| async { "</body>" }))
+            .merge(live_reload::router())
+            .layer(middleware::from_fn_with_state(
+                state.clone(),
+                live_reload::inject_live_reload_script,
+            ))
+            .with_state(state);
+        let response = app
+            .oneshot(Request::get(uri).body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        let status = response.status();
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (status, String::from_utf8_lossy(&body).into_owned())
+    }
+
+    #[tokio::test]
+    async fn test_live_reload_script_only_when_enabled() {
+        let views_dir = tempfile::tempdir().unwrap();
+        let views = ViewCounterStore::load(views_dir.path().join("views.tsv")).unwrap();
+        let disabled = AppState::new(InMemDatabase::new(), views);
+        let (sender, _) = broadcast::channel(4);
+        let enabled = disabled.clone().with_live_reload(sender);
+
+        let (_, body) = page_body(disabled.clone(), "/").await;
+        assert!(!body.contains(live_reload::LIVE_RELOAD_PATH));
+        let (status, _) = page_body(disabled, live_reload::LIVE_RELOAD_PATH).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+
+        let (_, body) = page_body(enabled.clone(), "/").await;
+        assert!(body.contains(live_reload::LIVE_RELOAD_PATH));
+        assert!(body.ends_with("</body></html>"));
+        let (_, body) = page_body(enabled, "/plain").await;
+        assert_eq!(body, "</body>");
+    }
+}